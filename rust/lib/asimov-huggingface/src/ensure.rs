@@ -17,6 +17,30 @@ pub fn file_exists(repo: &str, filename: &str) -> Option<PathBuf> {
     cache.repo(repo_id).get(filename)
 }
 
+/// Returns the directory of the local Hugging Face cache.
+pub fn cache_dir() -> PathBuf {
+    Cache::default().path().clone()
+}
+
+/// Removes the specified file from the local Hugging Face cache, such as when
+/// it turns out to be corrupted, so that it is downloaded again.
+///
+/// Arguments:
+/// - `repo`: repository id, e.g. `"facebook/dinov2-base"`.
+/// - `filename`: file within the repository, e.g. `"pytorch_model.bin"`.
+pub fn remove_file(repo: &str, filename: &str) -> std::io::Result<()> {
+    let Some(path) = file_exists(repo, filename) else {
+        return Ok(());
+    };
+    // cached files are links to blobs shared between snapshots
+    if let Ok(blob) = std::fs::canonicalize(&path)
+        && blob != path
+    {
+        std::fs::remove_file(blob)?;
+    }
+    std::fs::remove_file(path)
+}
+
 /// Ensures that the specified file from a Hugging Face repository is available locally.
 ///
 /// Behavior:
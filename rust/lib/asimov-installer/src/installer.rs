@@ -1,30 +1,52 @@
 // This is free and unencumbered software released into the public domain.
 
-use alloc::format;
-use asimov_module::{InstalledModuleManifest, ModuleManifest, ModuleName, tracing};
+use alloc::{format, vec, vec::Vec};
+use asimov_module::{
+    Index, InstallSource, InstalledModel, InstalledModuleManifest, ModuleManifest, ModuleName,
+    TrustedKeys, tracing,
+};
 use std::{
     boxed::Box,
+    collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
-    string::String,
+    string::{String, ToString as _},
+    sync::Arc,
 };
 
 pub mod error;
 use error::*;
 
-use asimov_registry::Registry;
+use asimov_registry::{LockMode, Registry};
 
 mod github;
+pub mod lock;
+pub use lock::{LOCK_FILE_NAME, LockedModule, Lockfile};
+mod model;
+pub use model::SystemResources;
+pub mod plan;
+pub use plan::{InstallPlan, PlannedModule};
 mod platform;
+pub use platform::{PlatformInfo, detect_platform};
+pub mod progress;
+pub use progress::{Phase, Progress, ProgressReporter};
+mod signature;
+pub use signature::verify_signature;
+pub mod source;
+pub use source::{ReleaseSource, ReleaseSources, SourceConfig, SourcesConfig};
+pub mod update;
+pub use update::{AvailableUpdate, UpdateCheck, UpgradeReport};
 
 #[derive(Clone, Debug)]
 pub struct Installer {
     client: reqwest::Client,
     registry: Registry,
+    sources: ReleaseSources,
+    index: Option<Arc<Index>>,
+    progress: ProgressReporter,
 }
 
 impl Default for Installer {
     fn default() -> Self {
-        // TODO: retry support
         let client = reqwest::Client::builder()
             .user_agent("asimov-module-installer")
             .connect_timeout(std::time::Duration::from_secs(10))
@@ -41,20 +63,78 @@ impl Default for Installer {
 pub struct InstallOptions {
     pub version: Option<String>,
     pub model_size: Option<String>,
+    /// The lockfile to install by, failing if the module or any of its
+    /// dependencies would deviate from it, as with `install --locked`.
+    pub locked: Option<Arc<Lockfile>>,
+    /// Whether to reject release assets without a published checksum,
+    /// rather than installing them unverified.
+    #[builder(default)]
+    pub require_checksums: bool,
+    /// The number of modules to install at the same time, by default
+    /// [`plan::DEFAULT_JOBS`].
+    pub jobs: Option<usize>,
+    /// Whether the module is installed only as a dependency of another one,
+    /// and so may be removed by [`Registry::gc`] once no longer required.
+    #[builder(default)]
+    pub dependency: bool,
 }
 
 #[derive(Clone, Debug)]
 struct Preinstalled {
     module_name: ModuleName,
     manifest: ModuleManifest,
-    version: String,
+    version: Option<String>,
     readme: Option<String>,
     extract_dir: PathBuf,
+    installed_models: BTreeMap<String, InstalledModel>,
+    source: InstallSource,
+    sha256: Option<String>,
+    dependency: bool,
 }
 
 impl Installer {
+    /// Creates an installer with the release sources configured in
+    /// [`SourcesConfig::config_path`], or else GitHub.
     pub fn new(client: reqwest::Client, registry: Registry) -> Self {
-        Self { client, registry }
+        let sources = ReleaseSources::from_config().unwrap_or_else(|err| {
+            tracing::warn!(%err, "ignoring the release sources configuration");
+            ReleaseSources::default()
+        });
+        Self {
+            client,
+            registry,
+            sources,
+            index: None,
+            progress: ProgressReporter::default(),
+        }
+    }
+
+    /// Replaces the release sources to install modules from.
+    pub fn with_sources(mut self, sources: ReleaseSources) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    pub fn sources(&self) -> &ReleaseSources {
+        &self.sources
+    }
+
+    /// Sets the module index whose pinned publisher keys must have signed
+    /// the release assets of their modules, which are otherwise rejected.
+    pub fn with_index(mut self, index: Arc<Index>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Sets a callback receiving the progress of installing modules, such as
+    /// to render progress bars.
+    pub fn with_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = ProgressReporter::new("", Some(Arc::new(callback)));
+        self
+    }
+
+    fn publisher_keys(&self, module_name: &str) -> Option<&TrustedKeys> {
+        self.index.as_ref()?.publisher_keys(module_name)
     }
 
     /// ```rust,no_run
@@ -67,6 +147,191 @@ impl Installer {
         &self,
         module_name: &ModuleName,
         options: &InstallOptions,
+    ) -> Result<(), InstallError> {
+        let plan = self.plan(module_name, options).await?;
+        self.install_plan(&plan, options).await
+    }
+
+    /// Resolves the modules to install for a module and its dependencies,
+    /// without installing anything, such as for a dry run. Dependencies that
+    /// are already installed are kept, along with their own dependencies.
+    ///
+    /// ```rust,no_run
+    /// # use asimov_installer::{Installer, InstallOptions};
+    /// # async fn example() {
+    /// let i = Installer::default();
+    /// let module = "foobar".parse().unwrap();
+    /// let plan = i.plan(&module, &InstallOptions::default()).await.unwrap();
+    /// print!("{plan}");
+    /// # }
+    /// ```
+    pub async fn plan(
+        &self,
+        module_name: &ModuleName,
+        options: &InstallOptions,
+    ) -> Result<InstallPlan, plan::PlanError> {
+        self.plan_with(module_name, options, None).await
+    }
+
+    /// Plans as [`Self::plan`], but with the requirements of the module
+    /// itself taken from its manifest, if given, rather than fetched.
+    async fn plan_with(
+        &self,
+        module_name: &ModuleName,
+        options: &InstallOptions,
+        manifest: Option<&ModuleManifest>,
+    ) -> Result<InstallPlan, plan::PlanError> {
+        use plan::PlanError;
+
+        let mut graph = BTreeMap::new();
+        let mut satisfied = BTreeSet::new();
+        let mut queue = vec![(module_name.clone(), options.version.clone())];
+
+        while let Some((name, version)) = queue.pop() {
+            if graph.contains_key(&name) || satisfied.contains(&name) {
+                continue;
+            }
+            if &name != module_name
+                && self
+                    .registry
+                    .is_module_installed(&name)
+                    .await
+                    .unwrap_or(false)
+            {
+                satisfied.insert(name);
+                continue;
+            }
+
+            let (version, requires) = match manifest {
+                Some(manifest) if &name == module_name => (
+                    version.unwrap_or_default(),
+                    manifest.requires.modules.clone(),
+                ),
+                _ => {
+                    let source = self.sources.for_module(&name);
+                    let version = match (version, &options.locked) {
+                        (Some(version), _) => version,
+                        (None, Some(lockfile)) => lockfile.module(&name)?.version.clone(),
+                        (None, None) => source
+                            .fetch_latest_release(&self.client, &name)
+                            .await
+                            .map_err(|e| PlanError::FetchRelease(name.to_string(), e))?,
+                    };
+                    let manifest = source
+                        .fetch_module_manifest(&self.client, &name, &version)
+                        .await
+                        .map_err(|e| PlanError::FetchManifest(name.to_string(), e))?;
+                    (version, manifest.requires.modules)
+                },
+            };
+
+            let requires = requires
+                .into_iter()
+                .map(ModuleName::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            queue.extend(requires.iter().map(|required| (required.clone(), None)));
+            graph.insert(name, (version, requires));
+        }
+
+        InstallPlan::new(module_name, graph, satisfied)
+    }
+
+    /// Installs the modules of a plan, each once the modules it requires are
+    /// installed, and up to [`InstallOptions::jobs`] at the same time. The
+    /// last module of the plan is installed with the given options, and the
+    /// others with the options passed on to dependencies.
+    pub async fn install_plan(
+        &self,
+        plan: &InstallPlan,
+        options: &InstallOptions,
+    ) -> Result<(), InstallError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
+        let root = plan.modules.last().map(|module| &module.name);
+        self.install_planned(plan.modules.iter().collect(), root, options)
+            .await
+    }
+
+    /// Installs the missing modules that a module requires, as planned with
+    /// its manifest, before the module itself is installed.
+    async fn install_requirements(
+        &self,
+        module_name: &ModuleName,
+        manifest: &ModuleManifest,
+        options: &InstallOptions,
+    ) -> Result<(), InstallError> {
+        let plan = self.plan_with(module_name, options, Some(manifest)).await?;
+        let requires = plan
+            .modules
+            .iter()
+            .filter(|module| &module.name != module_name)
+            .collect();
+        self.install_planned(requires, None, options).await
+    }
+
+    /// Installs planned modules, each once the modules it requires are
+    /// installed. The root module is installed with the given options, and
+    /// the others with the options passed on to dependencies.
+    async fn install_planned(
+        &self,
+        mut pending: Vec<&PlannedModule>,
+        root: Option<&ModuleName>,
+        options: &InstallOptions,
+    ) -> Result<(), InstallError> {
+        use futures::stream::{FuturesUnordered, StreamExt as _};
+
+        let jobs = options.jobs.unwrap_or(plan::DEFAULT_JOBS).max(1);
+        let mut installed = BTreeSet::new();
+        let mut running = FuturesUnordered::new();
+        let mut failed = None;
+
+        loop {
+            // after a failure, the running jobs are finished rather than
+            // dropped partway through, but no more are started
+            while failed.is_none()
+                && running.len() < jobs
+                && let Some(index) = pending
+                    .iter()
+                    .position(|module| module.requires.iter().all(|name| installed.contains(name)))
+            {
+                let module = pending.remove(index);
+                let options = InstallOptions {
+                    version: Some(module.version.clone()),
+                    ..if Some(&module.name) == root {
+                        options.clone()
+                    } else {
+                        dependency_options(options)
+                    }
+                };
+                running.push(async move {
+                    let result = self.install_single(&module.name, &options).await;
+                    (module, result)
+                });
+            }
+
+            let Some((module, result)) = running.next().await else {
+                break;
+            };
+            let err = match result {
+                Ok(()) => {
+                    installed.insert(&module.name);
+                    continue;
+                },
+                Err(err) if Some(&module.name) == root => err,
+                Err(err) => {
+                    PreinstallError::Dependency(module.name.to_string(), Box::new(err)).into()
+                },
+            };
+            tracing::debug!(?err, module_name = %module.name, "failed to install module");
+            failed.get_or_insert(err);
+        }
+
+        failed.map_or(Ok(()), Err)
+    }
+
+    async fn install_single(
+        &self,
+        module_name: &ModuleName,
+        options: &InstallOptions,
     ) -> Result<(), InstallError> {
         let work_dir = self.work_dir(module_name).await?;
 
@@ -79,13 +344,165 @@ impl Installer {
         Ok(())
     }
 
+    /// Installs a module from a local release archive, such as
+    /// `asimov-foo-module-linux-x86-gnu.tar.gz`, without fetching anything
+    /// but its missing dependencies and models, returning the name of the
+    /// module.
+    ///
+    /// The archive must contain the module manifest, as `.asimov/module.yaml`
+    /// or `module.yaml`, next to the programs. A checksum file next to the
+    /// archive, such as `asimov-foo-module-linux-x86-gnu.tar.gz.sha256`, is
+    /// verified if present.
+    ///
+    /// ```rust,no_run
+    /// # use asimov_installer::{Installer, InstallOptions};
+    /// let i = Installer::default();
+    /// i.install_from_archive("asimov-foo-module-linux-x86-gnu.tar.gz", &InstallOptions::default());
+    /// ```
+    pub async fn install_from_archive(
+        &self,
+        path: impl AsRef<Path>,
+        options: &InstallOptions,
+    ) -> Result<ModuleName, InstallError> {
+        let path = std::path::absolute(path.as_ref()).map_err(PreinstallError::Extract)?;
+
+        let checksum_path = path.with_file_name(format!(
+            "{}.sha256",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        match tokio::fs::read_to_string(&checksum_path).await {
+            Ok(checksum) => github::verify_checksum(&path, &checksum)
+                .await
+                .map_err(PreinstallError::from)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if options.require_checksums {
+                    Err(PreinstallError::NoChecksum(path.display().to_string()))?;
+                }
+                tracing::debug!(?path, "no checksum file next to the archive");
+            },
+            Err(err) => Err(PreinstallError::from(VerifyChecksumError::Io(err)))?,
+        }
+
+        let work_dir = self.work_dir("archive").await?;
+        let extract_dir = work_dir.path().join("extract");
+        tokio::fs::create_dir(&extract_dir)
+            .await
+            .map_err(PreinstallError::CreateExtractDir)?;
+        github::extract_files(&path, &extract_dir)
+            .await
+            .map_err(PreinstallError::Extract)?;
+
+        let source = InstallSource::Archive {
+            path: path.display().to_string(),
+        };
+        self.install_extracted(&extract_dir, options, source, Some(&path), work_dir.path())
+            .await
+    }
+
+    /// Installs a module from a local directory with the unpacked contents
+    /// of a release archive, which are copied rather than moved, returning
+    /// the name of the module. See [`Self::install_from_archive`].
+    pub async fn install_from_dir(
+        &self,
+        path: impl AsRef<Path>,
+        options: &InstallOptions,
+    ) -> Result<ModuleName, InstallError> {
+        let path = std::path::absolute(path.as_ref()).map_err(PreinstallError::Extract)?;
+
+        let work_dir = self.work_dir("dir").await?;
+        let extract_dir = work_dir.path().join("extract");
+        let (src, dst) = (path.clone(), extract_dir.clone());
+        tokio::task::spawn_blocking(move || copy_dir(&src, &dst))
+            .await
+            .map_err(std::io::Error::other)
+            .flatten()
+            .map_err(PreinstallError::Extract)?;
+
+        let source = InstallSource::Directory {
+            path: path.display().to_string(),
+        };
+        self.install_extracted(&extract_dir, options, source, None, work_dir.path())
+            .await
+    }
+
+    async fn install_extracted(
+        &self,
+        extract_dir: &Path,
+        options: &InstallOptions,
+        source: InstallSource,
+        archive: Option<&Path>,
+        work_dir: &Path,
+    ) -> Result<ModuleName, InstallError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
+        let (extract_dir, manifest) = find_manifest(extract_dir).await?;
+        let module_name = ModuleName::try_from(manifest.name.clone())
+            .map_err(PreinstallError::InvalidModuleName)?;
+
+        // a directory can't be signed, only the archive it was unpacked from
+        if let Some(keys) = self.publisher_keys(&module_name) {
+            let Some(archive) = archive else {
+                Err(PreinstallError::from(VerifySignatureError::Unsigned))?
+            };
+            let signature_path = archive.with_file_name(format!(
+                "{}.sig",
+                archive.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            let signature = match tokio::fs::read_to_string(&signature_path).await {
+                Ok(signature) => Some(signature),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => Err(PreinstallError::from(VerifySignatureError::Io(err)))?,
+            };
+            signature::verify_file(archive, signature.as_deref(), keys)
+                .await
+                .map_err(PreinstallError::from)?;
+        }
+
+        self.install_requirements(&module_name, &manifest, options)
+            .await?;
+        let installed_models = install_models(&module_name, &manifest, options, None).await?;
+        let readme = find_readme(&extract_dir).await;
+
+        let preinstalled = Preinstalled {
+            module_name: module_name.clone(),
+            manifest,
+            version: options.version.clone(),
+            readme,
+            extract_dir,
+            installed_models,
+            source,
+            sha256: None,
+            dependency: options.dependency,
+        };
+        self.finish_install(preinstalled, work_dir).await?;
+
+        Ok(module_name)
+    }
+
     pub async fn fetch_latest_release(
         &self,
         module_name: &ModuleName,
     ) -> Result<String, FetchError> {
-        github::fetch_latest_release(&self.client, module_name).await
+        self.sources
+            .for_module(module_name)
+            .fetch_latest_release(&self.client, module_name)
+            .await
+    }
+
+    /// Returns the released versions of a module, or just the latest one
+    /// where its source can't list them.
+    pub async fn fetch_releases(
+        &self,
+        module_name: &ModuleName,
+    ) -> Result<Vec<String>, FetchError> {
+        self.sources
+            .for_module(module_name)
+            .fetch_releases(&self.client, module_name)
+            .await
     }
 
+    /// Upgrades a module to the latest release, or the version of
+    /// `options`, failing if the module is pinned to other versions.
+    ///
     /// ```rust,no_run
     /// # use asimov_installer::{Installer, InstallOptions};
     /// let i = Installer::default();
@@ -97,11 +514,17 @@ impl Installer {
         module_name: &ModuleName,
         options: &InstallOptions,
     ) -> Result<(), UpgradeError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
         let version = if let Some(ref want_version) = options.version {
             want_version.clone()
         } else {
             self.fetch_latest_release(module_name).await?
         };
+        if let Some(pin) = self.registry.module_pin(module_name).await?
+            && !pin.allows(&version)
+        {
+            return Err(UpgradeError::Pinned { version, pin });
+        }
 
         let current_version = self.registry.module_version(module_name).await?;
         match current_version {
@@ -112,26 +535,73 @@ impl Installer {
 
         let work_dir = self.work_dir(module_name).await?;
 
-        // check if currently enabled, have to re-enable after upgrade
+        // the new version replaces the old one only once fully assembled,
+        // with the old one kept for `rollback_module`
         let was_enabled = self.registry.is_module_enabled(module_name).await?;
+        let result = async {
+            let mut preinstalled = self
+                .preinstall(module_name, options, work_dir.path())
+                .await?;
+            // modules newly required by this version are installed first
+            self.install_requirements(module_name, &preinstalled.manifest, options)
+                .await
+                .map_err(|e| UpgradeError::Requires(Box::new(e)))?;
+            // an upgrade doesn't change why the module was installed
+            preinstalled.dependency = self
+                .registry
+                .read_manifest(module_name)
+                .await
+                .is_ok_and(|manifest| manifest.dependency);
+            self.progress
+                .for_module(module_name.as_str())
+                .report(Phase::Install, 0, None);
+            let (_, module_dir) = assemble(preinstalled, work_dir.path()).await?;
+            self.registry
+                .replace_module(module_name, &module_dir)
+                .await?;
+            Ok(())
+        }
+        .await;
 
-        let preinstalled = self
-            .preinstall(module_name, options, work_dir.path())
-            .await?;
-
-        // now ok to uninstall old version
-        self.uninstall_module(module_name).await?;
-
-        self.finish_install(preinstalled, work_dir.path()).await?;
+        if result.is_err() {
+            self.restore_enabled(module_name, was_enabled).await;
+        }
+        result
+    }
 
-        if was_enabled {
-            self.registry.enable_module(module_name).await?;
+    /// Goes back to the version of a module that was installed before its
+    /// last upgrade, keeping the enabled state.
+    pub async fn rollback_module(
+        &self,
+        module_name: &ModuleName,
+    ) -> Result<(), asimov_registry::error::RollbackError> {
+        let was_enabled = self
+            .registry
+            .is_module_enabled(module_name)
+            .await
+            .unwrap_or(false);
+        let result = self.registry.rollback_module(module_name).await;
+        if result.is_err() {
+            self.restore_enabled(module_name, was_enabled).await;
         }
+        result
+    }
 
-        Ok(())
+    async fn restore_enabled(&self, module_name: &ModuleName, was_enabled: bool) {
+        if was_enabled
+            && !self
+                .registry
+                .is_module_enabled(module_name)
+                .await
+                .unwrap_or(true)
+            && let Err(err) = self.registry.enable_module(module_name).await
+        {
+            tracing::warn!(?err, %module_name, "failed to re-enable module");
+        }
     }
 
     pub async fn uninstall_module(&self, module_name: &ModuleName) -> Result<(), UninstallError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
         let manifest = self.registry.read_manifest(module_name).await?;
 
         self.registry.disable_module(module_name).await?;
@@ -144,11 +614,70 @@ impl Installer {
         }
 
         self.registry.remove_module(module_name).await?;
+        self.registry.unpin_module(module_name).await?;
+
+        Ok(())
+    }
+
+    /// Records the installed modules in a lockfile, to reproduce them with
+    /// [`Self::install_locked`].
+    pub async fn lockfile(&self) -> Result<Lockfile, lock::LockError> {
+        let mut lockfile = Lockfile::default();
+        for manifest in self.registry.installed_modules().await? {
+            let module_name = ModuleName::try_from(manifest.manifest.name.clone())?;
+            let enabled = self.registry.is_module_enabled(&module_name).await?;
+            let locked = LockedModule::from_installed(&manifest, enabled)?;
+            lockfile.modules.insert(module_name.into_string(), locked);
+        }
+        Ok(lockfile)
+    }
+
+    /// Installs, upgrades, enables and disables modules to match a lockfile,
+    /// failing if anything would deviate from it. Installed modules that
+    /// aren't in the lockfile are left alone.
+    pub async fn install_locked(&self, lockfile: Lockfile) -> Result<(), InstallLockedError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
+        let lockfile = Arc::new(lockfile);
+
+        for (module_name, locked) in &lockfile.modules {
+            let module_name = ModuleName::try_from(module_name.clone())?;
+            let options = InstallOptions::builder()
+                .version(locked.version.clone())
+                .locked(lockfile.clone())
+                .build();
+
+            if !self.registry.is_module_installed(&module_name).await? {
+                self.install_module(&module_name, &options)
+                    .await
+                    .map_err(|e| {
+                        InstallLockedError::Install(module_name.to_string(), Box::new(e))
+                    })?;
+            } else {
+                let installed = self.registry.read_manifest(&module_name).await?;
+                if installed.version.as_deref() == Some(&locked.version) {
+                    let sha256 = installed.sha256.as_deref().unwrap_or_default();
+                    lock::check(&module_name, "checksum", &locked.sha256, sha256)?;
+                } else {
+                    self.upgrade_module(&module_name, &options)
+                        .await
+                        .map_err(|e| {
+                            InstallLockedError::Upgrade(module_name.to_string(), Box::new(e))
+                        })?;
+                }
+            }
+
+            let enabled = self.registry.is_module_enabled(&module_name).await?;
+            if locked.enabled && !enabled {
+                self.registry.enable_module(&module_name).await?;
+            } else if !locked.enabled && enabled {
+                self.registry.disable_module(&module_name).await?;
+            }
+        }
 
         Ok(())
     }
 
-    async fn work_dir(&self, module_name: &ModuleName) -> Result<tempfile::TempDir, WorkDirError> {
+    async fn work_dir(&self, name: &str) -> Result<tempfile::TempDir, WorkDirError> {
         self.registry
             .create_file_tree()
             .await
@@ -157,11 +686,25 @@ impl Installer {
         let install_dir = self.registry.install_dir();
 
         tempfile::Builder::new()
-            .prefix(&format!(".{module_name}-"))
+            .prefix(&format!(".{name}-"))
             .tempdir_in(install_dir.parent().unwrap_or(install_dir))
             .map_err(WorkDirError::CreateDir)
     }
 
+    /// The directory to download the release asset of a module into, which
+    /// outlives the work directory, so that an interrupted download can be
+    /// resumed by the next install.
+    async fn download_dir(&self, module_name: &str, version: &str) -> std::io::Result<PathBuf> {
+        let install_dir = self.registry.install_dir();
+        let dir = install_dir
+            .parent()
+            .unwrap_or(install_dir)
+            .join(".downloads")
+            .join(format!("{module_name}-{version}"));
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(dir)
+    }
+
     async fn preinstall(
         &self,
         module_name: &ModuleName,
@@ -169,159 +712,307 @@ impl Installer {
         temp_dir: &Path,
     ) -> Result<Preinstalled, PreinstallError> {
         let platform = platform::detect_platform();
+        let source = self.sources.for_module(module_name);
+        let locked = match &options.locked {
+            Some(lockfile) => Some(lockfile.module(module_name)?),
+            None => None,
+        };
 
         let version = if let Some(ref want_version) = options.version {
             want_version.clone()
+        } else if let Some(locked) = locked {
+            locked.version.clone()
         } else {
-            github::fetch_latest_release(&self.client, module_name)
+            source
+                .fetch_latest_release(&self.client, module_name)
                 .await
                 .map_err(PreinstallError::FetchRelease)?
         };
+        if let Some(locked) = locked {
+            lock::check(module_name, "version", &locked.version, &version)?;
+        }
 
-        let manifest = github::fetch_module_manifest(&self.client, module_name, &version)
+        let manifest = source
+            .fetch_module_manifest(&self.client, module_name, &version)
             .await
             .map_err(PreinstallError::FetchManifest)?;
 
-        let (asset_url, download_path) = github::download_matching_asset(
-            &self.client,
-            module_name,
-            &version,
-            &platform,
-            temp_dir,
-        )
-        .await?;
-
-        // pass the model_size option to dependencies
-        let options = InstallOptions::builder()
-            .maybe_model_size(options.model_size.clone())
-            .build();
-        let subdeps = &manifest.requires.modules;
-        for module in subdeps {
-            let module = ModuleName::try_from(module.clone())
-                .map_err(PreinstallError::InvalidDependencyName)?;
-
-            if self
-                .registry
-                .is_module_installed(&module)
-                .await
-                .unwrap_or(false)
-            {
-                continue;
-            }
-            Box::pin(self.install_module(&module, &options))
-                .await
-                .map_err(|e| PreinstallError::Dependency(module.into_string(), Box::new(e)))?;
-        }
+        let progress = self.progress.for_module(module_name.as_str());
+        let download_dir = self
+            .download_dir(module_name, &version)
+            .await
+            .map_err(PreinstallError::CreateDownloadDir)?;
+        let (asset_url, download_path) = source
+            .download_asset(
+                &self.client,
+                module_name,
+                &version,
+                &platform,
+                &download_dir,
+                &progress,
+            )
+            .await?;
 
-        match github::fetch_checksum(&self.client, &asset_url).await {
-            Ok(None) => {},
-            Ok(Some(checksum)) => {
-                github::verify_checksum(&download_path, &checksum).await?;
+        // a failed download is kept to resume, but not a bad one
+        progress.report(Phase::Verify, 0, None);
+        let verified = self
+            .verify_asset(
+                source,
+                module_name,
+                &asset_url,
+                &download_path,
+                locked,
+                options,
+            )
+            .await;
+        let extracted = match verified {
+            Ok(sha256) => {
+                let extract_dir = temp_dir.join("extract");
+                progress.report(Phase::Extract, 0, None);
+                let extracted = async {
+                    tokio::fs::create_dir(&extract_dir)
+                        .await
+                        .map_err(PreinstallError::CreateExtractDir)?;
+                    github::extract_files(&download_path, &extract_dir)
+                        .await
+                        .map_err(PreinstallError::Extract)
+                }
+                .await;
+                extracted.map(|()| (sha256, extract_dir))
             },
-            Err(err) => Err(err)?,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = tokio::fs::remove_dir_all(&download_dir).await {
+            tracing::debug!(?err, ?download_dir, "failed to remove download");
         }
+        let (sha256, extract_dir) = extracted?;
 
-        let extract_dir = temp_dir.join("extract");
-        tokio::fs::create_dir(&extract_dir)
-            .await
-            .map_err(PreinstallError::CreateExtractDir)?;
-
-        github::extract_files(&download_path, &extract_dir)
-            .await
-            .map_err(PreinstallError::Extract)?;
-
-        for (name, model) in &manifest.requires.models {
-            let Some(repo) = name.strip_prefix("hf:") else {
-                tracing::debug!(
-                    ?name,
-                    "unexpected format for required model, only `hf:<user>/<repo>` is supported"
-                );
-                continue;
-            };
-
-            use asimov_module::RequiredModel;
-            let filename = match (model, &options.model_size) {
-                (RequiredModel::Url(url), None | Some(_)) => url,
-                (RequiredModel::Choices(choices), None) => {
-                    if choices
-                        .iter()
-                        .any(|(_, url)| asimov_huggingface::file_exists(repo, url).is_some())
-                    {
-                        // user didn't specify a model size/version to install
-                        // and one of the choices is already installed
-                        continue;
-                    }
-                    let Some((_, model)) = choices.first() else {
-                        // malformed manifest?
-                        tracing::warn!(
-                            ?module_name,
-                            "manifest defines required models with no choices"
-                        );
-                        continue;
-                    };
-                    model
-                },
-                (RequiredModel::Choices(choices), Some(want_model)) => {
-                    &choices
-                        .iter()
-                        .find(|(name, _)| *name == *want_model)
-                        .ok_or_else(|| PreinstallError::NoSuchModel(want_model.clone()))?
-                        .1
-                },
-            };
-
-            asimov_huggingface::ensure_file(repo, filename)?;
-        }
+        let installed_models = install_models(module_name, &manifest, options, locked).await?;
 
         let readme = match find_readme(&extract_dir).await {
             Some(readme) => Some(readme),
-            None => github::fetch_readme(&self.client, module_name, &version).await,
+            None => {
+                source
+                    .fetch_readme(&self.client, module_name, &version)
+                    .await
+            },
         };
 
         Ok(Preinstalled {
             module_name: ModuleName::try_from(manifest.name.clone())
                 .map_err(PreinstallError::InvalidModuleName)?,
             manifest,
-            version,
+            version: Some(version),
             readme,
             extract_dir,
+            installed_models,
+            source: InstallSource::Release { url: asset_url },
+            sha256: Some(sha256),
+            dependency: options.dependency,
         })
     }
 
+    /// Verifies a downloaded release asset against the lockfile, if any, its
+    /// published checksum, and the pinned keys of its publisher, if any,
+    /// returning its checksum.
+    async fn verify_asset(
+        &self,
+        source: &dyn ReleaseSource,
+        module_name: &ModuleName,
+        asset_url: &str,
+        download_path: &Path,
+        locked: Option<&LockedModule>,
+        options: &InstallOptions,
+    ) -> Result<String, PreinstallError> {
+        let sha256 = github::sha256_file(download_path)
+            .await
+            .map_err(VerifyChecksumError::Io)?;
+        if let Some(locked) = locked {
+            lock::check(module_name, "asset URL", &locked.url, asset_url)?;
+            lock::check(module_name, "checksum", &locked.sha256, &sha256)?;
+        }
+
+        match source.fetch_checksum(&self.client, asset_url).await? {
+            Some(checksum) => github::verify_checksum(download_path, &checksum).await?,
+            None if options.require_checksums => {
+                return Err(PreinstallError::NoChecksum(asset_url.into()));
+            },
+            None => tracing::debug!(asset_url, "no published checksum"),
+        }
+
+        if let Some(keys) = self.publisher_keys(module_name) {
+            let signature = source
+                .fetch_signature(&self.client, asset_url)
+                .await
+                .map_err(PreinstallError::FetchSignature)?;
+            signature::verify_file(download_path, signature.as_deref(), keys).await?;
+        }
+
+        Ok(sha256)
+    }
+
     async fn finish_install(
         &self,
         preinstalled: Preinstalled,
         work_dir: &Path,
     ) -> Result<(), FinishInstallError> {
-        let Preinstalled {
-            module_name,
-            manifest,
+        self.progress
+            .for_module(preinstalled.module_name.as_str())
+            .report(Phase::Install, 0, None);
+        let (module_name, module_dir) = assemble(preinstalled, work_dir).await?;
+
+        self.registry.add_module(&module_name, &module_dir).await?;
+
+        Ok(())
+    }
+}
+
+/// The options to pass on to the dependencies of a module.
+fn dependency_options(options: &InstallOptions) -> InstallOptions {
+    InstallOptions::builder()
+        .maybe_model_size(options.model_size.clone())
+        .maybe_locked(options.locked.clone())
+        .require_checksums(options.require_checksums)
+        .maybe_jobs(options.jobs)
+        .dependency(true)
+        .build()
+}
+
+/// Assembles a preinstalled module into the `module` directory of the work
+/// directory, ready to be added to the registry.
+async fn assemble(
+    preinstalled: Preinstalled,
+    work_dir: &Path,
+) -> Result<(ModuleName, PathBuf), FinishInstallError> {
+    let Preinstalled {
+        module_name,
+        manifest,
+        version,
+        readme,
+        extract_dir,
+        installed_models,
+        source,
+        sha256,
+        dependency,
+    } = preinstalled;
+
+    let module_dir = work_dir.join("module");
+
+    tokio::fs::create_dir(&module_dir)
+        .await
+        .map_err(|e| FinishInstallError::CreateDir(module_dir.clone(), e))?;
+
+    assemble_module(
+        &module_dir,
+        InstalledModuleManifest {
             version,
-            readme,
-            extract_dir,
-        } = preinstalled;
+            installed_models,
+            source: Some(source),
+            sha256,
+            dependency,
+            manifest,
+        },
+        readme,
+        &extract_dir,
+    )
+    .await?;
 
-        let module_dir = work_dir.join("module");
+    Ok((module_name, module_dir))
+}
 
-        tokio::fs::create_dir(&module_dir)
-            .await
-            .map_err(|e| FinishInstallError::CreateDir(module_dir.clone(), e))?;
+/// Installs the `hf:` models required by a module, choosing their variants
+/// by `options.model_size` or else by the system resources, and verifying
+/// the files whose hashes the manifest declares.
+async fn install_models(
+    module_name: &ModuleName,
+    manifest: &ModuleManifest,
+    options: &InstallOptions,
+    locked: Option<&LockedModule>,
+) -> Result<BTreeMap<String, InstalledModel>, PreinstallError> {
+    let resources = SystemResources::detect(&asimov_huggingface::cache_dir());
+    let mut installed_models = BTreeMap::new();
+
+    for (name, model) in &manifest.requires.models {
+        let Some(repo) = name.strip_prefix("hf:") else {
+            tracing::warn!(
+                ?name,
+                "unexpected format for required model, only `hf:<user>/<repo>` is supported"
+            );
+            continue;
+        };
 
-        assemble_module(
-            &module_dir,
-            InstalledModuleManifest {
-                version: Some(version),
-                manifest,
-            },
-            readme,
-            &extract_dir,
-        )
-        .await?;
+        let is_installed = |file: &asimov_module::ModelFile| {
+            asimov_huggingface::file_exists(repo, &file.file).is_some()
+        };
+        let locked_model = locked
+            .map(|locked| {
+                locked
+                    .models
+                    .get(name)
+                    .ok_or_else(|| lock::LockMismatch::ModelNotLocked {
+                        module: module_name.to_string(),
+                        model: name.clone(),
+                    })
+            })
+            .transpose()?;
+        let wanted = match locked_model {
+            Some(locked_model) => locked_model.variant.as_deref(),
+            None => options.model_size.as_deref(),
+        };
+        let selected = model::select_variant(model, wanted, is_installed, &resources)
+            .map_err(PreinstallError::NoSuchModel)?;
+        let Some((variant, file)) = selected else {
+            // malformed manifest?
+            tracing::warn!(
+                ?module_name,
+                "manifest defines required models with no choices"
+            );
+            continue;
+        };
+        tracing::debug!(
+            ?name,
+            ?variant,
+            file = file.file,
+            "installing required model"
+        );
+
+        let installed_model = InstalledModel {
+            variant,
+            file: file.file.clone(),
+            sha256: file.sha256.clone(),
+        };
+        if let Some(locked_model) = locked_model {
+            let field = "installed model";
+            lock::check(
+                module_name,
+                field,
+                &locked_model.file,
+                &installed_model.file,
+            )?;
+            let (expected, actual) = (&locked_model.sha256, &installed_model.sha256);
+            lock::check(
+                module_name,
+                field,
+                expected.as_deref().unwrap_or_default(),
+                actual.as_deref().unwrap_or_default(),
+            )?;
+        }
 
-        self.registry.add_module(&module_name, &module_dir).await?;
+        let path = asimov_huggingface::ensure_file(repo, &file.file)?;
+        if let Some(sha256) = &file.sha256
+            && let Err(err) = github::verify_checksum(&path, sha256).await
+        {
+            // don't leave a corrupted file in the cache to be reused
+            if let Err(err) = asimov_huggingface::remove_file(repo, &file.file) {
+                tracing::debug!(?err, ?path, "failed to remove model file");
+            }
+            return Err(PreinstallError::VerifyModel(name.clone(), err));
+        }
 
-        Ok(())
+        installed_models.insert(name.clone(), installed_model);
     }
+
+    Ok(installed_models)
 }
 
 async fn assemble_module(
@@ -392,6 +1083,64 @@ async fn assemble_module(
         .map_err(|e| FinishInstallError::WriteFile(manifest_path, e))
 }
 
+/// The paths of the module manifest in a release archive.
+const MANIFEST_PATHS: [&str; 2] = [".asimov/module.yaml", "module.yaml"];
+
+/// Finds the module manifest in an unpacked release archive, either at the
+/// top or within its only directory, returning the directory it's in.
+async fn find_manifest(extract_dir: &Path) -> Result<(PathBuf, ModuleManifest), PreinstallError> {
+    let mut dirs = std::vec![extract_dir.to_path_buf()];
+
+    let mut entries = tokio::fs::read_dir(extract_dir)
+        .await
+        .map_err(|e| PreinstallError::ReadManifest(extract_dir.into(), e))?;
+    let mut subdirs = std::vec::Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry
+            .file_type()
+            .await
+            .is_ok_and(|file_type| file_type.is_dir())
+            && entry.file_name() != ".asimov"
+        {
+            subdirs.push(entry.path());
+        }
+    }
+    if let [subdir] = &subdirs[..] {
+        dirs.push(subdir.clone());
+    }
+
+    for dir in dirs {
+        for manifest_path in MANIFEST_PATHS {
+            let path = dir.join(manifest_path);
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(PreinstallError::ReadManifest(path, err)),
+            };
+            let manifest = serde_yaml_ng::from_str(&content)
+                .map_err(|e| PreinstallError::ParseManifest(path, e))?;
+            return Ok((dir, manifest));
+        }
+    }
+
+    Err(PreinstallError::NoManifest)
+}
+
+/// Copies a directory recursively, following symbolic links.
+fn copy_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let (src, dst) = (entry.path(), dst.join(entry.file_name()));
+        if std::fs::metadata(&src)?.is_dir() {
+            copy_dir(&src, &dst)?;
+        } else {
+            std::fs::copy(&src, &dst)?;
+        }
+    }
+    Ok(())
+}
+
 async fn find_readme(extract_dir: &Path) -> Option<String> {
     let mut entries = tokio::fs::read_dir(extract_dir).await.ok()?;
 
@@ -1,6 +1,6 @@
 // This is free and unencumbered software released into the public domain.
 
-use super::platform::PlatformInfo;
+use super::{lock::LockMismatch, platform::PlatformInfo};
 use asimov_registry::error as registry;
 use std::{
     boxed::Box,
@@ -12,12 +12,16 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum InstallError {
+    #[error(transparent)]
+    Plan(#[from] super::plan::PlanError),
     #[error(transparent)]
     WorkDir(#[from] WorkDirError),
     #[error(transparent)]
     Preinstall(#[from] PreinstallError),
     #[error(transparent)]
     Finish(#[from] FinishInstallError),
+    #[error(transparent)]
+    Lock(#[from] registry::LockError),
 }
 
 #[derive(Debug, Error)]
@@ -32,12 +36,45 @@ pub enum UpgradeError {
     CheckEnabled(#[from] registry::IsModuleEnabledError),
     #[error(transparent)]
     Preinstall(#[from] PreinstallError),
-    #[error(transparent)]
-    Uninstall(#[from] UninstallError),
+    #[error("failed to install modules required by the new version: {0}")]
+    Requires(#[source] Box<InstallError>),
     #[error(transparent)]
     Install(#[from] FinishInstallError),
-    #[error("failed to re-enable module: {0}")]
-    ReEnable(#[from] registry::EnableError),
+    #[error("failed to replace installed module: {0}")]
+    Replace(#[from] registry::ReplaceModuleError),
+    #[error("version {version} is not allowed by the module's pin `{pin}`")]
+    Pinned {
+        version: String,
+        pin: asimov_registry::Pin,
+    },
+    #[error("unable to read module pin: {0}")]
+    Pin(#[from] registry::PinError),
+    #[error(transparent)]
+    Lock(#[from] registry::LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum InstallLockedError {
+    #[error("invalid module name in lockfile: {0}")]
+    InvalidModuleName(#[from] asimov_module::InvalidModuleName),
+    #[error("unable to read module manifest file: {0}")]
+    Read(#[from] registry::ManifestError),
+    #[error("unable to check if module is installed: {0}")]
+    CheckInstalled(#[from] registry::IsModuleInstalledError),
+    #[error("unable to check if module is enabled: {0}")]
+    CheckEnabled(#[from] registry::IsModuleEnabledError),
+    #[error("installation deviates from the lockfile: {0}")]
+    Locked(#[from] LockMismatch),
+    #[error("failed to install module `{0}`: {1}")]
+    Install(String, #[source] Box<InstallError>),
+    #[error("failed to upgrade module `{0}`: {1}")]
+    Upgrade(String, #[source] Box<UpgradeError>),
+    #[error(transparent)]
+    Enable(#[from] registry::EnableError),
+    #[error(transparent)]
+    Disable(#[from] registry::DisableError),
+    #[error(transparent)]
+    Lock(#[from] registry::LockError),
 }
 
 #[derive(Debug, Error)]
@@ -50,6 +87,10 @@ pub enum UninstallError {
     RemoveBinary(String, #[source] registry::RemoveBinaryError),
     #[error("unable to remove installed module: {0}")]
     RemoveModule(#[from] registry::RemoveModuleError),
+    #[error("unable to unpin removed module: {0}")]
+    Unpin(#[from] registry::PinError),
+    #[error(transparent)]
+    Lock(#[from] registry::LockError),
 }
 
 mod common {
@@ -59,8 +100,12 @@ mod common {
     pub enum FetchError {
         #[error(transparent)]
         Http(#[from] HttpError),
-        #[error("unable to deserialize GitHub API response: {0}")]
+        #[error("unable to deserialize response: {0}")]
         Deserialize(#[from] DeserializeError),
+        #[error("failed to read release: {0}")]
+        Io(#[from] io::Error),
+        #[error("no release found")]
+        NotFound,
     }
 
     impl From<reqwest::Error> for FetchError {
@@ -81,6 +126,8 @@ mod common {
     pub enum FetchChecksumError {
         #[error(transparent)]
         Http(#[from] HttpError),
+        #[error("failed to read checksum: {0}")]
+        Io(#[from] io::Error),
     }
 
     impl From<reqwest::Error> for FetchChecksumError {
@@ -97,6 +144,18 @@ mod common {
         InvalidChecksum(String, String),
     }
 
+    #[derive(Debug, Error)]
+    pub enum VerifySignatureError {
+        #[error("release asset is not signed, but the module index pins its publisher keys")]
+        Unsigned,
+        #[error("malformed signature of release asset: {0}")]
+        Malformed(String),
+        #[error("signature of release asset is not by a pinned publisher key")]
+        Untrusted,
+        #[error("failed to read release asset: {0}")]
+        Io(#[from] io::Error),
+    }
+
     #[derive(Debug, Error)]
     pub enum DownloadError {
         #[error(transparent)]
@@ -105,6 +164,8 @@ mod common {
         Io(#[from] io::Error),
         #[error("no matching asset found")]
         NoMatch,
+        #[error("failed to fetch release: {0}")]
+        Release(#[from] FetchError),
     }
 
     impl From<reqwest::Error> for DownloadError {
@@ -146,16 +207,38 @@ mod common {
         FetchChecksum(#[from] FetchChecksumError),
         #[error(transparent)]
         VerifyChecksum(#[from] VerifyChecksumError),
+        #[error("no published checksum for `{0}`, but checksums are required")]
+        NoChecksum(String),
+
+        #[error("failed to fetch signature: {0}")]
+        FetchSignature(#[source] FetchChecksumError),
+        #[error(transparent)]
+        VerifySignature(#[from] VerifySignatureError),
 
+        #[error("failed to create directory for downloading: {0}")]
+        CreateDownloadDir(io::Error),
         #[error("failed to create directory for extracting: {0}")]
         CreateExtractDir(io::Error),
 
         #[error("failed to extract archive: {0}")]
         Extract(io::Error),
 
+        #[error("no module manifest found in the archive")]
+        NoManifest,
+        #[error("failed to read module manifest `{0}`: {1}")]
+        ReadManifest(PathBuf, #[source] io::Error),
+        #[error("failed to parse module manifest `{0}`: {1}")]
+        ParseManifest(PathBuf, #[source] serde_yaml_ng::Error),
+
         #[error("module manifest does not have a choice of model size `{0}`")]
         NoSuchModel(String),
 
+        #[error("installation deviates from the lockfile: {0}")]
+        Locked(#[from] LockMismatch),
+
+        #[error("failed to verify required model `{0}`: {1}")]
+        VerifyModel(String, #[source] VerifyChecksumError),
+
         #[error("error while installing required model: {0}")]
         InstallModel(#[from] asimov_huggingface::HuggingfaceError),
     }
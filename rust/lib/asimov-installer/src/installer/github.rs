@@ -1,6 +1,9 @@
 // This is free and unencumbered software released into the public domain.
 
-use super::error::{DownloadError, FetchChecksumError, FetchError, HttpError, VerifyChecksumError};
+use super::{
+    error::{DownloadError, FetchChecksumError, FetchError, HttpError, VerifyChecksumError},
+    progress::{Phase, ProgressReporter},
+};
 use alloc::{
     borrow::ToOwned as _,
     format,
@@ -8,6 +11,7 @@ use alloc::{
     vec,
 };
 use asimov_module::ModuleManifest;
+use core::time::Duration;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
@@ -152,10 +156,24 @@ pub async fn fetch_checksum(
     client: &reqwest::Client,
     asset_url: &str,
 ) -> Result<Option<String>, FetchChecksumError> {
-    let checksum_url = format!("{asset_url}.sha256");
+    fetch_optional(client, &format!("{asset_url}.sha256")).await
+}
 
+/// Fetches the detached signature published next to an asset with a `.sig`
+/// suffix, if there is one.
+pub async fn fetch_signature(
+    client: &reqwest::Client,
+    asset_url: &str,
+) -> Result<Option<String>, FetchChecksumError> {
+    fetch_optional(client, &format!("{asset_url}.sig")).await
+}
+
+async fn fetch_optional(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<Option<String>, FetchChecksumError> {
     let response = client
-        .get(&checksum_url)
+        .get(url)
         .send()
         .await
         .inspect_err(|err| tracing::debug!(?err))?;
@@ -178,14 +196,12 @@ pub async fn fetch_checksum(
     ))
 }
 
-pub async fn verify_checksum(
-    binary_path: &Path,
-    expected_checksum: &str,
-) -> Result<(), VerifyChecksumError> {
+/// Returns the SHA-256 hash of a file, in hex.
+pub async fn sha256_file(path: &Path) -> std::io::Result<String> {
     use sha2::{Digest, Sha256};
 
     let mut hasher = Sha256::new();
-    let mut file = tokio::fs::File::open(binary_path).await?;
+    let mut file = tokio::fs::File::open(path).await?;
 
     const READ_BUFFER_SIZE: usize = 10 * 1024;
     let mut buf = vec![0u8; READ_BUFFER_SIZE];
@@ -198,7 +214,14 @@ pub async fn verify_checksum(
         hasher.update(&buf[..n]);
     }
 
-    let actual_checksum = format!("{:x}", hasher.finalize());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub async fn verify_checksum(
+    binary_path: &Path,
+    expected_checksum: &str,
+) -> Result<(), VerifyChecksumError> {
+    let actual_checksum = sha256_file(binary_path).await?;
 
     // Extract just the hash part from expected (in case it has filename)
     let expected_hash = expected_checksum
@@ -216,6 +239,13 @@ pub async fn verify_checksum(
     Ok(())
 }
 
+/// The number of times a download is retried after a transient error.
+const DOWNLOAD_RETRIES: u32 = 4;
+
+/// The delay before retrying a download for the first time, doubled for each
+/// further retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
 #[tracing::instrument(skip_all)]
 pub async fn download_matching_asset(
     client: &reqwest::Client,
@@ -223,76 +253,153 @@ pub async fn download_matching_asset(
     version: &str,
     platform: &super::platform::PlatformInfo,
     dst_dir: &Path,
+    progress: &ProgressReporter,
 ) -> Result<(String, PathBuf), DownloadError> {
-    let filenames = if let Some(libc) = &platform.libc {
-        vec![
-            format!(
-                "asimov-{}-module-{}-{}-{}.tar.gz",
-                module_name, platform.os, platform.arch, libc
-            ),
-            format!(
-                "asimov-{}-module-{}-{}-{}.zip",
-                module_name, platform.os, platform.arch, libc
-            ),
-            format!(
-                "asimov-{}-module-{}-{}.tar.gz",
-                module_name, platform.os, platform.arch
-            ),
-            format!(
-                "asimov-{}-module-{}-{}.zip",
-                module_name, platform.os, platform.arch
-            ),
-        ]
-    } else {
-        vec![
-            format!(
-                "asimov-{}-module-{}-{}.tar.gz",
-                module_name, platform.os, platform.arch
-            ),
-            format!(
-                "asimov-{}-module-{}-{}.zip",
-                module_name, platform.os, platform.arch
-            ),
-        ]
-    };
-
-    for filename in filenames {
+    for filename in platform.asset_names(module_name) {
         let url = format!(
             "https://github.com/asimov-modules/asimov-{module_name}-module/releases/download/{version}/{filename}"
         );
 
         tracing::debug!("trying asset URL {url}...");
 
-        let mut response = client
-            .get(&url)
-            .send()
-            .await
-            .inspect_err(|err| tracing::debug!(?err))?;
-
-        if response.status() == 404 {
+        let asset_path = dst_dir.join(&filename);
+        if !download(client, &url, &asset_path, progress).await? {
             // try another asset pattern
             continue;
         }
-        if !response.status().is_success() {
-            Err(HttpError::NotSuccess(response.status()))?;
-        }
 
-        let asset_path = dst_dir.join(&filename);
-        let mut dst = tokio::fs::File::create(&asset_path).await?;
+        return Ok((url, asset_path));
+    }
 
-        while let Some(chunk) = response
-            .chunk()
-            .await
-            .inspect_err(|err| tracing::debug!(?err))?
-        {
-            dst.write_all(&chunk).await?;
+    Err(DownloadError::NoMatch)
+}
+
+/// Downloads a file, returning `false` if there is no such file.
+///
+/// The file is downloaded to a `.part` file next to `path` first, renamed
+/// to `path` once complete. A partial download, such as from an interrupted
+/// earlier attempt, is resumed with an HTTP range request, and a file
+/// already at `path` is taken as downloaded. Transient errors, such as
+/// timeouts and server errors, are retried with exponential backoff.
+pub(super) async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+    progress: &ProgressReporter,
+) -> Result<bool, DownloadError> {
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        tracing::debug!(?path, "already downloaded");
+        progress.report(Phase::Download, metadata.len(), Some(metadata.len()));
+        return Ok(true);
+    }
+
+    let part_path = path.with_file_name(format!(
+        "{}.part",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let mut attempt = 0;
+    loop {
+        match download_part(client, url, &part_path, progress).await {
+            Ok(false) => return Ok(false),
+            Ok(true) => {
+                tokio::fs::rename(&part_path, path).await?;
+                return Ok(true);
+            },
+            Err(err) if attempt < DOWNLOAD_RETRIES && is_transient(&err) => {
+                let delay = RETRY_DELAY * 2u32.pow(attempt);
+                tracing::debug!(?err, ?delay, url, "retrying download");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            },
+            Err(err) => return Err(err),
         }
-        dst.flush().await?;
+    }
+}
 
-        return Ok((url, asset_path));
+/// Downloads the rest of a file into a partial download, returning `false`
+/// if there is no such file.
+async fn download_part(
+    client: &reqwest::Client,
+    url: &str,
+    part_path: &Path,
+    progress: &ProgressReporter,
+) -> Result<bool, DownloadError> {
+    let offset = match tokio::fs::metadata(part_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut response = request
+        .send()
+        .await
+        .inspect_err(|err| tracing::debug!(?err))?;
+
+    match response.status() {
+        status if status == 404 => return Ok(false),
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE => {
+            // the partial download doesn't fit the file, start over
+            tokio::fs::remove_file(part_path).await?;
+            Err(HttpError::NotSuccess(response.status()))?;
+        },
+        status if !status.is_success() => Err(HttpError::NotSuccess(status))?,
+        _ => {},
     }
 
-    Err(DownloadError::NoMatch)
+    // a server without range support sends the whole file
+    let resumed = offset > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut bytes = if resumed { offset } else { 0 };
+    let total = response.content_length().map(|length| bytes + length);
+    if resumed {
+        tracing::debug!(url, offset, "resuming download");
+    }
+
+    let mut dst = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part_path)
+        .await?;
+    progress.report(Phase::Download, bytes, total);
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .inspect_err(|err| tracing::debug!(?err))?
+    {
+        dst.write_all(&chunk).await?;
+        bytes += chunk.len() as u64;
+        progress.report(Phase::Download, bytes, total);
+    }
+    dst.flush().await?;
+
+    Ok(true)
+}
+
+/// Whether a download may succeed when retried.
+fn is_transient(err: &DownloadError) -> bool {
+    match err {
+        // including a body cut short, which reqwest reports as a decode error
+        DownloadError::Http(HttpError::Http(err)) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.is_request()
+                || err.is_body()
+                || err.is_decode()
+        },
+        DownloadError::Http(HttpError::NotSuccess(status)) => {
+            status.is_server_error()
+                || *status == reqwest::StatusCode::REQUEST_TIMEOUT
+                || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || *status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+        },
+        _ => false,
+    }
 }
 
 pub async fn extract_files(
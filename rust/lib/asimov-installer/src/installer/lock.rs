@@ -0,0 +1,245 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{collections::BTreeMap, format, string::String};
+use asimov_module::{InstalledModel, InstalledModuleManifest};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The conventional name of a lockfile, such as in a project directory.
+pub const LOCK_FILE_NAME: &str = "asimov.lock";
+
+const LOCK_FORMAT_VERSION: u32 = 1;
+
+/// The exact set of installed modules, to reproduce on another machine with
+/// [`Installer::install_locked`](super::Installer::install_locked).
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Lockfile {
+    /// The version of the lockfile format.
+    pub lock_version: u32,
+
+    /// The locked modules, by module name.
+    #[serde(default)]
+    pub modules: BTreeMap<String, LockedModule>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            lock_version: LOCK_FORMAT_VERSION,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+/// An installed module, as recorded in a [`Lockfile`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LockedModule {
+    /// The exact version.
+    pub version: String,
+
+    /// The location of the release asset.
+    pub url: String,
+
+    /// The SHA-256 hash of the release asset, in hex.
+    pub sha256: String,
+
+    /// The installed models and their chosen variants, by model name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, InstalledModel>,
+
+    /// Whether the module is enabled.
+    pub enabled: bool,
+}
+
+impl LockedModule {
+    /// Records an installed module, which must have been installed from a
+    /// release, rather than from a local archive or directory.
+    pub fn from_installed(
+        manifest: &InstalledModuleManifest,
+        enabled: bool,
+    ) -> Result<Self, LockError> {
+        let module = &manifest.manifest.name;
+        let not_reproducible = |reason| LockError::NotReproducible(module.clone(), reason);
+
+        let Some(asimov_module::InstallSource::Release { url }) = &manifest.source else {
+            return Err(not_reproducible("it wasn't installed from a release"));
+        };
+        Ok(Self {
+            version: manifest
+                .version
+                .clone()
+                .ok_or_else(|| not_reproducible("its version is unknown"))?,
+            url: url.clone(),
+            sha256: manifest
+                .sha256
+                .clone()
+                .ok_or_else(|| not_reproducible("its checksum is unknown"))?,
+            models: manifest.installed_models.clone(),
+            enabled,
+        })
+    }
+}
+
+/// Checks a property of a module being installed against the lockfile.
+pub(crate) fn check(
+    module: &str,
+    field: &'static str,
+    expected: &str,
+    actual: &str,
+) -> Result<(), LockMismatch> {
+    if expected == actual {
+        return Ok(());
+    }
+    Err(LockMismatch::Changed {
+        module: module.into(),
+        field,
+        expected: expected.into(),
+        actual: actual.into(),
+    })
+}
+
+impl Lockfile {
+    /// Returns the locked module, failing if it isn't locked.
+    pub fn module(&self, module_name: &str) -> Result<&LockedModule, LockMismatch> {
+        self.modules
+            .get(module_name)
+            .ok_or_else(|| LockMismatch::NotLocked(module_name.into()))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, LockfileError> {
+        let path = path.as_ref();
+        let content =
+            std::fs::read_to_string(path).map_err(|e| LockfileError::Io(path.into(), e))?;
+        let lockfile: Self =
+            serde_json::from_str(&content).map_err(|e| LockfileError::Parse(path.into(), e))?;
+        if lockfile.lock_version != LOCK_FORMAT_VERSION {
+            return Err(LockfileError::UnsupportedVersion(
+                path.into(),
+                lockfile.lock_version,
+            ));
+        }
+        Ok(lockfile)
+    }
+
+    /// Writes the lockfile atomically.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), LockfileError> {
+        let path = path.as_ref();
+        let mut content = serde_json::to_string_pretty(self).map_err(LockfileError::Serialize)?;
+        content.push('\n');
+
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, content)
+            .and_then(|()| std::fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&tmp_path);
+                LockfileError::Io(path.into(), e)
+            })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LockfileError {
+    #[error("failed to access lockfile `{0}`: {1}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("invalid lockfile `{0}`: {1}")]
+    Parse(PathBuf, #[source] serde_json::Error),
+    #[error("failed to serialize lockfile: {0}")]
+    Serialize(#[source] serde_json::Error),
+    #[error("unsupported version {1} of lockfile `{0}`")]
+    UnsupportedVersion(PathBuf, u32),
+}
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("unable to list installed modules: {0}")]
+    InstalledModules(#[from] asimov_registry::error::InstalledModulesError),
+    #[error("unable to check if module is enabled: {0}")]
+    CheckEnabled(#[from] asimov_registry::error::IsModuleEnabledError),
+    #[error("invalid name of installed module: {0}")]
+    InvalidModuleName(#[from] asimov_module::InvalidModuleName),
+    #[error("unable to lock module `{0}`, as {1}")]
+    NotReproducible(String, &'static str),
+}
+
+/// How an installation would deviate from a lockfile.
+#[derive(Debug, Error)]
+pub enum LockMismatch {
+    #[error("module `{0}` is not in the lockfile")]
+    NotLocked(String),
+    #[error("model `{model}` of module `{module}` is not in the lockfile")]
+    ModelNotLocked { module: String, model: String },
+    #[error("{field} of module `{module}` would be `{actual}`, but the lockfile has `{expected}`")]
+    Changed {
+        module: String,
+        field: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        let mut lockfile = Lockfile::default();
+        lockfile.modules.insert(
+            "example".into(),
+            LockedModule {
+                version: "0.1.0".into(),
+                url: "https://example.org/example.tar.gz".into(),
+                sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into(),
+                models: BTreeMap::from([(
+                    "hf:example/model".into(),
+                    InstalledModel {
+                        variant: Some("small".into()),
+                        file: "small.bin".into(),
+                        sha256: None,
+                    },
+                )]),
+                enabled: true,
+            },
+        );
+        lockfile.write(&path).unwrap();
+        assert_eq!(Lockfile::read(&path).unwrap(), lockfile);
+
+        std::fs::write(&path, r#"{"lock_version": 2}"#).unwrap();
+        assert!(matches!(
+            Lockfile::read(&path),
+            Err(LockfileError::UnsupportedVersion(_, 2))
+        ));
+    }
+
+    #[test]
+    fn lock_installed() {
+        let mut manifest = InstalledModuleManifest {
+            version: Some("0.1.0".into()),
+            sha256: Some("00".into()),
+            ..Default::default()
+        };
+        manifest.manifest.name = "example".into();
+        assert!(matches!(
+            LockedModule::from_installed(&manifest, true),
+            Err(LockError::NotReproducible(..))
+        ));
+
+        manifest.source = Some(asimov_module::InstallSource::Release {
+            url: "https://example.org/example.tar.gz".into(),
+        });
+        let locked = LockedModule::from_installed(&manifest, false).unwrap();
+        assert_eq!(locked.version, "0.1.0");
+        assert!(!locked.enabled);
+        assert!(check("example", "version", "0.1.0", "0.1.0").is_ok());
+        assert!(matches!(
+            check("example", "version", "0.1.0", "0.2.0"),
+            Err(LockMismatch::Changed { .. })
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+// This is free and unencumbered software released into the public domain.
+
+use asimov_module::{ModelFile, RequiredModel, tracing};
+use std::{
+    path::Path,
+    string::{String, ToString},
+};
+
+/// The disk space to leave free after installing a model.
+const DISK_RESERVE: u64 = 1024 * 1024 * 1024;
+
+/// The share of the total memory that a model may take up when loaded.
+const MEMORY_SHARE: u64 = 2;
+
+/// The free disk space and the total memory of the system, where known, to
+/// choose a variant of a model that fits by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemResources {
+    /// Bytes available to unprivileged users on the disk of the model cache.
+    pub free_disk: Option<u64>,
+    /// Bytes of physical memory.
+    pub total_memory: Option<u64>,
+}
+
+impl SystemResources {
+    /// Detects the free disk space on the disk of the given directory, which
+    /// doesn't need to exist yet, and the total memory.
+    pub fn detect(dir: &Path) -> Self {
+        Self {
+            free_disk: dir
+                .ancestors()
+                .find(|dir| dir.exists())
+                .and_then(free_disk_space),
+            total_memory: total_memory(),
+        }
+    }
+
+    /// Whether a model file of the given size fits, leaving some disk space
+    /// free, and taking up at most half of the memory.
+    pub fn fits(&self, size: u64) -> bool {
+        self.free_disk
+            .is_none_or(|free| size <= free.saturating_sub(DISK_RESERVE))
+            && self
+                .total_memory
+                .is_none_or(|total| size <= total / MEMORY_SHARE)
+    }
+}
+
+#[cfg(unix)]
+fn free_disk_space(dir: &Path) -> Option<u64> {
+    let stat = rustix::fs::statvfs(dir)
+        .inspect_err(|err| tracing::debug!(?err, ?dir, "failed to get free disk space"))
+        .ok()?;
+    Some(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn free_disk_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn total_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kilobytes = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn total_memory() -> Option<u64> {
+    None
+}
+
+/// Chooses the file to install for a required model, along with the name of
+/// its variant for [`RequiredModel::Choices`].
+///
+/// A variant is chosen, in order of preference:
+/// - by name, if one is wanted, failing with the name if there is no such variant;
+/// - as the first variant already installed, according to `is_installed`;
+/// - as the largest variant that fits the system resources, if every variant
+///   declares its size, or else the smallest one;
+/// - as the first variant.
+pub(crate) fn select_variant<'a>(
+    model: &'a RequiredModel,
+    wanted: Option<&str>,
+    is_installed: impl Fn(&ModelFile) -> bool,
+    resources: &SystemResources,
+) -> Result<Option<(Option<String>, &'a ModelFile)>, String> {
+    let choices = match model {
+        RequiredModel::Url(file) => return Ok(Some((None, file))),
+        RequiredModel::Choices(choices) => choices,
+    };
+
+    let selected = if let Some(wanted) = wanted {
+        let Some(choice) = choices.iter().find(|(name, _)| name == wanted) else {
+            return Err(wanted.into());
+        };
+        choice
+    } else if let Some(choice) = choices.iter().find(|(_, file)| is_installed(file)) {
+        choice
+    } else if let Some(sizes) = choices
+        .iter()
+        .map(|(_, file)| file.size)
+        .collect::<Option<std::vec::Vec<u64>>>()
+    {
+        let by_size = || choices.iter().zip(&sizes);
+        let fitting = by_size()
+            .filter(|(_, size)| resources.fits(**size))
+            .max_by_key(|(_, size)| **size);
+        match fitting {
+            Some((choice, _)) => choice,
+            None => {
+                let Some((choice, _)) = by_size().min_by_key(|(_, size)| **size) else {
+                    return Ok(None);
+                };
+                tracing::warn!(
+                    variant = choice.0,
+                    ?resources,
+                    "no variant of the model fits the available resources, choosing the smallest"
+                );
+                choice
+            },
+        }
+    } else if let Some(choice) = choices.first() {
+        choice
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some((Some(selected.0.to_string()), &selected.1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use std::{vec, vec::Vec};
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn choices(sizes: &[(&str, Option<u64>)]) -> RequiredModel {
+        RequiredModel::Choices(
+            sizes
+                .iter()
+                .map(|(name, size)| {
+                    let file = ModelFile {
+                        file: format!("{name}.bin"),
+                        size: *size,
+                        ..Default::default()
+                    };
+                    (name.to_string(), file)
+                })
+                .collect(),
+        )
+    }
+
+    fn selected(
+        model: &RequiredModel,
+        wanted: Option<&str>,
+        installed: &[&str],
+        resources: SystemResources,
+    ) -> Option<String> {
+        let installed: Vec<String> = installed.iter().map(|name| format!("{name}.bin")).collect();
+        select_variant(
+            model,
+            wanted,
+            |file| installed.contains(&file.file),
+            &resources,
+        )
+        .unwrap()
+        .and_then(|(variant, _)| variant)
+    }
+
+    #[test]
+    fn select_by_choice() {
+        let model = choices(&[("small", None), ("large", None)]);
+        let none = SystemResources::default();
+
+        assert_eq!(
+            selected(&model, Some("large"), &["small"], none).as_deref(),
+            Some("large")
+        );
+        assert_eq!(
+            select_variant(&model, Some("huge"), |_| false, &none).unwrap_err(),
+            "huge"
+        );
+        assert_eq!(
+            selected(&model, None, &["large"], none).as_deref(),
+            Some("large")
+        );
+        // without sizes, the first variant:
+        assert_eq!(selected(&model, None, &[], none).as_deref(), Some("small"));
+
+        let url = RequiredModel::Url("model.bin".into());
+        let (variant, file) = select_variant(&url, None, |_| false, &none)
+            .unwrap()
+            .unwrap();
+        assert_eq!((variant, file.file.as_str()), (None, "model.bin"));
+
+        assert_eq!(
+            select_variant(&RequiredModel::Choices(vec![]), None, |_| false, &none).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn select_by_resources() {
+        let model = choices(&[
+            ("small", Some(GB)),
+            ("medium", Some(4 * GB)),
+            ("large", Some(16 * GB)),
+        ]);
+        let resources = |free_disk: u64, total_memory: u64| SystemResources {
+            free_disk: Some(free_disk * GB),
+            total_memory: Some(total_memory * GB),
+        };
+
+        assert_eq!(
+            selected(&model, None, &[], resources(100, 64)).as_deref(),
+            Some("large")
+        );
+        // limited by memory:
+        assert_eq!(
+            selected(&model, None, &[], resources(100, 16)).as_deref(),
+            Some("medium")
+        );
+        // limited by disk space:
+        assert_eq!(
+            selected(&model, None, &[], resources(4, 64)).as_deref(),
+            Some("small")
+        );
+        // nothing fits:
+        assert_eq!(
+            selected(&model, None, &[], resources(1, 1)).as_deref(),
+            Some("small")
+        );
+        // unknown resources don't limit anything:
+        assert_eq!(
+            selected(&model, None, &[], SystemResources::default()).as_deref(),
+            Some("large")
+        );
+        // an installed variant is kept:
+        assert_eq!(
+            selected(&model, None, &["medium"], resources(100, 64)).as_deref(),
+            Some("medium")
+        );
+    }
+}
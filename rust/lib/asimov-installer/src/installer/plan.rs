@@ -0,0 +1,185 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{error::FetchError, lock::LockMismatch};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use asimov_module::{InvalidModuleName, ModuleName};
+use thiserror::Error;
+
+/// The number of modules installed at the same time, unless
+/// [`InstallOptions::jobs`](super::InstallOptions::jobs) says otherwise.
+pub const DEFAULT_JOBS: usize = 4;
+
+/// The modules to install for a module and its missing dependencies, as
+/// returned by [`Installer::plan`](super::Installer::plan).
+///
+/// Its `Display` lists what would be installed, for a dry run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstallPlan {
+    /// The modules to install, each after the modules it requires.
+    pub modules: Vec<PlannedModule>,
+
+    /// The required modules that are already installed.
+    pub satisfied: Vec<ModuleName>,
+}
+
+/// A module to install as part of an [`InstallPlan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedModule {
+    pub name: ModuleName,
+    pub version: String,
+
+    /// The modules of the plan that must be installed before this one.
+    pub requires: Vec<ModuleName>,
+}
+
+impl core::fmt::Display for InstallPlan {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for module in &self.modules {
+            write!(f, "install {} {}", module.name, module.version)?;
+            if let Some((first, rest)) = module.requires.split_first() {
+                write!(f, " (after {first}")?;
+                for name in rest {
+                    write!(f, ", {name}")?;
+                }
+                f.write_str(")")?;
+            }
+            f.write_str("\n")?;
+        }
+        for name in &self.satisfied {
+            writeln!(f, "keep {name} (already installed)")?;
+        }
+        Ok(())
+    }
+}
+
+impl InstallPlan {
+    /// Orders the modules to install by their requirements, failing on a
+    /// cycle. Requirements on the modules in `satisfied` are left out.
+    pub(crate) fn new(
+        root: &ModuleName,
+        mut graph: BTreeMap<ModuleName, (String, Vec<ModuleName>)>,
+        satisfied: BTreeSet<ModuleName>,
+    ) -> Result<Self, PlanError> {
+        fn visit(
+            name: &ModuleName,
+            graph: &BTreeMap<ModuleName, (String, Vec<ModuleName>)>,
+            path: &mut Vec<ModuleName>,
+            order: &mut Vec<ModuleName>,
+        ) -> Result<(), PlanError> {
+            if order.contains(name) || !graph.contains_key(name) {
+                return Ok(());
+            }
+            if let Some(start) = path.iter().position(|n| n == name) {
+                let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+                cycle.push(name.to_string());
+                return Err(PlanError::Cycle(cycle));
+            }
+
+            path.push(name.clone());
+            for required in &graph[name].1 {
+                visit(required, graph, path, order)?;
+            }
+            path.pop();
+            order.push(name.clone());
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        visit(root, &graph, &mut Vec::new(), &mut order)?;
+
+        let modules = order
+            .into_iter()
+            .filter_map(|name| {
+                let (version, requires) = graph.remove(&name)?;
+                let requires = requires
+                    .into_iter()
+                    .filter(|required| !satisfied.contains(required))
+                    .collect();
+                Some(PlannedModule {
+                    name,
+                    version,
+                    requires,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            modules,
+            satisfied: satisfied.into_iter().collect(),
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error("failed to fetch release of module `{0}`: {1}")]
+    FetchRelease(String, #[source] FetchError),
+    #[error("failed to fetch manifest of module `{0}`: {1}")]
+    FetchManifest(String, #[source] FetchError),
+    #[error("invalid name for a required module: {0}")]
+    InvalidDependencyName(#[from] InvalidModuleName),
+    #[error("installation deviates from the lockfile: {0}")]
+    Locked(#[from] LockMismatch),
+    #[error("dependency cycle between modules: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn graph(edges: &[(&str, &[&str])]) -> BTreeMap<ModuleName, (String, Vec<ModuleName>)> {
+        edges
+            .iter()
+            .map(|(name, requires)| {
+                let requires = requires.iter().map(|r| r.parse().unwrap()).collect();
+                (name.parse().unwrap(), ("1.0.0".to_string(), requires))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn order() {
+        let root = "app".parse().unwrap();
+        let plan = InstallPlan::new(
+            &root,
+            graph(&[
+                ("app", &["http", "json"]),
+                ("http", &["json", "tls"]),
+                ("json", &[]),
+            ]),
+            BTreeSet::from(["tls".parse().unwrap()]),
+        )
+        .unwrap();
+
+        let names: Vec<&str> = plan.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["json", "http", "app"]);
+        assert_eq!(plan.modules[1].requires, vec!["json".parse().unwrap()]);
+        assert_eq!(
+            plan.to_string(),
+            "install json 1.0.0\n\
+             install http 1.0.0 (after json)\n\
+             install app 1.0.0 (after http, json)\n\
+             keep tls (already installed)\n"
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let root = "app".parse().unwrap();
+        let result = InstallPlan::new(
+            &root,
+            graph(&[("app", &["a"]), ("a", &["b"]), ("b", &["a"])]),
+            BTreeSet::new(),
+        );
+        let Err(PlanError::Cycle(cycle)) = result else {
+            panic!("expected a cycle");
+        };
+        assert_eq!(cycle, ["a", "b", "a"]);
+    }
+}
@@ -1,14 +1,32 @@
 // This is free and unencumbered software released into the public domain.
 
-use alloc::string::String;
+use alloc::{format, string::String, vec::Vec};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PlatformInfo {
     pub os: String,
     pub arch: String,
     pub libc: Option<String>,
 }
 
+impl PlatformInfo {
+    /// The file names of the release assets of a module for the platform, in
+    /// order of preference, such as `asimov-foo-module-linux-x86-gnu.tar.gz`.
+    pub fn asset_names(&self, module_name: &str) -> Vec<String> {
+        let Self { os, arch, libc } = self;
+        let mut stems = Vec::with_capacity(2);
+        if let Some(libc) = libc {
+            stems.push(format!("asimov-{module_name}-module-{os}-{arch}-{libc}"));
+        }
+        stems.push(format!("asimov-{module_name}-module-{os}-{arch}"));
+
+        stems
+            .iter()
+            .flat_map(|stem| [format!("{stem}.tar.gz"), format!("{stem}.zip")])
+            .collect()
+    }
+}
+
 pub fn detect_platform() -> PlatformInfo {
     #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
     let os = "unknown";
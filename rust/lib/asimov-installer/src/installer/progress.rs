@@ -0,0 +1,74 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::{string::String, sync::Arc};
+
+/// A step of installing a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Downloading the release asset.
+    Download,
+    /// Verifying the checksum and signature of the release asset.
+    Verify,
+    /// Extracting the release asset.
+    Extract,
+    /// Adding the module to the registry.
+    Install,
+}
+
+/// The progress of installing a module, as reported to the callback of
+/// [`Installer::with_progress`](super::Installer::with_progress).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub module: String,
+    pub phase: Phase,
+    /// The bytes downloaded so far, including those of an earlier,
+    /// interrupted download, or else zero.
+    pub bytes: u64,
+    /// The total bytes to download, where known.
+    pub total: Option<u64>,
+}
+
+/// A callback receiving the [`Progress`] of installing modules, which may be
+/// called from concurrent installs.
+pub type ProgressFn = dyn Fn(&Progress) + Send + Sync;
+
+/// Reports the progress of installing a module to an optional callback.
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    module: String,
+    callback: Option<Arc<ProgressFn>>,
+}
+
+impl ProgressReporter {
+    pub fn new(module: impl Into<String>, callback: Option<Arc<ProgressFn>>) -> Self {
+        Self {
+            module: module.into(),
+            callback,
+        }
+    }
+
+    /// Returns a reporter for another module, with the same callback.
+    pub fn for_module(&self, module: impl Into<String>) -> Self {
+        Self::new(module, self.callback.clone())
+    }
+
+    pub fn report(&self, phase: Phase, bytes: u64, total: Option<u64>) {
+        if let Some(callback) = &self.callback {
+            callback(&Progress {
+                module: self.module.clone(),
+                phase,
+                bytes,
+                total,
+            });
+        }
+    }
+}
+
+impl core::fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("module", &self.module)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,83 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::error::VerifySignatureError;
+use alloc::string::ToString as _;
+use asimov_id::PublicKey;
+use asimov_module::{IndexSignature, SignatureError, TrustedKeys, tracing};
+use std::path::Path;
+
+/// Verifies the detached signature of a release asset, returning the key of
+/// the publisher which made it.
+///
+/// As with minisign, a signature is published next to the asset, with a
+/// `.sig` suffix, on a line of its own after any `untrusted comment:` lines.
+/// It is an Ed25519 signature of the content of the asset, encoded in Base58
+/// like the signature of the module index.
+pub fn verify_signature(
+    content: &[u8],
+    signature: &str,
+    keys: &TrustedKeys,
+) -> Result<PublicKey, VerifySignatureError> {
+    let signature = signature
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or(VerifySignatureError::Malformed("no signature".into()))?;
+
+    let signature: IndexSignature = signature.parse().map_err(|err| match err {
+        SignatureError::Malformed(err) => VerifySignatureError::Malformed(err),
+        err => VerifySignatureError::Malformed(err.to_string()),
+    })?;
+    keys.verify(content, &signature)
+        .copied()
+        .map_err(|_| VerifySignatureError::Untrusted)
+}
+
+/// Verifies the signature of a release asset on disk, which is required.
+pub(crate) async fn verify_file(
+    path: &Path,
+    signature: Option<&str>,
+    keys: &TrustedKeys,
+) -> Result<(), VerifySignatureError> {
+    let signature = signature.ok_or(VerifySignatureError::Unsigned)?;
+    let content = tokio::fs::read(path).await?;
+    let key = verify_signature(&content, signature, keys)?;
+    tracing::debug!(?path, %key, "verified asset signature");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const ASSET: &[u8] = b"asset";
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn verify() {
+        let keys = TrustedKeys::new([PublicKey::from(&signing_key(1).verifying_key())]);
+        let signature = IndexSignature::from(signing_key(1).sign(ASSET));
+        let signed = format!("untrusted comment: signature of asset\n{signature}\n");
+
+        assert!(verify_signature(ASSET, &signed, &keys).is_ok());
+        assert!(matches!(
+            verify_signature(b"tampered", &signed, &keys),
+            Err(VerifySignatureError::Untrusted)
+        ));
+
+        let other = IndexSignature::from(signing_key(2).sign(ASSET));
+        assert!(matches!(
+            verify_signature(ASSET, &other.to_string(), &keys),
+            Err(VerifySignatureError::Untrusted)
+        ));
+        assert!(matches!(
+            verify_signature(ASSET, "untrusted comment: nothing\n", &keys),
+            Err(VerifySignatureError::Malformed(_))
+        ));
+    }
+}
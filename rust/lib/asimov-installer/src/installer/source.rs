@@ -0,0 +1,346 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    error::{DownloadError, FetchChecksumError, FetchError},
+    platform::PlatformInfo,
+    progress::ProgressReporter,
+};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use asimov_module::{ModuleManifest, tracing};
+use async_trait::async_trait;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+mod dir;
+pub use dir::*;
+mod github;
+pub use github::*;
+mod mirror;
+pub use mirror::*;
+mod oci;
+pub use oci::*;
+
+/// The name of the file configuring the release sources, in the modules
+/// directory of the ASIMOV root.
+pub const SOURCES_FILE_NAME: &str = "sources.yaml";
+
+/// Where the releases of modules are installed from.
+///
+/// The installer asks a source for the latest version of a module, unless a
+/// version is given, then for the manifest of that version, and then for the
+/// release asset that matches the platform.
+#[async_trait]
+pub trait ReleaseSource: core::fmt::Debug + Send + Sync {
+    /// Returns the latest released version of the module.
+    async fn fetch_latest_release(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<String, FetchError>;
+
+    /// Returns the released versions of the module, in no particular order.
+    /// Sources that can't list them return only the latest one.
+    async fn fetch_releases(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<Vec<String>, FetchError> {
+        Ok(alloc::vec![
+            self.fetch_latest_release(client, module_name).await?
+        ])
+    }
+
+    /// Returns the manifest of the given version of the module.
+    async fn fetch_module_manifest(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleManifest, FetchError>;
+
+    /// Returns the README of the given version of the module, if there is
+    /// one. The README in the release asset, if any, takes precedence.
+    async fn fetch_readme(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Option<String>;
+
+    /// Downloads the release asset for the platform into `dst_dir`,
+    /// returning the location it was downloaded from and the path of the
+    /// downloaded file, whose name tells the archive format.
+    ///
+    /// The directory may hold a partial download from an interrupted
+    /// earlier attempt, to resume where the source supports it.
+    async fn download_asset(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        platform: &PlatformInfo,
+        dst_dir: &Path,
+        progress: &ProgressReporter,
+    ) -> Result<(String, PathBuf), DownloadError>;
+
+    /// Returns the published SHA-256 checksum of an asset, by the location
+    /// returned from [`Self::download_asset`], if there is one.
+    async fn fetch_checksum(
+        &self,
+        client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError>;
+
+    /// Returns the published detached signature of an asset, by the location
+    /// returned from [`Self::download_asset`], if there is one. See
+    /// [`verify_signature`](super::verify_signature) for the format.
+    async fn fetch_signature(
+        &self,
+        _client: &reqwest::Client,
+        _asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        Ok(None)
+    }
+}
+
+/// The configuration of a [`ReleaseSource`], such as in
+/// [`SourcesConfig`].
+///
+/// ```yaml
+/// type: http
+/// url: https://mirror.example.org/asimov/modules/
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SourceConfig {
+    /// The releases of the `asimov-modules` organization on GitHub.
+    GitHub,
+    /// A static HTTP mirror, see [`MirrorSource`].
+    Http { url: url::Url },
+    /// A local directory, see [`DirSource`].
+    Dir { path: PathBuf },
+    /// An OCI registry, see [`OciSource`].
+    Oci { url: url::Url, namespace: String },
+}
+
+impl SourceConfig {
+    pub fn to_source(&self) -> Arc<dyn ReleaseSource> {
+        match self {
+            Self::GitHub => Arc::new(GitHubSource),
+            Self::Http { url } => Arc::new(MirrorSource::new(url.clone())),
+            Self::Dir { path } => Arc::new(DirSource::new(path.clone())),
+            Self::Oci { url, namespace } => {
+                Arc::new(OciSource::new(url.clone(), namespace.clone()))
+            },
+        }
+    }
+}
+
+/// The configuration of the release sources, globally and per module, as in
+/// `~/.asimov/modules/sources.yaml`:
+///
+/// ```yaml
+/// default:
+///   type: http
+///   url: https://mirror.example.org/asimov/modules/
+/// modules:
+///   internal:
+///     type: dir
+///     path: /srv/asimov/modules
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SourcesConfig {
+    /// The source of all modules without a source of their own, GitHub by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<SourceConfig>,
+
+    /// The sources of specific modules, by module name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, SourceConfig>,
+}
+
+impl SourcesConfig {
+    /// The path of the configuration file, `~/.asimov/modules/sources.yaml`.
+    pub fn config_path() -> PathBuf {
+        asimov_env::paths::asimov_root()
+            .join("modules")
+            .join(SOURCES_FILE_NAME)
+    }
+
+    /// Reads the configuration file, if there is one.
+    pub fn from_config() -> Result<Self, SourcesConfigError> {
+        Self::read(&Self::config_path())
+    }
+
+    /// Reads a configuration file, which is empty if missing.
+    pub fn read(path: &Path) -> Result<Self, SourcesConfigError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(SourcesConfigError::Io(path.into(), err)),
+        };
+        serde_yaml_ng::from_str(&content).map_err(|e| SourcesConfigError::Parse(path.into(), e))
+    }
+}
+
+/// The release sources of the installer, with a default source and sources
+/// for specific modules.
+#[derive(Clone, Debug)]
+pub struct ReleaseSources {
+    default: Arc<dyn ReleaseSource>,
+    modules: BTreeMap<String, Arc<dyn ReleaseSource>>,
+}
+
+impl Default for ReleaseSources {
+    fn default() -> Self {
+        Self::new(Arc::new(GitHubSource))
+    }
+}
+
+impl From<&SourcesConfig> for ReleaseSources {
+    fn from(config: &SourcesConfig) -> Self {
+        let default = config
+            .default
+            .as_ref()
+            .map_or_else(|| Arc::new(GitHubSource) as _, SourceConfig::to_source);
+        let modules = config
+            .modules
+            .iter()
+            .map(|(name, source)| (name.clone(), source.to_source()))
+            .collect();
+        Self { default, modules }
+    }
+}
+
+impl ReleaseSources {
+    pub fn new(default: Arc<dyn ReleaseSource>) -> Self {
+        Self {
+            default,
+            modules: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the sources in the configuration file, if any, or else GitHub.
+    pub fn from_config() -> Result<Self, SourcesConfigError> {
+        SourcesConfig::from_config().map(|config| Self::from(&config))
+    }
+
+    /// Sets the source of a specific module.
+    pub fn with_module(
+        mut self,
+        module_name: impl Into<String>,
+        source: Arc<dyn ReleaseSource>,
+    ) -> Self {
+        self.modules.insert(module_name.into(), source);
+        self
+    }
+
+    /// Returns the source to install the given module from.
+    pub fn for_module(&self, module_name: &str) -> &dyn ReleaseSource {
+        let source = self.modules.get(module_name).unwrap_or(&self.default);
+        tracing::trace!(module_name, ?source, "using release source");
+        source.as_ref()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SourcesConfigError {
+    #[error("failed to read release sources `{0}`: {1}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("invalid release sources `{0}`: {1}")]
+    Parse(PathBuf, #[source] serde_yaml_ng::Error),
+}
+
+/// Compares two versions by their numeric components, so that `0.10.0`
+/// comes after `0.9.1`, and a pre-release before its release, ignoring any
+/// leading `v` and build metadata.
+pub(crate) fn compare_versions(a: &str, b: &str) -> core::cmp::Ordering {
+    use core::cmp::Ordering;
+
+    fn parse(version: &str) -> (Vec<u64>, Option<&str>) {
+        let version = version.trim_start_matches('v');
+        let version = version
+            .split_once('+')
+            .map_or(version, |(version, _)| version);
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+        let core = core
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect();
+        (core, pre)
+    }
+
+    let (a_core, a_pre) = parse(a);
+    let (b_core, b_pre) = parse(b);
+    a_core.cmp(&b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(b),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use core::cmp::Ordering;
+
+    #[test]
+    fn compare() {
+        assert_eq!(compare_versions("0.10.0", "0.9.1"), Ordering::Greater);
+        assert_eq!(compare_versions("v1.2.3", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0-rc.1", "1.2.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0+build", "1.2.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: SourcesConfig = serde_yaml_ng::from_str(
+            r#"
+default:
+  type: http
+  url: https://mirror.example.org/modules/
+modules:
+  internal:
+    type: dir
+    path: /srv/modules
+  ghcr:
+    type: oci
+    url: https://ghcr.io
+    namespace: asimov-modules
+  public:
+    type: github
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.default,
+            Some(SourceConfig::Http {
+                url: "https://mirror.example.org/modules/".parse().unwrap()
+            })
+        );
+        assert_eq!(
+            config.modules["internal"],
+            SourceConfig::Dir {
+                path: "/srv/modules".into()
+            }
+        );
+        assert_eq!(config.modules["public"], SourceConfig::GitHub);
+
+        let sources = ReleaseSources::from(&config);
+        assert!(format!("{:?}", sources.for_module("internal")).starts_with("DirSource"));
+        assert!(format!("{:?}", sources.for_module("ghcr")).starts_with("OciSource"));
+        assert!(format!("{:?}", sources.for_module("other")).starts_with("MirrorSource"));
+        assert!(format!("{:?}", ReleaseSources::default().for_module("x")).starts_with("GitHub"));
+    }
+}
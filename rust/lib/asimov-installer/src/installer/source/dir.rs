@@ -0,0 +1,176 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    super::{
+        error::{DeserializeError, DownloadError, FetchChecksumError, FetchError},
+        platform::PlatformInfo,
+        progress::{Phase, ProgressReporter},
+    },
+    ReleaseSource, compare_versions,
+};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use asimov_module::{ModuleManifest, tracing};
+use async_trait::async_trait;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A local directory of module releases, such as a copy of a
+/// [`MirrorSource`](super::MirrorSource) on removable media, with the same
+/// layout.
+///
+/// Without a `{module}/latest` file, the latest version is the greatest of
+/// the version directories of the module.
+#[derive(Clone, Debug)]
+pub struct DirSource {
+    dir: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn release_dir(&self, module_name: &str, version: &str) -> PathBuf {
+        self.dir.join(module_name).join(version)
+    }
+}
+
+/// Reads a file, returning `None` if there is no such file.
+async fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for DirSource {
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_latest_release(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<String, FetchError> {
+        let module_dir = self.dir.join(module_name);
+        if let Some(version) = read_optional(&module_dir.join("latest")).await? {
+            return Ok(version.trim().into());
+        }
+
+        self.fetch_releases(client, module_name)
+            .await?
+            .into_iter()
+            .max_by(|a, b| compare_versions(a, b))
+            .ok_or(FetchError::NotFound)
+    }
+
+    async fn fetch_releases(
+        &self,
+        _client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<Vec<String>, FetchError> {
+        let mut entries = match tokio::fs::read_dir(self.dir.join(module_name)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(FetchError::NotFound);
+            },
+            Err(err) => return Err(err.into()),
+        };
+        let mut versions = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            if let Some(version) = entry.file_name().to_str() {
+                versions.push(version.into());
+            }
+        }
+        Ok(versions)
+    }
+
+    #[tracing::instrument(skip(self, _client))]
+    async fn fetch_module_manifest(
+        &self,
+        _client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleManifest, FetchError> {
+        let path = self.release_dir(module_name, version).join("module.yaml");
+        let content = read_optional(&path).await?.ok_or(FetchError::NotFound)?;
+
+        serde_yaml_ng::from_str(&content)
+            .inspect_err(|err| tracing::debug!(?err, ?path))
+            .map_err(|e| FetchError::Deserialize(DeserializeError::Yaml(e)))
+    }
+
+    async fn fetch_readme(
+        &self,
+        _client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Option<String> {
+        let path = self.release_dir(module_name, version).join("README.md");
+        read_optional(&path)
+            .await
+            .inspect_err(|err| tracing::debug!(?err, ?path))
+            .ok()?
+    }
+
+    #[tracing::instrument(skip(self, _client, platform, dst_dir))]
+    async fn download_asset(
+        &self,
+        _client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        platform: &PlatformInfo,
+        dst_dir: &Path,
+        progress: &ProgressReporter,
+    ) -> Result<(String, PathBuf), DownloadError> {
+        let release_dir = self.release_dir(module_name, version);
+
+        for filename in platform.asset_names(module_name) {
+            let src = release_dir.join(&filename);
+            tracing::debug!("trying asset path {}...", src.display());
+
+            let asset_path = dst_dir.join(&filename);
+            match tokio::fs::copy(&src, &asset_path).await {
+                Ok(bytes) => {
+                    progress.report(Phase::Download, bytes, Some(bytes));
+                    return Ok((src.display().to_string(), asset_path));
+                },
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(DownloadError::NoMatch)
+    }
+
+    async fn fetch_checksum(
+        &self,
+        _client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        let checksum = read_optional(Path::new(&format!("{asset_url}.sha256"))).await?;
+        Ok(checksum.map(|checksum| checksum.trim().into()))
+    }
+
+    async fn fetch_signature(
+        &self,
+        _client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        Ok(read_optional(Path::new(&format!("{asset_url}.sig"))).await?)
+    }
+}
@@ -0,0 +1,78 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    super::{
+        error::{DownloadError, FetchChecksumError, FetchError},
+        github,
+        platform::PlatformInfo,
+        progress::ProgressReporter,
+    },
+    ReleaseSource,
+};
+use alloc::{boxed::Box, string::String};
+use asimov_module::ModuleManifest;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+/// The releases of the modules of the `asimov-modules` organization on
+/// GitHub, in repositories such as `asimov-modules/asimov-foo-module`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitHubSource;
+
+#[async_trait]
+impl ReleaseSource for GitHubSource {
+    async fn fetch_latest_release(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<String, FetchError> {
+        github::fetch_latest_release(client, module_name).await
+    }
+
+    async fn fetch_module_manifest(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleManifest, FetchError> {
+        github::fetch_module_manifest(client, module_name, version).await
+    }
+
+    async fn fetch_readme(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Option<String> {
+        github::fetch_readme(client, module_name, version).await
+    }
+
+    async fn download_asset(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        platform: &PlatformInfo,
+        dst_dir: &Path,
+        progress: &ProgressReporter,
+    ) -> Result<(String, PathBuf), DownloadError> {
+        github::download_matching_asset(client, module_name, version, platform, dst_dir, progress)
+            .await
+    }
+
+    async fn fetch_checksum(
+        &self,
+        client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        github::fetch_checksum(client, asset_url).await
+    }
+
+    async fn fetch_signature(
+        &self,
+        client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        github::fetch_signature(client, asset_url).await
+    }
+}
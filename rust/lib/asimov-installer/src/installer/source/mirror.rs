@@ -0,0 +1,168 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    super::{
+        error::{DeserializeError, DownloadError, FetchChecksumError, FetchError, HttpError},
+        github,
+        platform::PlatformInfo,
+        progress::ProgressReporter,
+    },
+    ReleaseSource,
+};
+use alloc::{boxed::Box, format, string::String};
+use asimov_module::{ModuleManifest, tracing};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+/// A static HTTP mirror of module releases, such as an internal server for
+/// air-gapped networks, with the layout:
+///
+/// ```text
+/// {module}/latest                                 the latest version, such as `0.1.2`
+/// {module}/{version}/module.yaml                  the module manifest
+/// {module}/{version}/README.md                    optional
+/// {module}/{version}/asimov-{module}-module-linux-x86-gnu.tar.gz
+/// {module}/{version}/asimov-{module}-module-linux-x86-gnu.tar.gz.sha256   optional
+/// ```
+///
+/// The release assets are named as on GitHub, so that a mirror can be
+/// filled with the assets of the GitHub releases as they are.
+#[derive(Clone, Debug)]
+pub struct MirrorSource {
+    base_url: url::Url,
+}
+
+impl MirrorSource {
+    pub fn new(mut base_url: url::Url) -> Self {
+        // relative URLs resolve against the base as a directory:
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Self { base_url }
+    }
+
+    pub fn base_url(&self) -> &url::Url {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> Result<url::Url, FetchError> {
+        self.base_url.join(path).map_err(|err| {
+            tracing::debug!(?err, path);
+            FetchError::NotFound
+        })
+    }
+}
+
+/// Sends a GET request, returning `None` if nothing is found.
+pub(super) async fn get(
+    client: &reqwest::Client,
+    url: url::Url,
+) -> Result<Option<reqwest::Response>, HttpError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .inspect_err(|err| tracing::debug!(?err))?;
+
+    match response.status() {
+        status if status == 404 => Ok(None),
+        status if !status.is_success() => Err(HttpError::NotSuccess(status)),
+        _ => Ok(Some(response)),
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for MirrorSource {
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_latest_release(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<String, FetchError> {
+        let url = self.url(&format!("{module_name}/latest"))?;
+        let response = get(client, url).await?.ok_or(FetchError::NotFound)?;
+        let version = response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+        Ok(version.trim().into())
+    }
+
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_module_manifest(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleManifest, FetchError> {
+        let url = self.url(&format!("{module_name}/{version}/module.yaml"))?;
+        let response = get(client, url).await?.ok_or(FetchError::NotFound)?;
+        let content = response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+
+        serde_yaml_ng::from_str(&content)
+            .inspect_err(|err| tracing::debug!(?err, ?content))
+            .map_err(|e| FetchError::Deserialize(DeserializeError::Yaml(e)))
+    }
+
+    async fn fetch_readme(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Option<String> {
+        let url = self
+            .url(&format!("{module_name}/{version}/README.md"))
+            .ok()?;
+        let response = get(client, url)
+            .await
+            .inspect_err(|err| tracing::debug!(?err))
+            .ok()??;
+        response.text().await.ok()
+    }
+
+    #[tracing::instrument(skip(self, client, platform, dst_dir))]
+    async fn download_asset(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        platform: &PlatformInfo,
+        dst_dir: &Path,
+        progress: &ProgressReporter,
+    ) -> Result<(String, PathBuf), DownloadError> {
+        for filename in platform.asset_names(module_name) {
+            let Ok(url) = self.url(&format!("{module_name}/{version}/{filename}")) else {
+                continue;
+            };
+            tracing::debug!("trying asset URL {url}...");
+
+            let asset_path = dst_dir.join(&filename);
+            if !github::download(client, url.as_str(), &asset_path, progress).await? {
+                continue;
+            }
+
+            return Ok((url.into(), asset_path));
+        }
+
+        Err(DownloadError::NoMatch)
+    }
+
+    async fn fetch_checksum(
+        &self,
+        client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        github::fetch_checksum(client, asset_url).await
+    }
+
+    async fn fetch_signature(
+        &self,
+        client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        github::fetch_signature(client, asset_url).await
+    }
+}
@@ -0,0 +1,263 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    super::{
+        error::{DeserializeError, DownloadError, FetchChecksumError, FetchError, HttpError},
+        github,
+        platform::PlatformInfo,
+        progress::ProgressReporter,
+    },
+    ReleaseSource, compare_versions,
+    mirror::get,
+};
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec::Vec};
+use asimov_module::{ModuleManifest, tracing};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The annotation of a layer with its file name.
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// The annotation of a manifest with the version it was released as.
+const VERSION_ANNOTATION: &str = "org.opencontainers.image.version";
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// An OCI registry of module releases as artifacts, such as pushed with
+/// `oras push`, in repositories named `{namespace}/{module}` and tagged
+/// with their versions.
+///
+/// The files of a release are the layers of its manifest, named with the
+/// `org.opencontainers.image.title` annotation: `module.yaml`, optionally
+/// `README.md`, and the release assets, named as on GitHub. The assets are
+/// verified with the digests of their layers.
+///
+/// The latest version is the `org.opencontainers.image.version` annotation
+/// of the `latest` tag, if any, or else the greatest of the tags.
+#[derive(Clone, Debug)]
+pub struct OciSource {
+    url: url::Url,
+    namespace: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OciManifest {
+    #[serde(default)]
+    layers: Vec<OciDescriptor>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OciDescriptor {
+    digest: String,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+impl OciManifest {
+    fn layer(&self, title: &str) -> Option<&OciDescriptor> {
+        self.layers.iter().find(|layer| {
+            layer.annotations.get(TITLE_ANNOTATION).map(String::as_str) == Some(title)
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OciTagList {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl OciSource {
+    pub fn new(url: url::Url, namespace: impl Into<String>) -> Self {
+        Self {
+            url,
+            namespace: namespace.into(),
+        }
+    }
+
+    fn endpoint(&self, module_name: &str, path: &str) -> Result<url::Url, FetchError> {
+        let namespace = self.namespace.trim_matches('/');
+        let repository = if namespace.is_empty() {
+            module_name.into()
+        } else {
+            format!("{namespace}/{module_name}")
+        };
+        self.url
+            .join(&format!("/v2/{repository}/{path}"))
+            .map_err(|err| {
+                tracing::debug!(?err, path);
+                FetchError::NotFound
+            })
+    }
+
+    async fn fetch_manifest(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        reference: &str,
+    ) -> Result<Option<OciManifest>, FetchError> {
+        let url = self.endpoint(module_name, &format!("manifests/{reference}"))?;
+        let response = client
+            .get(url)
+            .header(reqwest::header::ACCEPT, MANIFEST_MEDIA_TYPES)
+            .send()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+
+        match response.status() {
+            status if status == 404 => return Ok(None),
+            status if !status.is_success() => Err(HttpError::NotSuccess(status))?,
+            _ => {},
+        }
+
+        let content = response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+        serde_json::from_str(&content)
+            .inspect_err(|err| tracing::debug!(?err, ?content))
+            .map_err(|e| FetchError::Deserialize(DeserializeError::Json(e)))
+            .map(Some)
+    }
+
+    /// Fetches a file of a release as text.
+    async fn fetch_file(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        title: &str,
+    ) -> Result<String, FetchError> {
+        let manifest = self
+            .fetch_manifest(client, module_name, version)
+            .await?
+            .ok_or(FetchError::NotFound)?;
+        let layer = manifest.layer(title).ok_or(FetchError::NotFound)?;
+        let url = self.endpoint(module_name, &format!("blobs/{}", layer.digest))?;
+        let response = get(client, url).await?.ok_or(FetchError::NotFound)?;
+        Ok(response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?)
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for OciSource {
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_latest_release(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<String, FetchError> {
+        if let Some(manifest) = self.fetch_manifest(client, module_name, "latest").await?
+            && let Some(version) = manifest.annotations.get(VERSION_ANNOTATION)
+        {
+            return Ok(version.clone());
+        }
+
+        self.fetch_releases(client, module_name)
+            .await?
+            .into_iter()
+            .max_by(|a, b| compare_versions(a, b))
+            .ok_or(FetchError::NotFound)
+    }
+
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_releases(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+    ) -> Result<Vec<String>, FetchError> {
+        let url = self.endpoint(module_name, "tags/list")?;
+        let response = get(client, url).await?.ok_or(FetchError::NotFound)?;
+        let content = response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+        let tag_list: OciTagList = serde_json::from_str(&content)
+            .inspect_err(|err| tracing::debug!(?err, ?content))
+            .map_err(|e| FetchError::Deserialize(DeserializeError::Json(e)))?;
+
+        Ok(tag_list
+            .tags
+            .into_iter()
+            .filter(|tag| tag != "latest")
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self, client))]
+    async fn fetch_module_manifest(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleManifest, FetchError> {
+        let content = self
+            .fetch_file(client, module_name, version, "module.yaml")
+            .await?;
+
+        serde_yaml_ng::from_str(&content)
+            .inspect_err(|err| tracing::debug!(?err, ?content))
+            .map_err(|e| FetchError::Deserialize(DeserializeError::Yaml(e)))
+    }
+
+    async fn fetch_readme(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+    ) -> Option<String> {
+        self.fetch_file(client, module_name, version, "README.md")
+            .await
+            .inspect_err(|err| tracing::debug!(?err))
+            .ok()
+    }
+
+    #[tracing::instrument(skip(self, client, platform, dst_dir))]
+    async fn download_asset(
+        &self,
+        client: &reqwest::Client,
+        module_name: &str,
+        version: &str,
+        platform: &PlatformInfo,
+        dst_dir: &Path,
+        progress: &ProgressReporter,
+    ) -> Result<(String, PathBuf), DownloadError> {
+        let manifest = self
+            .fetch_manifest(client, module_name, version)
+            .await?
+            .ok_or(DownloadError::NoMatch)?;
+
+        for filename in platform.asset_names(module_name) {
+            let Some(layer) = manifest.layer(&filename) else {
+                continue;
+            };
+            let url = self.endpoint(module_name, &format!("blobs/{}", layer.digest))?;
+            tracing::debug!("downloading asset {filename} from {url}...");
+
+            let asset_path = dst_dir.join(&filename);
+            if !github::download(client, url.as_str(), &asset_path, progress).await? {
+                continue;
+            }
+
+            return Ok((url.into(), asset_path));
+        }
+
+        Err(DownloadError::NoMatch)
+    }
+
+    async fn fetch_checksum(
+        &self,
+        _client: &reqwest::Client,
+        asset_url: &str,
+    ) -> Result<Option<String>, FetchChecksumError> {
+        // blobs are addressed by their digests:
+        Ok(asset_url
+            .rsplit_once("/blobs/sha256:")
+            .map(|(_, checksum)| checksum.into()))
+    }
+}
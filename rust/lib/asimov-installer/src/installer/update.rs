@@ -0,0 +1,194 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    InstallOptions, Installer,
+    error::{FetchError, UpgradeError},
+    source::compare_versions,
+};
+use alloc::{string::String, vec::Vec};
+use asimov_module::{InstallSource, InvalidModuleName, ModuleName, tracing};
+use asimov_registry::{LockMode, Pin, error as registry};
+use thiserror::Error;
+
+/// A newer release of an installed module, as found by
+/// [`Installer::check_updates`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvailableUpdate {
+    pub name: ModuleName,
+    /// The installed version, unless the module doesn't define one.
+    pub current: Option<String>,
+    pub latest: String,
+    /// The version to upgrade to: the latest release, or else the newest
+    /// release that the pin allows, unless none is newer than the current
+    /// version.
+    pub target: Option<String>,
+    pub pin: Option<Pin>,
+}
+
+impl AvailableUpdate {
+    /// Whether the pin of the module keeps it from every newer release.
+    pub fn is_held_back(&self) -> bool {
+        self.target.is_none()
+    }
+}
+
+impl core::fmt::Display for AvailableUpdate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(current) = &self.current {
+            write!(f, " {current}")?;
+        }
+        let Some(pin) = &self.pin else {
+            return write!(f, " -> {}", self.latest);
+        };
+        match &self.target {
+            Some(target) if *target == self.latest => write!(f, " -> {target}"),
+            Some(target) => write!(f, " -> {target} ({} held back by pin `{pin}`)", self.latest),
+            None => write!(f, " -> {} (held back by pin `{pin}`)", self.latest),
+        }
+    }
+}
+
+/// What [`Installer::check_updates`] found.
+#[derive(Debug, Default)]
+pub struct UpdateCheck {
+    pub updates: Vec<AvailableUpdate>,
+    /// The modules whose releases couldn't be fetched.
+    pub failed: Vec<(ModuleName, FetchError)>,
+}
+
+/// What [`Installer::upgrade_all`] did.
+#[derive(Debug, Default)]
+pub struct UpgradeReport {
+    /// The modules upgraded to their target versions.
+    pub upgraded: Vec<AvailableUpdate>,
+    /// The modules whose pins keep them from every newer release.
+    pub held_back: Vec<AvailableUpdate>,
+    /// The modules whose releases couldn't be fetched.
+    pub failed: Vec<(ModuleName, FetchError)>,
+}
+
+impl Installer {
+    /// Looks for newer releases of all installed modules, without installing
+    /// anything.
+    ///
+    /// Modules installed from local archives or directories are skipped, and
+    /// those whose releases can't be fetched are reported as failed.
+    pub async fn check_updates(&self) -> Result<UpdateCheck, CheckUpdatesError> {
+        let mut pins = self.registry.pins().await?;
+        let mut check = UpdateCheck::default();
+        for manifest in self.registry.installed_modules().await? {
+            let name = ModuleName::try_from(manifest.manifest.name)?;
+            // modules installed before their sources were recorded came from releases
+            if !matches!(manifest.source, None | Some(InstallSource::Release { .. })) {
+                tracing::debug!(%name, "skipping module not installed from a release");
+                continue;
+            }
+            let current = manifest.version;
+            let pin = pins.remove(&name);
+            let result = async {
+                let latest = self.fetch_latest_release(&name).await?;
+                if current
+                    .as_deref()
+                    .is_some_and(|current| compare_versions(&latest, current).is_le())
+                {
+                    return Ok(None);
+                }
+                let target = self
+                    .update_target(&name, current.as_deref(), &latest, pin.as_ref())
+                    .await?;
+                Ok(Some((latest, target)))
+            }
+            .await;
+            match result {
+                Ok(Some((latest, target))) => check.updates.push(AvailableUpdate {
+                    name,
+                    current,
+                    latest,
+                    target,
+                    pin,
+                }),
+                Ok(None) => {},
+                Err(err) => {
+                    tracing::debug!(%name, ?err, "failed to check for updates");
+                    check.failed.push((name, err));
+                },
+            }
+        }
+        Ok(check)
+    }
+
+    /// Returns the latest release, or else the newest release that the pin
+    /// allows, if it's newer than the current version.
+    async fn update_target(
+        &self,
+        name: &ModuleName,
+        current: Option<&str>,
+        latest: &str,
+        pin: Option<&Pin>,
+    ) -> Result<Option<String>, FetchError> {
+        let Some(pin) = pin.filter(|pin| !pin.allows(latest)) else {
+            return Ok(Some(latest.into()));
+        };
+        Ok(self
+            .fetch_releases(name)
+            .await?
+            .into_iter()
+            .filter(|version| pin.allows(version))
+            .filter(|version| {
+                current.is_none_or(|current| compare_versions(version, current).is_gt())
+            })
+            .max_by(|a, b| compare_versions(a, b)))
+    }
+
+    /// Upgrades all installed modules to their latest release, or the newest
+    /// release that their pins allow, reporting those that no newer release
+    /// is allowed for as held back. The version of `options` is ignored.
+    pub async fn upgrade_all(
+        &self,
+        options: &InstallOptions,
+    ) -> Result<UpgradeReport, UpgradeAllError> {
+        let _lock = self.registry.lock(LockMode::Exclusive).await?;
+        let check = self.check_updates().await?;
+        let mut report = UpgradeReport {
+            failed: check.failed,
+            ..Default::default()
+        };
+        for update in check.updates {
+            let Some(target) = &update.target else {
+                tracing::debug!(%update, "holding back module upgrade");
+                report.held_back.push(update);
+                continue;
+            };
+            let options = InstallOptions {
+                version: Some(target.clone()),
+                ..options.clone()
+            };
+            self.upgrade_module(&update.name, &options)
+                .await
+                .map_err(|e| UpgradeAllError::Upgrade(update.name.clone(), e))?;
+            report.upgraded.push(update);
+        }
+        Ok(report)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CheckUpdatesError {
+    #[error("unable to read installed modules: {0}")]
+    InstalledModules(#[from] registry::InstalledModulesError),
+    #[error("invalid name of installed module: {0}")]
+    InvalidModuleName(#[from] InvalidModuleName),
+    #[error("unable to read module pins: {0}")]
+    Pins(#[from] registry::PinError),
+}
+
+#[derive(Debug, Error)]
+pub enum UpgradeAllError {
+    #[error(transparent)]
+    Check(#[from] CheckUpdatesError),
+    #[error("failed to upgrade module `{0}`: {1}")]
+    Upgrade(ModuleName, #[source] UpgradeError),
+    #[error(transparent)]
+    Lock(#[from] registry::LockError),
+}
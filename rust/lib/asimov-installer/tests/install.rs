@@ -0,0 +1,769 @@
+// This is free and unencumbered software released into the public domain.
+
+use asimov_installer::{
+    AvailableUpdate, InstallOptions, Installer, ReleaseSources, detect_platform,
+    error::{
+        InstallError, InstallLockedError, PreinstallError, UpgradeError, VerifySignatureError,
+    },
+    lock::{LockError, LockMismatch},
+    plan::PlanError,
+    source::{DirSource, MirrorSource},
+};
+use asimov_module::{Index, IndexSignature, InstallSource};
+use asimov_registry::Registry;
+use sha2::{Digest, Sha256};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+const MANIFEST: &str = r#"
+name: example
+label: Example
+provides:
+  programs:
+    - asimov-example-fetcher
+"#;
+
+/// Creates a release asset with the manifest and a program printing the
+/// given version, returning its checksum and content.
+fn create_asset(module: &str, manifest: &str, version: &str) -> (String, Vec<u8>) {
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    let program = format!("#!/bin/sh\necho {version}\n");
+    let mut header = tar::Header::new_gnu();
+    header.set_size(program.len() as u64);
+    header.set_mode(0o755);
+    archive
+        .append_data(
+            &mut header,
+            format!("asimov-{module}-fetcher"),
+            program.as_bytes(),
+        )
+        .unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    archive
+        .append_data(&mut header, ".asimov/module.yaml", manifest.as_bytes())
+        .unwrap();
+    let asset = archive.into_inner().unwrap().finish().unwrap();
+
+    (format!("{:x}", Sha256::digest(&asset)), asset)
+}
+
+/// Creates the releases `0.1.0` and `0.2.0` of the `example` module in the
+/// mirror layout, without a `latest` file.
+fn create_releases(dir: &Path) {
+    for version in ["0.1.0", "0.2.0"] {
+        create_release(dir, "example", MANIFEST, version);
+    }
+}
+
+/// Creates a release of a module in the mirror layout.
+fn create_release(dir: &Path, module: &str, manifest: &str, version: &str) {
+    let asset_name = detect_platform()
+        .asset_names(module)
+        .into_iter()
+        .find(|name| name.ends_with(".tar.gz"))
+        .unwrap();
+
+    let release_dir = dir.join(module).join(version);
+    std::fs::create_dir_all(&release_dir).unwrap();
+    std::fs::write(release_dir.join("module.yaml"), manifest).unwrap();
+
+    let (checksum, asset) = create_asset(module, manifest, version);
+    std::fs::write(release_dir.join(&asset_name), asset).unwrap();
+    std::fs::write(
+        release_dir.join(format!("{asset_name}.sha256")),
+        format!("{checksum}  {asset_name}\n"),
+    )
+    .unwrap();
+}
+
+/// Serves the files of a directory, standing in for an HTTP mirror.
+async fn serve(dir: &Path) -> url::Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mirror/", listener.local_addr().unwrap());
+
+    let dir = dir.to_owned();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..len]);
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+            let file = path
+                .strip_prefix("/mirror/")
+                .and_then(|path| std::fs::read(dir.join(path)).ok());
+            let (status, body) = match file {
+                Some(body) => ("200 OK", body),
+                None => ("404 Not Found", Vec::new()),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+
+    url.parse().unwrap()
+}
+
+/// Serves the files of a directory like [`serve`], but cuts the first
+/// download of an asset short, and honors range requests, recording them.
+async fn serve_interrupted(dir: &Path) -> (url::Url, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mirror/", listener.local_addr().unwrap());
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+
+    let dir = dir.to_owned();
+    let recorded = ranges.clone();
+    tokio::spawn(async move {
+        let mut interrupted = false;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            let range = request
+                .lines()
+                .find_map(|line| {
+                    line.to_lowercase()
+                        .strip_prefix("range: bytes=")
+                        .map(String::from)
+                })
+                .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+
+            let Some(body) = path
+                .strip_prefix("/mirror/")
+                .and_then(|path| std::fs::read(dir.join(path)).ok())
+            else {
+                let head =
+                    "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+                stream.write_all(head.as_bytes()).await.unwrap();
+                continue;
+            };
+
+            let (status, body) = match range {
+                Some(offset) => {
+                    recorded.lock().unwrap().push(format!("bytes={offset}-"));
+                    ("206 Partial Content", body[offset..].to_vec())
+                },
+                None => ("200 OK", body),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            if path.ends_with(".gz") && !interrupted {
+                interrupted = true;
+                stream.write_all(&body[..body.len() / 2]).await.unwrap();
+                stream.flush().await.unwrap();
+                continue;
+            }
+            stream.write_all(&body).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+
+    (url.parse().unwrap(), ranges)
+}
+
+async fn setup(sources: ReleaseSources) -> (tempfile::TempDir, Registry, Installer) {
+    let root = tempfile::tempdir().unwrap();
+    let registry = Registry::new(root.path(), Default::default());
+    let installer = Installer::new(reqwest::Client::new(), registry.clone()).with_sources(sources);
+    (root, registry, installer)
+}
+
+#[tokio::test]
+async fn install_from_dir_source() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+
+    let source = Arc::new(DirSource::new(releases.path()));
+    let (_root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let module = "example".parse().unwrap();
+
+    // the greatest version, without a `latest` file:
+    assert_eq!(
+        installer.fetch_latest_release(&module).await.unwrap(),
+        "0.2.0"
+    );
+
+    let options = InstallOptions::builder().version("0.1.0").build();
+    installer.install_module(&module, &options).await.unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert!(
+        registry
+            .module_dir(&module)
+            .join("bin/asimov-example-fetcher")
+            .is_file()
+    );
+}
+
+#[tokio::test]
+async fn install_from_mirror() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    std::fs::write(releases.path().join("example/latest"), "0.1.0\n").unwrap();
+    let url = serve(releases.path()).await;
+
+    // only the `example` module comes from the mirror:
+    let sources =
+        ReleaseSources::default().with_module("example", Arc::new(MirrorSource::new(url)));
+    let (_root, registry, installer) = setup(sources).await;
+    let module = "example".parse().unwrap();
+
+    installer
+        .install_module(&module, &InstallOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+
+    // a corrupted asset fails its checksum:
+    let release_dir = releases.path().join("example/0.2.0");
+    for entry in std::fs::read_dir(&release_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            std::fs::write(&path, b"corrupted").unwrap();
+        }
+    }
+    let options = InstallOptions::builder().version("0.2.0").build();
+    assert!(installer.upgrade_module(&module, &options).await.is_err());
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+}
+
+#[tokio::test]
+async fn install_offline() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let release_dir = releases.path().join("example/0.2.0");
+    let archive = std::fs::read_dir(&release_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "gz"))
+        .unwrap();
+
+    // no release source is ever asked:
+    let source = Arc::new(DirSource::new(releases.path().join("missing")));
+    let (_root, registry, installer) = setup(ReleaseSources::new(source)).await;
+
+    let options = InstallOptions::builder().version("0.2.0").build();
+    let module = installer
+        .install_from_archive(&archive, &options)
+        .await
+        .unwrap();
+    assert_eq!(module.as_str(), "example");
+    let manifest = registry.read_manifest(&module).await.unwrap();
+    assert_eq!(manifest.version.as_deref(), Some("0.2.0"));
+    assert_eq!(
+        manifest.source,
+        Some(InstallSource::Archive {
+            path: archive.display().to_string()
+        })
+    );
+
+    // an unpacked directory, which is left as it is:
+    let unpacked = tempfile::tempdir().unwrap();
+    let program = unpacked.path().join("asimov-example-fetcher");
+    std::fs::create_dir(unpacked.path().join(".asimov")).unwrap();
+    std::fs::write(unpacked.path().join(".asimov/module.yaml"), MANIFEST).unwrap();
+    std::fs::write(&program, "#!/bin/sh\n").unwrap();
+
+    installer.uninstall_module(&module).await.unwrap();
+    installer
+        .install_from_dir(unpacked.path(), &InstallOptions::default())
+        .await
+        .unwrap();
+    assert!(program.is_file());
+    let manifest = registry.read_manifest(&module).await.unwrap();
+    assert_eq!(manifest.version, None);
+    assert!(matches!(
+        manifest.source,
+        Some(InstallSource::Directory { .. })
+    ));
+
+    // a corrupted archive fails the checksum next to it:
+    std::fs::write(&archive, b"corrupted").unwrap();
+    let result = installer.install_from_archive(&archive, &options).await;
+    assert!(matches!(
+        result,
+        Err(InstallError::Preinstall(PreinstallError::VerifyChecksum(_)))
+    ));
+}
+
+#[tokio::test]
+async fn install_locked() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let sources = || ReleaseSources::new(Arc::new(DirSource::new(releases.path())));
+    let module = "example".parse().unwrap();
+
+    let (_root, registry, installer) = setup(sources()).await;
+    let options = InstallOptions::builder().version("0.1.0").build();
+    installer.install_module(&module, &options).await.unwrap();
+    registry.enable_module(&module).await.unwrap();
+
+    let lockfile = installer.lockfile().await.unwrap();
+    let locked = &lockfile.modules["example"];
+    assert_eq!(locked.version, "0.1.0");
+    assert!(locked.enabled);
+
+    // another machine, where the latest version would be 0.2.0:
+    let (_root, registry, other) = setup(sources()).await;
+    other.install_locked(lockfile.clone()).await.unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert!(registry.is_module_enabled(&module).await.unwrap());
+    assert_eq!(other.lockfile().await.unwrap(), lockfile);
+
+    // a release that changed since it was locked:
+    let release_dir = releases.path().join("example/0.1.0");
+    let (_, asset) = create_asset("example", MANIFEST, "0.1.1");
+    for entry in std::fs::read_dir(&release_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            std::fs::write(&path, &asset).unwrap();
+        }
+    }
+    let (_root, registry, other) = setup(sources()).await;
+    let result = other.install_locked(lockfile).await;
+    assert!(
+        matches!(
+            &result,
+            Err(InstallLockedError::Install(_, err)) if matches!(
+                **err,
+                InstallError::Preinstall(PreinstallError::Locked(LockMismatch::Changed {
+                    field: "checksum",
+                    ..
+                }))
+            )
+        ),
+        "{result:?}"
+    );
+    assert!(!registry.is_module_installed(&module).await.unwrap());
+
+    // modules installed from local directories can't be locked:
+    let unpacked = tempfile::tempdir().unwrap();
+    std::fs::write(unpacked.path().join("module.yaml"), MANIFEST).unwrap();
+    std::fs::write(unpacked.path().join("asimov-example-fetcher"), "").unwrap();
+    installer.uninstall_module(&module).await.unwrap();
+    installer
+        .install_from_dir(unpacked.path(), &InstallOptions::default())
+        .await
+        .unwrap();
+    assert!(matches!(
+        installer.lockfile().await,
+        Err(LockError::NotReproducible(..))
+    ));
+}
+
+#[tokio::test]
+async fn upgrade_and_rollback() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+
+    let source = Arc::new(DirSource::new(releases.path()));
+    let (root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let module = "example".parse().unwrap();
+    let program = root.path().join("libexec/asimov-example-fetcher");
+
+    let options = InstallOptions::builder().version("0.1.0").build();
+    installer.install_module(&module, &options).await.unwrap();
+    registry.enable_module(&module).await.unwrap();
+
+    installer
+        .upgrade_module(&module, &InstallOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.2.0")
+    );
+    assert!(registry.is_module_enabled(&module).await.unwrap());
+    assert!(std::fs::read_to_string(&program).unwrap().contains("0.2.0"));
+
+    installer.rollback_module(&module).await.unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert!(registry.is_module_enabled(&module).await.unwrap());
+    assert!(std::fs::read_to_string(&program).unwrap().contains("0.1.0"));
+
+    // a failed upgrade leaves the installed version in place:
+    let options = InstallOptions::builder().version("0.3.0").build();
+    assert!(installer.upgrade_module(&module, &options).await.is_err());
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert!(registry.is_module_enabled(&module).await.unwrap());
+    assert!(std::fs::read_to_string(&program).unwrap().contains("0.1.0"));
+}
+
+#[tokio::test]
+async fn install_verified() {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let release_dir = releases.path().join("example/0.1.0");
+    let asset = std::fs::read_dir(&release_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "gz"))
+        .unwrap();
+    std::fs::remove_file(format!("{}.sha256", asset.display())).unwrap();
+
+    let source = Arc::new(DirSource::new(releases.path()));
+    let (_root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let module = "example".parse().unwrap();
+
+    let options = InstallOptions::builder()
+        .version("0.1.0")
+        .require_checksums(true)
+        .build();
+    assert!(matches!(
+        installer.install_module(&module, &options).await,
+        Err(InstallError::Preinstall(PreinstallError::NoChecksum(_)))
+    ));
+
+    // the index pins the key of the publisher:
+    let publisher = SigningKey::from_bytes(&[1; 32]);
+    let index: Index = format!(
+        "{{\"name\":\"example\",\"publisher_keys\":[\"{}\"]}}",
+        asimov_id::PublicKey::from(&publisher.verifying_key())
+    )
+    .parse()
+    .unwrap();
+    let installer = installer.with_index(Arc::new(index));
+    let options = InstallOptions::builder().version("0.1.0").build();
+    assert!(matches!(
+        installer.install_module(&module, &options).await,
+        Err(InstallError::Preinstall(PreinstallError::VerifySignature(
+            VerifySignatureError::Unsigned
+        )))
+    ));
+
+    let sign = |key: &SigningKey| {
+        let signature = IndexSignature::from(key.sign(&std::fs::read(&asset).unwrap()));
+        std::fs::write(
+            format!("{}.sig", asset.display()),
+            format!("untrusted comment: signature of example 0.1.0\n{signature}\n"),
+        )
+        .unwrap();
+    };
+    sign(&SigningKey::from_bytes(&[2; 32]));
+    assert!(matches!(
+        installer.install_module(&module, &options).await,
+        Err(InstallError::Preinstall(PreinstallError::VerifySignature(
+            VerifySignatureError::Untrusted
+        )))
+    ));
+
+    sign(&publisher);
+    installer.install_module(&module, &options).await.unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+}
+
+#[tokio::test]
+async fn install_with_dependencies() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let requires = |name: &str, modules: &[&str]| {
+        format!(
+            "name: {name}\nprovides:\n  programs:\n    - asimov-{name}-fetcher\nrequires:\n  modules: [{}]\n",
+            modules.join(", ")
+        )
+    };
+    create_release(
+        releases.path(),
+        "http",
+        &requires("http", &["example"]),
+        "1.0.0",
+    );
+    create_release(releases.path(), "json", &requires("json", &[]), "1.0.0");
+    create_release(
+        releases.path(),
+        "app",
+        &requires("app", &["http", "json"]),
+        "1.0.0",
+    );
+
+    let source = Arc::new(DirSource::new(releases.path()));
+    let (_root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let example = "example".parse().unwrap();
+    let app = "app".parse().unwrap();
+
+    let options = InstallOptions::builder().version("0.1.0").build();
+    installer.install_module(&example, &options).await.unwrap();
+
+    let options = InstallOptions::builder().jobs(2).build();
+    let plan = installer.plan(&app, &options).await.unwrap();
+    assert_eq!(
+        plan.to_string(),
+        "install http 1.0.0\n\
+         install json 1.0.0\n\
+         install app 1.0.0 (after http, json)\n\
+         keep example (already installed)\n"
+    );
+    // a dry run installs nothing:
+    assert!(!registry.is_module_installed(&app).await.unwrap());
+
+    installer.install_plan(&plan, &options).await.unwrap();
+    for module in ["app", "http", "json"] {
+        let module = module.parse().unwrap();
+        assert!(registry.is_module_installed(&module).await.unwrap());
+        let manifest = registry.read_manifest(&module).await.unwrap();
+        assert_eq!(manifest.dependency, module != app);
+    }
+    assert_eq!(
+        registry.module_version(&example).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+
+    // a cycle is found before anything is installed:
+    create_release(releases.path(), "a", &requires("a", &["b"]), "1.0.0");
+    create_release(releases.path(), "b", &requires("b", &["a"]), "1.0.0");
+    let a = "a".parse().unwrap();
+    assert!(matches!(
+        installer
+            .install_module(&a, &InstallOptions::default())
+            .await,
+        Err(InstallError::Plan(PlanError::Cycle(_)))
+    ));
+    assert!(!registry.is_module_installed(&a).await.unwrap());
+
+    // so is one through a module installed from a directory, whose other
+    // requirements are planned too
+    create_release(releases.path(), "c", &requires("c", &["local"]), "1.0.0");
+    let unpacked = tempfile::tempdir().unwrap();
+    std::fs::write(
+        unpacked.path().join("module.yaml"),
+        requires("local", &["json", "c"]),
+    )
+    .unwrap();
+    assert!(matches!(
+        installer
+            .install_from_dir(unpacked.path(), &InstallOptions::default())
+            .await,
+        Err(InstallError::Plan(PlanError::Cycle(_)))
+    ));
+
+    create_release(releases.path(), "d", &requires("d", &[]), "1.0.0");
+    std::fs::write(
+        unpacked.path().join("module.yaml"),
+        requires("local", &["json", "d"]),
+    )
+    .unwrap();
+    std::fs::write(unpacked.path().join("asimov-local-fetcher"), "#!/bin/sh\n").unwrap();
+    let local = installer
+        .install_from_dir(unpacked.path(), &InstallOptions::default())
+        .await
+        .unwrap();
+    assert!(!registry.read_manifest(&local).await.unwrap().dependency);
+    let d = "d".parse().unwrap();
+    assert!(registry.read_manifest(&d).await.unwrap().dependency);
+
+    // a failed dependency lets the others being installed finish
+    create_release(releases.path(), "e", &requires("e", &[]), "1.0.0");
+    create_release(releases.path(), "f", &requires("f", &[]), "1.0.0");
+    create_release(releases.path(), "g", &requires("g", &["e", "f"]), "1.0.0");
+    for entry in std::fs::read_dir(releases.path().join("e/1.0.0")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.file_name().unwrap() != "module.yaml" {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+    let g = "g".parse().unwrap();
+    let options = InstallOptions::builder().jobs(2).build();
+    assert!(matches!(
+        installer.install_module(&g, &options).await,
+        Err(InstallError::Preinstall(PreinstallError::Dependency(name, _))) if name == "e"
+    ));
+    assert!(
+        registry
+            .is_module_installed(&"f".parse().unwrap())
+            .await
+            .unwrap()
+    );
+    assert!(!registry.is_module_installed(&g).await.unwrap());
+}
+
+#[tokio::test]
+async fn install_resumed() {
+    use asimov_installer::{Phase, Progress};
+
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let (url, ranges) = serve_interrupted(releases.path()).await;
+
+    let source = Arc::new(MirrorSource::new(url));
+    let (root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let reports = Arc::new(Mutex::new(Vec::<Progress>::new()));
+    let installer = installer.with_progress({
+        let reports = reports.clone();
+        move |progress| reports.lock().unwrap().push(progress.clone())
+    });
+    let module = "example".parse().unwrap();
+
+    let options = InstallOptions::builder().version("0.2.0").build();
+    installer.install_module(&module, &options).await.unwrap();
+    assert_eq!(
+        registry.module_version(&module).await.unwrap().as_deref(),
+        Some("0.2.0")
+    );
+
+    // the download was resumed where it was cut off:
+    let ranges = ranges.lock().unwrap();
+    assert_eq!(ranges.len(), 1);
+    assert_ne!(ranges[0], "bytes=0-");
+
+    let reports = reports.lock().unwrap();
+    assert!(reports.iter().all(|progress| progress.module == "example"));
+    let downloaded = reports
+        .iter()
+        .rfind(|progress| progress.phase == Phase::Download)
+        .unwrap();
+    assert_eq!(Some(downloaded.bytes), downloaded.total);
+    let phases: Vec<Phase> = reports
+        .iter()
+        .map(|progress| progress.phase)
+        .filter(|phase| *phase != Phase::Download)
+        .collect();
+    assert_eq!(phases, [Phase::Verify, Phase::Extract, Phase::Install]);
+
+    // the download is removed once installed:
+    let downloads = root.path().join("modules/.downloads");
+    assert_eq!(std::fs::read_dir(downloads).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn upgrade_all_with_pins() {
+    let releases = tempfile::tempdir().unwrap();
+    create_releases(releases.path());
+    let manifest = "name: other\nprovides:\n  programs:\n    - asimov-other-fetcher\n";
+    for version in ["1.0.0", "1.1.0", "2.0.0"] {
+        create_release(releases.path(), "other", manifest, version);
+    }
+    let manifest = "name: gone\nprovides:\n  programs:\n    - asimov-gone-fetcher\n";
+    create_release(releases.path(), "gone", manifest, "1.0.0");
+
+    let source = Arc::new(DirSource::new(releases.path()));
+    let (_root, registry, installer) = setup(ReleaseSources::new(source)).await;
+    let example = "example".parse().unwrap();
+    let other = "other".parse().unwrap();
+
+    let options = InstallOptions::builder().version("0.1.0").build();
+    installer.install_module(&example, &options).await.unwrap();
+    let options = InstallOptions::builder().version("1.0.0").build();
+    installer.install_module(&other, &options).await.unwrap();
+    registry
+        .pin_module(&example, "0.1.0".parse().unwrap())
+        .await
+        .unwrap();
+    registry
+        .pin_module(&other, "^1".parse().unwrap())
+        .await
+        .unwrap();
+
+    // a module whose releases are gone, and one installed locally
+    let gone = "gone".parse().unwrap();
+    installer
+        .install_module(&gone, &InstallOptions::default())
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(releases.path().join("gone")).unwrap();
+    let unpacked = tempfile::tempdir().unwrap();
+    std::fs::create_dir(unpacked.path().join(".asimov")).unwrap();
+    std::fs::write(unpacked.path().join(".asimov/module.yaml"), "name: local\n").unwrap();
+    installer
+        .install_from_dir(unpacked.path(), &InstallOptions::default())
+        .await
+        .unwrap();
+
+    // checking installs nothing
+    let check = installer.check_updates().await.unwrap();
+    assert_eq!(check.failed.len(), 1);
+    assert_eq!(check.failed[0].0, gone);
+    let updates = check.updates;
+    assert_eq!(updates.len(), 2);
+    assert!(updates[0].is_held_back());
+    assert_eq!(
+        updates[0].to_string(),
+        "example 0.1.0 -> 0.2.0 (held back by pin `0.1.0`)"
+    );
+    assert!(!updates[1].is_held_back());
+    assert_eq!(
+        updates[1].to_string(),
+        "other 1.0.0 -> 1.1.0 (2.0.0 held back by pin `^1`)"
+    );
+    assert_eq!(
+        registry.module_version(&other).await.unwrap().as_deref(),
+        Some("1.0.0")
+    );
+
+    // a pin keeps a module from being upgraded, even on request
+    assert!(matches!(
+        installer
+            .upgrade_module(&example, &InstallOptions::default())
+            .await,
+        Err(UpgradeError::Pinned { .. })
+    ));
+
+    let report = installer
+        .upgrade_all(&InstallOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.upgraded.len(), 1);
+    assert_eq!(report.upgraded[0].name, other);
+    assert_eq!(report.held_back.len(), 1);
+    assert_eq!(report.held_back[0].name, example);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(
+        registry.module_version(&example).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert_eq!(
+        registry.module_version(&other).await.unwrap().as_deref(),
+        Some("1.1.0")
+    );
+    // nothing newer is allowed by the pin anymore
+    let updates = installer.check_updates().await.unwrap().updates;
+    assert_eq!(updates.len(), 2);
+    assert!(updates.iter().all(AvailableUpdate::is_held_back));
+
+    // the pin goes with the module
+    installer.uninstall_module(&example).await.unwrap();
+    assert_eq!(registry.module_pin(&example).await.unwrap(), None);
+}
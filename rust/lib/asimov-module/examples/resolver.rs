@@ -132,6 +132,9 @@ fn main() {
         println!("answers:");
         for answer in answers {
             println!(" - {}", answer.name);
+            for (name, value) in &answer.params {
+                println!("     {name}: {value}");
+            }
         }
     }
 }
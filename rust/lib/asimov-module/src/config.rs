@@ -0,0 +1,696 @@
+// This is free and unencumbered software released into the public domain.
+
+#[cfg(feature = "config")]
+use crate::WriteVarError;
+use crate::{ConfigurationVariable, ModuleManifest, ReadVarError};
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+mod secret;
+pub use secret::*;
+
+mod source;
+pub use source::*;
+
+mod value;
+pub use value::*;
+
+/// The environment variable naming a passphrase file to derive the key for
+/// secret variables from, instead of using the local master key.
+pub const PASSPHRASE_FILE_VAR: &str = "ASIMOV_PASSPHRASE_FILE";
+
+/// The name of the local master key file, in the directory of a [`ConfigStore`].
+pub const MASTER_KEY_FILE_NAME: &str = ".master-key";
+
+/// The name of the file with the name of the profile that a profile inherits
+/// from, in the directory of the profile.
+pub const EXTENDS_FILE_NAME: &str = ".extends";
+
+/// The configured values of module variables, each saved in a file at
+/// `$dir/$profile/$module/$name`.
+///
+/// A profile inherits the values of the profile named in its
+/// [`EXTENDS_FILE_NAME`] file, such as `work` extending `default`. Values in
+/// a project-local directory, at `$project_dir/$module/$name`, take precedence
+/// over those of any profile. See [`ConfigStore::resolve`].
+///
+/// Values of secret variables are encrypted with AES-256-GCM, with a key from
+/// a [`SecretKeySource`]. Secret values saved as plain text, such as by hand,
+/// are still read, but should be saved again with [`ConfigStore::write`].
+///
+/// Saving values, and reading encrypted ones, takes the `config` feature.
+#[derive(Clone, Debug)]
+pub struct ConfigStore {
+    dir: PathBuf,
+    project_dir: Option<PathBuf>,
+    key_source: SecretKeySource,
+}
+
+impl Default for ConfigStore {
+    /// Stores values in `~/.asimov/configs/`, with the project-local
+    /// directory found from the current directory, if any; see
+    /// [`find_project_dir`]. Secret values are encrypted with the passphrase
+    /// file of `$ASIMOV_PASSPHRASE_FILE` if set, or else with the local master
+    /// key.
+    fn default() -> Self {
+        let project_dir = std::env::current_dir()
+            .ok()
+            .and_then(|dir| find_project_dir(&dir));
+        let store = Self::new(asimov_env::paths::asimov_root().join("configs"))
+            .with_project_dir(project_dir);
+        match getenv::var(PASSPHRASE_FILE_VAR) {
+            Some(path) => store.with_key_source(SecretKeySource::PassphraseFile(path.into())),
+            None => store,
+        }
+    }
+}
+
+impl ConfigStore {
+    /// Stores values in the given directory, without a project-local
+    /// directory, encrypting secret values with the master key in
+    /// [`MASTER_KEY_FILE_NAME`] there.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let key_source = SecretKeySource::MasterKeyFile(dir.join(MASTER_KEY_FILE_NAME));
+        Self {
+            dir,
+            project_dir: None,
+            key_source,
+        }
+    }
+
+    pub fn with_project_dir(mut self, project_dir: Option<PathBuf>) -> Self {
+        self.project_dir = project_dir;
+        self
+    }
+
+    pub fn with_key_source(mut self, key_source: SecretKeySource) -> Self {
+        self.key_source = key_source;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn project_dir(&self) -> Option<&Path> {
+        self.project_dir.as_deref()
+    }
+
+    pub fn key_source(&self) -> &SecretKeySource {
+        &self.key_source
+    }
+
+    /// Returns the profile that a profile inherits from, if any.
+    pub fn extends(&self, profile: &str) -> Result<Option<String>, ProfileError> {
+        let path = self.dir.join(profile).join(EXTENDS_FILE_NAME);
+        let parent = match fs::read_to_string(&path) {
+            Ok(content) => String::from(content.trim()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(ProfileError::Io(path, err)),
+        };
+        if parent.is_empty() {
+            return Ok(None);
+        }
+        if !is_profile_name(&parent) {
+            return Err(ProfileError::InvalidName(parent));
+        }
+        Ok(Some(parent))
+    }
+
+    /// Makes a profile inherit from another one, or from none.
+    #[cfg(feature = "config")]
+    pub fn set_extends(&self, profile: &str, parent: Option<&str>) -> Result<(), ProfileError> {
+        let path = self.dir.join(profile).join(EXTENDS_FILE_NAME);
+        let Some(parent) = parent else {
+            return match fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    Err(ProfileError::Io(path, err))
+                },
+                _ => Ok(()),
+            };
+        };
+
+        if !is_profile_name(parent) {
+            return Err(ProfileError::InvalidName(parent.into()));
+        }
+        if self.profiles(parent)?.iter().any(|name| name == profile) {
+            return Err(ProfileError::Cycle(profile.into()));
+        }
+        write_private_file(&path, parent.as_bytes()).map_err(|err| ProfileError::Io(path, err))
+    }
+
+    /// Returns the profile followed by the profiles it inherits from, in the
+    /// order that their values take precedence.
+    pub fn profiles(&self, profile: &str) -> Result<Vec<String>, ProfileError> {
+        let mut profiles = alloc::vec![String::from(profile)];
+        let mut current = String::from(profile);
+        while let Some(parent) = self.extends(&current)? {
+            if profiles.contains(&parent) {
+                return Err(ProfileError::Cycle(parent));
+            }
+            profiles.push(parent.clone());
+            current = parent;
+        }
+        Ok(profiles)
+    }
+
+    /// Returns the value of a variable, with where it comes from: from its
+    /// environment variable if set, or else from the project-local directory,
+    /// or else from the profile or the closest profile it inherits from, or
+    /// else from its default value.
+    pub fn resolve(
+        &self,
+        profile: &str,
+        module: &str,
+        var: &ConfigurationVariable,
+    ) -> Result<ResolvedValue, ReadVarError> {
+        let resolved = |value, source| ResolvedValue {
+            value,
+            source,
+            secret: var.secret,
+        };
+
+        if let Some(name) = var.environment.as_deref()
+            && let Ok(value) = std::env::var(name)
+        {
+            return Ok(resolved(value, ValueSource::Environment(name.into())));
+        }
+
+        if let Some(project_dir) = &self.project_dir {
+            let path = project_dir.join(module).join(&var.name);
+            if let Some(value) = self.read_file(&path, module, var)? {
+                return Ok(resolved(value, ValueSource::Project(project_dir.clone())));
+            }
+        }
+
+        for profile in self.profiles(profile)? {
+            if let Some(value) = self.read(&profile, module, var)? {
+                return Ok(resolved(value, ValueSource::Profile(profile)));
+            }
+        }
+
+        match &var.default_value {
+            Some(value) => Ok(resolved(value.clone(), ValueSource::Default)),
+            None => Err(ReadVarError::UnconfiguredVar(var.name.clone())),
+        }
+    }
+
+    /// The file of the value of a variable of a module.
+    pub fn path(&self, profile: &str, module: &str, name: &str) -> PathBuf {
+        self.dir.join(profile).join(module).join(name)
+    }
+
+    /// Returns the value of a variable configured in exactly the given
+    /// profile, decrypting it if needed, or `None` if it isn't configured
+    /// there. See [`ConfigStore::resolve`] for inherited values.
+    pub fn read(
+        &self,
+        profile: &str,
+        module: &str,
+        var: &ConfigurationVariable,
+    ) -> Result<Option<String>, ReadVarError> {
+        self.read_file(&self.path(profile, module, &var.name), module, var)
+    }
+
+    fn read_file(
+        &self,
+        path: &Path,
+        module: &str,
+        var: &ConfigurationVariable,
+    ) -> Result<Option<String>, ReadVarError> {
+        let io_error = |source| ReadVarError::Io {
+            name: var.name.clone(),
+            source,
+        };
+
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(io_error(err)),
+        };
+
+        if !secret::is_encrypted(&content) {
+            if var.secret {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    ?path,
+                    "secret variable is saved as plain text, configure it again to encrypt it"
+                );
+            }
+            return String::from_utf8(content)
+                .map(Some)
+                .map_err(|err| io_error(io::Error::new(io::ErrorKind::InvalidData, err)));
+        }
+
+        let secret_error = |source| ReadVarError::Secret {
+            name: var.name.clone(),
+            source,
+        };
+        let value = self
+            .key_source
+            .decrypt(&context(module, &var.name), &content)
+            .map_err(secret_error)?;
+        String::from_utf8(value.to_vec())
+            .map(Some)
+            .map_err(|err| io_error(io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Saves the value of a variable, encrypted if the variable is secret,
+    /// after checking it against the type and the constraints of the variable.
+    ///
+    /// Only the owner can read the saved value, or list the directories it is
+    /// saved in.
+    #[cfg(feature = "config")]
+    pub fn write(
+        &self,
+        profile: &str,
+        module: &str,
+        var: &ConfigurationVariable,
+        value: &str,
+    ) -> Result<(), WriteVarError> {
+        let path = self.path(profile, module, &var.name);
+
+        var.parse_value(value)
+            .map_err(|source| WriteVarError::Invalid {
+                name: var.name.clone(),
+                source,
+            })?;
+
+        let content = if var.secret {
+            let key = self
+                .key_source
+                .load(true)
+                .map_err(|source| WriteVarError::Secret {
+                    name: var.name.clone(),
+                    source,
+                })?;
+            key.encrypt(&context(module, &var.name), value.as_bytes())
+        } else {
+            value.as_bytes().to_vec()
+        };
+
+        write_private_file(&path, &content).map_err(|source| WriteVarError::Io {
+            name: var.name.clone(),
+            source,
+        })?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?path, secret = var.secret, "saved variable");
+
+        Ok(())
+    }
+
+    /// Removes the configured value of a variable, if any.
+    #[cfg(feature = "config")]
+    pub fn remove(
+        &self,
+        profile: &str,
+        module: &str,
+        var: &ConfigurationVariable,
+    ) -> Result<(), WriteVarError> {
+        match fs::remove_file(self.path(profile, module, &var.name)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(WriteVarError::Io {
+                name: var.name.clone(),
+                source: err,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Variables of a module for display or logging, with the values of secret
+/// variables redacted. See [`ModuleManifest::redacted`].
+#[derive(Clone, Copy)]
+pub struct RedactedVariables<'a> {
+    manifest: &'a ModuleManifest,
+    variables: &'a BTreeMap<String, VariableValue>,
+}
+
+impl core::fmt::Debug for RedactedVariables<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let is_secret = |name: &str| {
+            self.manifest
+                .config
+                .iter()
+                .flat_map(|config| &config.variables)
+                .find(|var| var.name == name)
+                // unknown variables may be anything, so they are redacted too
+                .is_none_or(|var| var.secret)
+        };
+
+        let mut map = f.debug_map();
+        for (name, value) in self.variables {
+            if is_secret(name) {
+                map.entry(name, &format_args!("[REDACTED]"));
+            } else {
+                map.entry(name, value);
+            }
+        }
+        map.finish()
+    }
+}
+
+impl ModuleManifest {
+    /// Wraps variables read with [`ModuleManifest::read_variables`] for
+    /// display or logging, without the values of secret variables.
+    pub fn redacted<'a>(
+        &'a self,
+        variables: &'a BTreeMap<String, VariableValue>,
+    ) -> RedactedVariables<'a> {
+        RedactedVariables {
+            manifest: self,
+            variables,
+        }
+    }
+}
+
+/// Whether a profile name names a directory in the store, rather than a path
+/// elsewhere or a hidden file.
+fn is_profile_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// What an encrypted value is bound to.
+fn context(module: &str, name: &str) -> String {
+    format!("{module}/{name}")
+}
+
+/// Creates a directory with its missing parents, which only the owner can
+/// access when newly created.
+#[cfg(feature = "config")]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)
+}
+
+/// Writes a file that only the owner can read, through a temporary file so
+/// that it is never seen partially written.
+#[cfg(feature = "config")]
+fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)?;
+    }
+
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let result = options.open(&temp_path).and_then(|mut file| {
+        // the mode only applies to new files
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    result
+        .and_then(|_| fs::rename(&temp_path, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("failed to read profile inheritance `{0}`: {1}")]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("invalid profile name `{0}`")]
+    InvalidName(String),
+
+    #[error("profile `{0}` inherits from itself")]
+    Cycle(String),
+}
+
+#[cfg(all(test, feature = "config"))]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn variable(name: &str, secret: bool) -> ConfigurationVariable {
+        ConfigurationVariable {
+            name: name.into(),
+            secret,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn secret_values_are_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(dir.path());
+        let token = variable("token", true);
+        let nickname = variable("nickname", false);
+
+        assert_eq!(store.read("default", "example", &token).unwrap(), None);
+
+        store
+            .write("default", "example", &token, "hunter2")
+            .unwrap();
+        store.write("default", "example", &nickname, "ada").unwrap();
+
+        let saved = fs::read(store.path("default", "example", "token")).unwrap();
+        assert!(is_encrypted(&saved));
+        assert!(!saved.windows(7).any(|window| window == b"hunter2"));
+        let saved = fs::read(store.path("default", "example", "nickname")).unwrap();
+        assert_eq!(saved, b"ada");
+
+        assert_eq!(
+            store.read("default", "example", &token).unwrap().as_deref(),
+            Some("hunter2")
+        );
+        assert_eq!(
+            store
+                .read("default", "example", &nickname)
+                .unwrap()
+                .as_deref(),
+            Some("ada")
+        );
+
+        // an encrypted value can't be moved to another variable:
+        fs::copy(
+            store.path("default", "example", "token"),
+            store.path("default", "example", "password"),
+        )
+        .unwrap();
+        assert!(matches!(
+            store.read("default", "example", &variable("password", true)),
+            Err(ReadVarError::Secret {
+                source: SecretError::Decrypt,
+                ..
+            })
+        ));
+
+        // nor read with another key:
+        fs::write(dir.path().join(MASTER_KEY_FILE_NAME), [0; 32]).unwrap();
+        assert!(matches!(
+            store.read("default", "example", &token),
+            Err(ReadVarError::Secret {
+                source: SecretError::Decrypt,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn passphrase_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase = dir.path().join("passphrase");
+        fs::write(&passphrase, "correct horse battery staple\n").unwrap();
+        let token = variable("token", true);
+
+        let store = |dir: &Path| {
+            ConfigStore::new(dir)
+                .with_key_source(SecretKeySource::PassphraseFile(passphrase.clone()))
+        };
+        store(&dir.path().join("a"))
+            .write("default", "example", &token, "hunter2")
+            .unwrap();
+
+        // the same passphrase reads the value anywhere:
+        let other = dir.path().join("b");
+        fs::create_dir_all(other.join("default/example")).unwrap();
+        fs::copy(
+            dir.path().join("a/default/example/token"),
+            other.join("default/example/token"),
+        )
+        .unwrap();
+        assert_eq!(
+            store(&other)
+                .read("default", "example", &token)
+                .unwrap()
+                .as_deref(),
+            Some("hunter2")
+        );
+        assert!(!dir.path().join("a").join(MASTER_KEY_FILE_NAME).exists());
+
+        // while the master key of another store doesn't:
+        let result = ConfigStore::new(&other).read("default", "example", &token);
+        assert!(matches!(
+            result,
+            Err(ReadVarError::Secret {
+                source: SecretError::Key(..),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn master_key_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = SecretKeySource::MasterKeyFile(dir.path().join(MASTER_KEY_FILE_NAME));
+
+        // processes creating the key at once all end up with the same one
+        let keys: Vec<_> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| source.load(true).unwrap()))
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        let encrypted = keys[0].encrypt("example/token", b"hunter2");
+        for key in &keys {
+            assert_eq!(
+                key.decrypt("example/token", &encrypted).unwrap().as_slice(),
+                b"hunter2"
+            );
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // a key of the wrong length is rejected rather than used
+        fs::write(source.path(), [0; 16]).unwrap();
+        assert!(matches!(source.load(true), Err(SecretError::InvalidKey(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn values_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(dir.path().join("configs"));
+        store
+            .write("default", "example", &variable("token", true), "hunter2")
+            .unwrap();
+
+        let mode = |path: PathBuf| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(store.path("default", "example", "token")), 0o600);
+        assert_eq!(mode(store.dir().join(MASTER_KEY_FILE_NAME)), 0o600);
+        assert_eq!(mode(store.dir().join("default/example")), 0o700);
+    }
+
+    #[test]
+    fn resolve_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let project_dir = dir.path().join("project/.asimov/configs");
+        let store = ConfigStore::new(dir.path().join("configs"))
+            .with_project_dir(Some(project_dir.clone()));
+        let var = |name: &str| ConfigurationVariable {
+            default_value: Some("fallback".into()),
+            ..variable(name, false)
+        };
+        let resolve = |profile, name| store.resolve(profile, "example", &var(name)).unwrap();
+
+        store
+            .write("default", "example", &var("a"), "default a")
+            .unwrap();
+        store
+            .write("default", "example", &var("b"), "default b")
+            .unwrap();
+        store.write("work", "example", &var("a"), "work a").unwrap();
+
+        // without inheritance, a profile falls back to the default value:
+        assert_eq!(resolve("work", "b").value, "fallback");
+        assert_eq!(resolve("work", "b").source, ValueSource::Default);
+
+        store.set_extends("work", Some("default")).unwrap();
+        assert_eq!(store.profiles("work").unwrap(), ["work", "default"]);
+        assert_eq!(
+            resolve("work", "a"),
+            ResolvedValue {
+                value: "work a".into(),
+                source: ValueSource::Profile("work".into()),
+                secret: false,
+            }
+        );
+        assert_eq!(resolve("work", "b").value, "default b");
+        assert_eq!(
+            resolve("work", "b").source,
+            ValueSource::Profile("default".into())
+        );
+
+        // project-local values take precedence over any profile:
+        fs::create_dir_all(project_dir.join("example")).unwrap();
+        fs::write(project_dir.join("example/b"), "project b").unwrap();
+        assert_eq!(resolve("work", "b").value, "project b");
+        assert_eq!(
+            resolve("work", "b").source,
+            ValueSource::Project(project_dir.clone())
+        );
+        assert_eq!(resolve("work", "a").value, "work a");
+
+        assert!(matches!(
+            store.set_extends("default", Some("work")),
+            Err(ProfileError::Cycle(_))
+        ));
+        assert!(matches!(
+            store.set_extends("work", Some("../elsewhere")),
+            Err(ProfileError::InvalidName(_))
+        ));
+        fs::write(dir.path().join("configs/default/.extends"), "work").unwrap();
+        assert!(matches!(
+            store.resolve("work", "example", &var("c")),
+            Err(ReadVarError::Profile(ProfileError::Cycle(_)))
+        ));
+    }
+
+    #[test]
+    fn find_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("project/src/nested");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_dir(&nested), None);
+
+        let configs = dir.path().join("project/.asimov/configs");
+        fs::create_dir_all(&configs).unwrap();
+        assert_eq!(find_project_dir(&nested), Some(configs));
+    }
+
+    #[test]
+    fn resolved_secrets_are_redacted() {
+        let resolved = ResolvedValue {
+            value: "hunter2".into(),
+            source: ValueSource::Profile("default".into()),
+            secret: true,
+        };
+        assert!(!format!("{resolved:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn redacted() {
+        let manifest = ModuleManifest {
+            name: "example".into(),
+            config: Some(crate::Configuration {
+                variables: alloc::vec![variable("nickname", false), variable("token", true)],
+            }),
+            ..Default::default()
+        };
+        let variables = BTreeMap::from([
+            ("nickname".to_string(), VariableValue::String("ada".into())),
+            ("token".to_string(), VariableValue::String("hunter2".into())),
+        ]);
+
+        assert_eq!(
+            format!("{:?}", manifest.redacted(&variables)),
+            r#"{"nickname": String("ada"), "token": [REDACTED]}"#
+        );
+    }
+}
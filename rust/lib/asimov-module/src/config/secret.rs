@@ -0,0 +1,241 @@
+// This is free and unencumbered software released into the public domain.
+
+#[cfg(feature = "config")]
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload, rand_core::RngCore},
+};
+use alloc::vec::Vec;
+use secrecy::zeroize::Zeroizing;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The start of every encrypted value, followed by a format version.
+const MAGIC: &[u8; 8] = b"ASIMOVSV";
+
+#[cfg(feature = "config")]
+const FORMAT_VERSION: u8 = 1;
+
+#[cfg(feature = "config")]
+const NONCE_LEN: usize = 12;
+
+/// The BLAKE3 key derivation contexts.
+#[cfg(feature = "config")]
+const PASSPHRASE_CONTEXT: &str = "asimov-module 2025-10-19 configuration passphrase";
+#[cfg(feature = "config")]
+const ENCRYPTION_CONTEXT: &str = "asimov-module 2025-10-19 configuration encryption";
+
+/// Where the key encrypting secret variables comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretKeySource {
+    /// A file of 32 random bytes, which is created with the first secret
+    /// saved. The key never leaves the machine, so secrets saved with it can
+    /// only be read on that machine.
+    MasterKeyFile(PathBuf),
+
+    /// A file with a passphrase, such as one kept in a password manager and
+    /// shared between machines, so that the same secrets can be read
+    /// anywhere. The key is derived from the passphrase without any key
+    /// stretching, so the passphrase should be long and random.
+    PassphraseFile(PathBuf),
+}
+
+impl SecretKeySource {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::MasterKeyFile(path) | Self::PassphraseFile(path) => path,
+        }
+    }
+
+    /// Decrypts a value saved with [`SecretKey::encrypt`].
+    #[cfg(feature = "config")]
+    pub(crate) fn decrypt(
+        &self,
+        context: &str,
+        data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, SecretError> {
+        self.load(false)?.decrypt(context, data)
+    }
+
+    /// Encrypted values are only read with the `config` feature.
+    #[cfg(not(feature = "config"))]
+    pub(crate) fn decrypt(
+        &self,
+        _context: &str,
+        _data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, SecretError> {
+        Err(SecretError::Unsupported)
+    }
+
+    /// Loads the key, creating a missing master key if `create` is set.
+    #[cfg(feature = "config")]
+    pub(crate) fn load(&self, create: bool) -> Result<SecretKey, SecretError> {
+        use std::fs;
+
+        let path = self.path();
+        let key_error = |err| SecretError::Key(path.into(), err);
+
+        let material = match self {
+            Self::MasterKeyFile(_) => match fs::read(path) {
+                Ok(bytes) => Zeroizing::new(bytes),
+                Err(err) if err.kind() == io::ErrorKind::NotFound && create => {
+                    create_master_key(path).map_err(key_error)?
+                },
+                Err(err) => return Err(key_error(err)),
+            },
+            Self::PassphraseFile(_) => {
+                let passphrase = Zeroizing::new(fs::read_to_string(path).map_err(key_error)?);
+                let passphrase = passphrase.trim();
+                if passphrase.is_empty() {
+                    return Err(SecretError::InvalidKey(path.into()));
+                }
+                Zeroizing::new(blake3::derive_key(PASSPHRASE_CONTEXT, passphrase.as_bytes()).into())
+            },
+        };
+
+        let material: &[u8; 32] = material
+            .as_slice()
+            .try_into()
+            .map_err(|_| SecretError::InvalidKey(path.into()))?;
+        Ok(SecretKey(Zeroizing::new(blake3::derive_key(
+            ENCRYPTION_CONTEXT,
+            material,
+        ))))
+    }
+}
+
+/// Creates a master key file that only its owner can read, unless another
+/// process just did so, in which case that key is used.
+///
+/// The key is written to a temporary file first and then linked into place,
+/// so that the key file is never seen partially written.
+#[cfg(feature = "config")]
+fn create_master_key(path: &Path) -> io::Result<Zeroizing<Vec<u8>>> {
+    use std::{fs, io::Write};
+
+    let mut key = Zeroizing::new(alloc::vec![0u8; 32]);
+    OsRng.fill_bytes(&mut key);
+
+    if let Some(parent) = path.parent() {
+        super::create_private_dir(parent)?;
+    }
+    // unique to the thread too, as threads of a process may race as well
+    let temp_path = path.with_extension(alloc::format!(
+        "{}.{:x}.tmp",
+        std::process::id(),
+        OsRng.next_u64()
+    ));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let result = options
+        .open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(&key)?;
+            file.sync_all()
+        })
+        // unlike renaming, linking fails if another process created the key
+        .and_then(|()| fs::hard_link(&temp_path, path));
+    let _ = fs::remove_file(&temp_path);
+
+    match result {
+        Ok(()) => {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?path, "created the master key for secret variables");
+            Ok(key)
+        },
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            Ok(Zeroizing::new(fs::read(path)?))
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// The key encrypting secret variables with AES-256-GCM.
+#[cfg(feature = "config")]
+pub(crate) struct SecretKey(Zeroizing<[u8; 32]>);
+
+#[cfg(feature = "config")]
+impl core::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("SecretKey([REDACTED])")
+    }
+}
+
+#[cfg(feature = "config")]
+impl SecretKey {
+    /// Encrypts a value, bound to the given context, such as the name of its
+    /// variable, so that it can't be moved to another variable.
+    pub(crate) fn encrypt(&self, context: &str, value: &[u8]) -> Vec<u8> {
+        let cipher = Aes256Gcm::new(self.0.as_ref().into());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: value,
+            aad: context.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(&nonce, payload)
+            .expect("AES-GCM encryption of a value in memory can't fail");
+
+        let mut output = Vec::with_capacity(MAGIC.len() + 1 + NONCE_LEN + ciphertext.len());
+        output.extend_from_slice(MAGIC);
+        output.push(FORMAT_VERSION);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        output
+    }
+
+    pub(crate) fn decrypt(
+        &self,
+        context: &str,
+        data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, SecretError> {
+        let data = data.strip_prefix(MAGIC).ok_or(SecretError::Decrypt)?;
+        let (&version, data) = data.split_first().ok_or(SecretError::Decrypt)?;
+        if version != FORMAT_VERSION {
+            return Err(SecretError::UnsupportedVersion(version));
+        }
+        if data.len() < NONCE_LEN {
+            return Err(SecretError::Decrypt);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+        let cipher = Aes256Gcm::new(self.0.as_ref().into());
+        let payload = Payload {
+            msg: ciphertext,
+            aad: context.as_bytes(),
+        };
+        cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map(Zeroizing::new)
+            .map_err(|_| SecretError::Decrypt)
+    }
+}
+
+/// Whether a stored value was encrypted, rather than saved as plain text.
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("failed to read the key for secret variables `{0}`: {1}")]
+    Key(PathBuf, #[source] io::Error),
+
+    #[error("invalid key for secret variables `{0}`")]
+    InvalidKey(PathBuf),
+
+    #[error("failed to decrypt the value, it was saved with another key or has been modified")]
+    Decrypt,
+
+    #[error("unsupported format version {0} of an encrypted value")]
+    UnsupportedVersion(u8),
+
+    #[error("encrypted values are only read with the `config` feature of asimov-module")]
+    Unsupported,
+}
@@ -0,0 +1,76 @@
+// This is free and unencumbered software released into the public domain.
+
+use alloc::string::String;
+use std::path::{Path, PathBuf};
+
+/// The name of the directory of project-local configuration, such as
+/// `.asimov/configs/` next to a project's `.git/`.
+pub const PROJECT_DIR_NAME: &str = ".asimov";
+
+/// Where the value of a variable comes from, from the highest precedence to
+/// the lowest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueSource {
+    /// The environment variable of the given name.
+    Environment(String),
+    /// The project-local configuration directory at the given path.
+    Project(PathBuf),
+    /// The given profile, which is either the requested profile or one that
+    /// it inherits from.
+    Profile(String),
+    /// The default value in the module manifest.
+    Default,
+}
+
+impl core::fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Environment(name) => write!(f, "environment variable `{name}`"),
+            Self::Project(path) => write!(f, "project configuration `{}`", path.display()),
+            Self::Profile(profile) => write!(f, "profile `{profile}`"),
+            Self::Default => f.write_str("default value"),
+        }
+    }
+}
+
+/// The value of a variable along with where it comes from. The `Debug`
+/// output redacts secret values.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ResolvedValue {
+    pub value: String,
+    pub source: ValueSource,
+    pub secret: bool,
+}
+
+impl core::fmt::Debug for ResolvedValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("ResolvedValue");
+        if self.secret {
+            debug.field("value", &format_args!("[REDACTED]"));
+        } else {
+            debug.field("value", &self.value);
+        }
+        debug
+            .field("source", &self.source)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+/// Finds the project-local configuration directory, `.asimov/configs/`, in
+/// the given directory or the closest of its ancestors that has one.
+///
+/// The user's own `~/.asimov/` is never taken for a project.
+pub fn find_project_dir(start: &Path) -> Option<PathBuf> {
+    let asimov_root = asimov_env::paths::asimov_root();
+    let asimov_root = asimov_root.canonicalize().unwrap_or(asimov_root);
+
+    start.ancestors().find_map(|dir| {
+        let project_dir = dir.join(PROJECT_DIR_NAME);
+        let is_asimov_root = project_dir
+            .canonicalize()
+            .is_ok_and(|project_dir| project_dir == asimov_root);
+        let configs = project_dir.join("configs");
+        (!is_asimov_root && configs.is_dir()).then_some(configs)
+    })
+}
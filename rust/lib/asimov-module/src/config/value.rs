@@ -0,0 +1,279 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{ConfigurationVariable, VariableType};
+use alloc::{format, string::String, vec::Vec};
+use thiserror::Error;
+
+/// The validated value of a [`ConfigurationVariable`], of its
+/// [`VariableType`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariableValue {
+    /// A string or one of the choices of an enum.
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Url(url::Url),
+    Json(serde_json::Value),
+}
+
+impl VariableValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_url(&self) -> Option<&url::Url> {
+        match self {
+            Self::Url(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::Json(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for VariableValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::String(value) => f.write_str(value),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Boolean(value) => write!(f, "{value}"),
+            Self::Url(value) => f.write_str(value.as_str()),
+            Self::Json(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl ConfigurationVariable {
+    /// Parses a value of the variable, checking it against the type and the
+    /// constraints of the variable.
+    pub fn parse_value(&self, input: &str) -> Result<VariableValue, InvalidValue> {
+        if let Some(pattern) = &self.pattern {
+            let regex = regex::Regex::new(&format!("^(?:{pattern})$"))
+                .map_err(|err| InvalidValue::InvalidPattern(pattern.clone(), err))?;
+            if !regex.is_match(input) {
+                return Err(InvalidValue::Pattern(pattern.clone()));
+            }
+        }
+
+        let value = match self.value_type() {
+            VariableType::String => VariableValue::String(input.into()),
+            VariableType::Integer => {
+                let value = input.trim().parse().map_err(|_| InvalidValue::NotInteger)?;
+                if let Some(min) = self.min.filter(|&min| value < min) {
+                    return Err(InvalidValue::TooSmall(min));
+                }
+                if let Some(max) = self.max.filter(|&max| value > max) {
+                    return Err(InvalidValue::TooLarge(max));
+                }
+                return Ok(VariableValue::Integer(value));
+            },
+            VariableType::Boolean => {
+                let value = match input.trim().to_ascii_lowercase().as_str() {
+                    "true" | "yes" | "on" | "1" => true,
+                    "false" | "no" | "off" | "0" => false,
+                    _ => return Err(InvalidValue::NotBoolean),
+                };
+                VariableValue::Boolean(value)
+            },
+            VariableType::Url => VariableValue::Url(input.trim().parse()?),
+            VariableType::Enum => {
+                if !self.choices.iter().any(|choice| choice == input) {
+                    return Err(InvalidValue::NotChoice(self.choices.clone()));
+                }
+                VariableValue::String(input.into())
+            },
+            VariableType::Json => VariableValue::Json(serde_json::from_str(input)?),
+        };
+
+        let length = i64::try_from(input.chars().count()).unwrap_or(i64::MAX);
+        if let Some(min) = self.min.filter(|&min| length < min) {
+            return Err(InvalidValue::TooShort(min));
+        }
+        if let Some(max) = self.max.filter(|&max| length > max) {
+            return Err(InvalidValue::TooLong(max));
+        }
+
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidValue {
+    #[error("expected an integer")]
+    NotInteger,
+
+    #[error("expected a boolean, such as `true` or `false`")]
+    NotBoolean,
+
+    #[error("expected a URL: {0}")]
+    NotUrl(#[from] url::ParseError),
+
+    #[error("expected one of: {}", .0.join(", "))]
+    NotChoice(Vec<String>),
+
+    #[error("expected JSON: {0}")]
+    NotJson(#[from] serde_json::Error),
+
+    #[error("expected a value matching `{0}`")]
+    Pattern(String),
+
+    #[error("invalid pattern `{0}` in module manifest: {1}")]
+    InvalidPattern(String, #[source] regex::Error),
+
+    #[error("expected at least {0}")]
+    TooSmall(i64),
+
+    #[error("expected at most {0}")]
+    TooLarge(i64),
+
+    #[error("expected at least {0} characters")]
+    TooShort(i64),
+
+    #[error("expected at most {0} characters")]
+    TooLong(i64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn variable(value_type: VariableType) -> ConfigurationVariable {
+        ConfigurationVariable {
+            name: "example".into(),
+            value_type: Some(value_type),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_typed_values() {
+        use VariableType::*;
+
+        let parse = |var: &ConfigurationVariable, input| var.parse_value(input);
+
+        assert_eq!(
+            parse(&variable(String), " as is ").unwrap(),
+            VariableValue::String(" as is ".into())
+        );
+        assert_eq!(
+            parse(&variable(Integer), "-42").unwrap(),
+            VariableValue::Integer(-42)
+        );
+        assert!(matches!(
+            parse(&variable(Integer), "4.2"),
+            Err(InvalidValue::NotInteger)
+        ));
+        assert_eq!(
+            parse(&variable(Boolean), "Yes").unwrap(),
+            VariableValue::Boolean(true)
+        );
+        assert_eq!(
+            parse(&variable(Boolean), "0").unwrap(),
+            VariableValue::Boolean(false)
+        );
+        assert!(matches!(
+            parse(&variable(Boolean), "maybe"),
+            Err(InvalidValue::NotBoolean)
+        ));
+        assert_eq!(
+            parse(&variable(Url), "https://example.org/api")
+                .unwrap()
+                .to_string(),
+            "https://example.org/api"
+        );
+        assert!(matches!(
+            parse(&variable(Url), "example.org"),
+            Err(InvalidValue::NotUrl(_))
+        ));
+        assert_eq!(
+            parse(&variable(Json), r#"{"a": [1]}"#)
+                .unwrap()
+                .as_json()
+                .unwrap()["a"][0],
+            1
+        );
+        assert!(matches!(
+            parse(&variable(Json), "{"),
+            Err(InvalidValue::NotJson(_))
+        ));
+
+        let level = ConfigurationVariable {
+            choices: vec!["debug".into(), "info".into()],
+            ..variable(Enum)
+        };
+        assert_eq!(
+            parse(&level, "info").unwrap(),
+            VariableValue::String("info".into())
+        );
+        assert_eq!(
+            parse(&level, "trace").unwrap_err().to_string(),
+            "expected one of: debug, info"
+        );
+    }
+
+    #[test]
+    fn check_constraints() {
+        let port = ConfigurationVariable {
+            min: Some(1),
+            max: Some(65535),
+            ..variable(VariableType::Integer)
+        };
+        assert!(port.parse_value("8080").is_ok());
+        assert!(matches!(
+            port.parse_value("0"),
+            Err(InvalidValue::TooSmall(1))
+        ));
+        assert!(matches!(
+            port.parse_value("65536"),
+            Err(InvalidValue::TooLarge(65535))
+        ));
+
+        let token = ConfigurationVariable {
+            pattern: Some("sk-[a-z0-9]+".into()),
+            min: Some(8),
+            ..variable(VariableType::String)
+        };
+        assert!(token.parse_value("sk-abc123").is_ok());
+        assert!(matches!(
+            token.parse_value("sk-a"),
+            Err(InvalidValue::TooShort(8))
+        ));
+        // the pattern must match the whole value:
+        assert!(matches!(
+            token.parse_value("xsk-abc123"),
+            Err(InvalidValue::Pattern(_))
+        ));
+
+        let invalid = ConfigurationVariable {
+            pattern: Some("(".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            invalid.parse_value(""),
+            Err(InvalidValue::InvalidPattern(..))
+        ));
+    }
+}
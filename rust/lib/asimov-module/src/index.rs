@@ -1,9 +1,19 @@
 // This is free and unencumbered software released into the public domain.
 
 use crate::ModuleManifest;
-use alloc::{string::String, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use asimov_id::PublicKey;
 use thiserror::Error;
 
+mod cache;
+pub use cache::*;
+
+mod search;
+pub use search::*;
+
+mod signature;
+pub use signature::*;
+
 /// The URL of the public index of ASIMOV modules, in JSONL format.
 pub const INDEX_URL: &str =
     "https://raw.githubusercontent.com/asimov-modules/asimov-modules/master/index.jsonl";
@@ -12,27 +22,31 @@ pub const INDEX_URL: &str =
 #[derive(Clone, Debug, Default)]
 pub struct Index {
     modules: Vec<ModuleManifest>,
+    publisher_keys: BTreeMap<String, TrustedKeys>,
 }
 
 impl Index {
-    /// Fetches the index from [`INDEX_URL`].
+    /// Fetches the index from [`INDEX_URL`], verifying its signature with the
+    /// keys of [`TrustedKeys::from_config`].
+    ///
+    /// See [`IndexCache`] for fetching the index only when it has changed.
     pub async fn fetch() -> Result<Self, FetchIndexError> {
-        let client = reqwest::Client::builder()
-            .user_agent("asimov-module-registry")
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .read_timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Failed to build HTTP client");
-
-        Self::fetch_from(&client, INDEX_URL).await
+        let trusted_keys = TrustedKeys::from_config()?;
+        Self::fetch_from(&http_client(), INDEX_URL, &trusted_keys).await
     }
 
+    /// Fetches the index at the given URL.
+    ///
+    /// Unless `trusted_keys` is empty, the index is only accepted with a valid
+    /// signature by one of them, fetched from the URL with a `.sig` suffix.
     pub async fn fetch_from(
         client: &reqwest::Client,
         url: impl AsRef<str>,
+        trusted_keys: &TrustedKeys,
     ) -> Result<Self, FetchIndexError> {
+        let url = url.as_ref();
         let response = client
-            .get(url.as_ref())
+            .get(url)
             .send()
             .await
             .inspect_err(|err| tracing::debug!(?err))?;
@@ -46,6 +60,10 @@ impl Index {
             .await
             .inspect_err(|err| tracing::debug!(?err))?;
 
+        trusted_keys
+            .verify_fetched(client, url, content.as_bytes())
+            .await?;
+
         Ok(content.parse()?)
     }
 
@@ -53,6 +71,26 @@ impl Index {
         &self.modules
     }
 
+    /// Returns the keys pinned for the publisher of a module, whose release
+    /// assets must be signed by one of them, if the index pins any.
+    pub fn publisher_keys(&self, module_name: &str) -> Option<&TrustedKeys> {
+        self.publisher_keys.get(module_name)
+    }
+
+    /// Adds the modules of another index, except for those with the same name
+    /// as a module already in this index.
+    pub fn merge(&mut self, other: Index) {
+        let mut publisher_keys = other.publisher_keys;
+        for module in other.modules {
+            if !self.modules.iter().any(|m| m.name == module.name) {
+                if let Some(keys) = publisher_keys.remove(&module.name) {
+                    self.publisher_keys.insert(module.name.clone(), keys);
+                }
+                self.modules.push(module);
+            }
+        }
+    }
+
     /// Searches the index for modules matching the given query.
     ///
     /// The query is split into whitespace-separated terms, and a module matches
@@ -60,7 +98,8 @@ impl Index {
     /// module's name, label, title, summary, links, provided programs, or
     /// handled inputs. An empty query matches every module.
     ///
-    /// The matching modules are returned in index order.
+    /// The matching modules are returned in index order. See [`Index::rank`]
+    /// for searching by relevance.
     pub fn search(&self, query: impl AsRef<str>) -> impl Iterator<Item = &ModuleManifest> {
         let terms: Vec<String> = query
             .as_ref()
@@ -83,22 +122,46 @@ impl core::str::FromStr for Index {
     type Err = ParseIndexError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        /// The fields of an index entry besides the module manifest.
+        #[derive(serde::Deserialize)]
+        struct Publisher {
+            #[serde(default)]
+            publisher_keys: Vec<PublicKey>,
+        }
+
         // parse an index in JSONL format, one module manifest per line
-        let modules = input
-            .lines()
-            .enumerate()
-            .filter(|(_, line)| !line.trim().is_empty())
-            .map(|(line_index, line)| {
-                serde_json::from_str::<ModuleManifest>(line)
-                    .inspect_err(|err| tracing::debug!(?err, ?line))
-                    .map_err(|err| ParseIndexError(line_index + 1, err))
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(Self { modules })
+        let mut index = Self::default();
+        for (line_index, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (module, publisher) = serde_json::from_str::<ModuleManifest>(line)
+                .and_then(|module| Ok((module, serde_json::from_str::<Publisher>(line)?)))
+                .inspect_err(|err| tracing::debug!(?err, ?line))
+                .map_err(|err| ParseIndexError(line_index + 1, err))?;
+
+            if !publisher.publisher_keys.is_empty() {
+                index.publisher_keys.insert(
+                    module.name.clone(),
+                    TrustedKeys::new(publisher.publisher_keys),
+                );
+            }
+            index.modules.push(module);
+        }
+
+        Ok(index)
     }
 }
 
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent("asimov-module-registry")
+        .connect_timeout(std::time::Duration::from_secs(10))
+        .read_timeout(std::time::Duration::from_secs(30))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
 /// The searchable fields of a module manifest, lowercased and newline-separated.
 fn searchable_text(module: &ModuleManifest) -> String {
     let handles = &module.handles;
@@ -139,6 +202,12 @@ pub enum FetchIndexError {
     Http(#[from] HttpError),
     #[error(transparent)]
     Parse(#[from] ParseIndexError),
+    #[error("module index `{0}` is not cached, and can't be fetched while offline")]
+    NotCached(String),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    TrustedKeys(#[from] TrustedKeysError),
 }
 
 impl From<reqwest::Error> for FetchIndexError {
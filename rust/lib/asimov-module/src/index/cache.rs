@@ -0,0 +1,385 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{FetchIndexError, HttpError, INDEX_URL, Index, IndexSignature, TrustedKeys};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::time::Duration;
+use reqwest::{StatusCode, header};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How long a cached index is used before its source is checked for changes.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A module index to fetch, in the JSONL format of [`INDEX_URL`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexSource {
+    /// Names the cached copy of the index, so it must be unique among the
+    /// sources of a cache, and usable as a file name.
+    pub name: String,
+    pub url: String,
+    /// The keys of which one must have signed the index, if any.
+    pub trusted_keys: TrustedKeys,
+}
+
+impl IndexSource {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            trusted_keys: TrustedKeys::default(),
+        }
+    }
+
+    /// The public index of ASIMOV modules at [`INDEX_URL`].
+    pub fn public() -> Self {
+        Self::new("public", INDEX_URL)
+    }
+
+    /// Only accepts the index with a signature by one of the given keys.
+    pub fn with_trusted_keys(mut self, trusted_keys: TrustedKeys) -> Self {
+        self.trusted_keys = trusted_keys;
+        self
+    }
+}
+
+#[derive(Clone, Debug, bon::Builder)]
+pub struct IndexCacheOptions {
+    /// The indexes to fetch, in order of precedence: when several of them have
+    /// a module with the same name, the module of the first one is used.
+    #[builder(default = vec![IndexSource::public()])]
+    pub sources: Vec<IndexSource>,
+
+    /// How long a cached index is used before its source is checked for changes.
+    #[builder(default = DEFAULT_TTL)]
+    pub ttl: Duration,
+
+    /// Whether to only use cached indexes, without any network access.
+    #[builder(default)]
+    pub offline: bool,
+}
+
+impl Default for IndexCacheOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Fetches module indexes and keeps copies of them on disk, so that they are
+/// downloaded only when they have changed, and are available offline.
+///
+/// A cached index is used as is until it is older than
+/// [`IndexCacheOptions::ttl`]. After that, it is refreshed with a conditional
+/// request, which only downloads the index again if the source has changed
+/// since. If the source can't be reached, the cached index is used regardless
+/// of its age.
+#[derive(Clone, Debug)]
+pub struct IndexCache {
+    dir: PathBuf,
+    options: IndexCacheOptions,
+    client: reqwest::Client,
+}
+
+impl Default for IndexCache {
+    /// Caches indexes in `~/.asimov/index/`.
+    fn default() -> Self {
+        Self::new(
+            asimov_env::paths::asimov_root().join("index"),
+            IndexCacheOptions::default(),
+        )
+    }
+}
+
+impl IndexCache {
+    pub fn new(dir: impl Into<PathBuf>, options: IndexCacheOptions) -> Self {
+        Self {
+            dir: dir.into(),
+            options,
+            client: super::http_client(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn options(&self) -> &IndexCacheOptions {
+        &self.options
+    }
+
+    /// Fetches every source and merges them into one index, in the order of
+    /// [`IndexCacheOptions::sources`].
+    pub async fn fetch(&self) -> Result<Index, FetchIndexError> {
+        let mut index = Index::default();
+        for source in &self.options.sources {
+            index.merge(self.fetch_source(source).await?);
+        }
+        Ok(index)
+    }
+
+    /// Fetches a single source, using or refreshing its cached copy.
+    pub async fn fetch_source(&self, source: &IndexSource) -> Result<Index, FetchIndexError> {
+        let cached = self.read_cached(source).await;
+
+        if let Some(cached) = &cached
+            && (self.options.offline || cached.metadata.age() < self.options.ttl)
+        {
+            match cached.content.parse() {
+                Ok(index) => return Ok(index),
+                Err(err) => tracing::debug!(?err, source = source.name, "invalid cached index"),
+            }
+        }
+
+        if self.options.offline {
+            return Err(FetchIndexError::NotCached(source.name.clone()));
+        }
+
+        let metadata = cached.as_ref().map(|cached| &cached.metadata);
+        match self.download(source, metadata).await {
+            Ok(Download::NotModified) => {
+                let Some(mut cached) = cached else {
+                    // the request was only conditional if there was a cached copy
+                    Err(HttpError::NotSuccess(StatusCode::NOT_MODIFIED))?
+                };
+                let index = cached.content.parse()?;
+
+                cached.metadata.fetched_at = now();
+                self.write_cached(source, &cached.metadata, None).await;
+
+                Ok(index)
+            },
+            Ok(Download::Modified { metadata, content }) => {
+                let index = content.parse()?;
+                self.write_cached(source, &metadata, Some(&content)).await;
+                Ok(index)
+            },
+            Err(err) => {
+                let Some(index) = cached.and_then(|cached| cached.content.parse().ok()) else {
+                    return Err(err);
+                };
+                tracing::warn!(
+                    ?err,
+                    source = source.name,
+                    "failed to refresh the module index, using the cached copy"
+                );
+                Ok(index)
+            },
+        }
+    }
+
+    async fn download(
+        &self,
+        source: &IndexSource,
+        cached: Option<&CacheMetadata>,
+    ) -> Result<Download, FetchIndexError> {
+        let mut request = self.client.get(&source.url);
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+
+        if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+            return Ok(Download::NotModified);
+        }
+        if !response.status().is_success() {
+            Err(HttpError::NotSuccess(response.status()))?;
+        }
+
+        let header_value = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let metadata = CacheMetadata {
+            url: source.url.clone(),
+            etag: header_value(header::ETAG),
+            last_modified: header_value(header::LAST_MODIFIED),
+            fetched_at: now(),
+            signature: None,
+        };
+
+        let content = response
+            .text()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))?;
+
+        let signature = source
+            .trusted_keys
+            .verify_fetched(&self.client, &source.url, content.as_bytes())
+            .await?;
+        let metadata = CacheMetadata {
+            signature: signature.as_ref().map(alloc::string::ToString::to_string),
+            ..metadata
+        };
+
+        Ok(Download::Modified { metadata, content })
+    }
+
+    /// Returns the cached copy of the source, if there is one for its URL.
+    async fn read_cached(&self, source: &IndexSource) -> Option<Cached> {
+        let (metadata_path, content_path) = self.paths(source);
+
+        let result = async {
+            let metadata = tokio::fs::read(&metadata_path).await?;
+            let metadata: CacheMetadata =
+                serde_json::from_slice(&metadata).map_err(io::Error::other)?;
+            let content = tokio::fs::read_to_string(&content_path).await?;
+            io::Result::Ok(Cached { metadata, content })
+        }
+        .await;
+
+        match result {
+            Ok(cached) if cached.metadata.url != source.url => None,
+            Ok(cached) if !cached.is_trusted(&source.trusted_keys) => {
+                tracing::debug!(
+                    source = source.name,
+                    "cached index isn't signed by a trusted key"
+                );
+                None
+            },
+            Ok(cached) => Some(cached),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::debug!(?err, source = source.name, "failed to read cached index");
+                None
+            },
+        }
+    }
+
+    /// Saves the cached copy of the source. Failing to do so is not an error,
+    /// since the index is then simply fetched again next time.
+    async fn write_cached(
+        &self,
+        source: &IndexSource,
+        metadata: &CacheMetadata,
+        content: Option<&str>,
+    ) {
+        let (metadata_path, content_path) = self.paths(source);
+
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            if let Some(content) = content {
+                write_file(&content_path, content.as_bytes()).await?;
+            }
+            let metadata = serde_json::to_vec(metadata).map_err(io::Error::other)?;
+            write_file(&metadata_path, &metadata).await
+        }
+        .await;
+
+        if let Err(err) = result {
+            tracing::warn!(
+                ?err,
+                source = source.name,
+                "failed to cache the module index"
+            );
+        }
+    }
+
+    /// The paths of the metadata and the content of the cached copy of the source.
+    fn paths(&self, source: &IndexSource) -> (PathBuf, PathBuf) {
+        (
+            self.dir.join(format!("{}.json", source.name)),
+            self.dir.join(format!("{}.jsonl", source.name)),
+        )
+    }
+}
+
+impl Index {
+    /// Fetches the indexes of the default [`IndexCache`], using cached copies
+    /// where they are recent enough, and verifying the signature of the public
+    /// index with the keys of [`TrustedKeys::from_config`].
+    pub async fn fetch_cached() -> Result<Self, FetchIndexError> {
+        let trusted_keys = TrustedKeys::from_config()?;
+        let options = IndexCacheOptions::builder()
+            .sources(vec![IndexSource::public().with_trusted_keys(trusted_keys)])
+            .build();
+        IndexCache {
+            options,
+            ..IndexCache::default()
+        }
+        .fetch()
+        .await
+    }
+}
+
+enum Download {
+    NotModified,
+    Modified {
+        metadata: CacheMetadata,
+        content: String,
+    },
+}
+
+struct Cached {
+    metadata: CacheMetadata,
+    content: String,
+}
+
+impl Cached {
+    /// Whether the copy was signed by one of the keys, if there are any. The
+    /// signature is checked again since the keys may have changed after it
+    /// was cached.
+    fn is_trusted(&self, trusted_keys: &TrustedKeys) -> bool {
+        if trusted_keys.is_empty() {
+            return true;
+        }
+        self.metadata
+            .signature
+            .as_deref()
+            .and_then(|signature| signature.parse::<IndexSignature>().ok())
+            .is_some_and(|signature| {
+                trusted_keys
+                    .verify(self.content.as_bytes(), &signature)
+                    .is_ok()
+            })
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+struct CacheMetadata {
+    /// The URL of the source, in case it changes for the same name.
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// When the cached copy was last fetched or found unchanged, in seconds
+    /// since the Unix epoch.
+    fetched_at: u64,
+    /// The signature of the index, if it was verified.
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+impl CacheMetadata {
+    fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.fetched_at))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Writes a file through a temporary file, so that it is never seen partially written.
+async fn write_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    tokio::fs::write(&temp_path, content).await?;
+    tokio::fs::rename(&temp_path, path).await.inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp_path);
+    })
+}
@@ -0,0 +1,393 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::Index;
+use crate::{
+    ModuleManifest,
+    resolve::{Resolver, error::UrlParseError},
+};
+use alloc::{string::String, vec::Vec};
+
+/// The BM25 term frequency saturation.
+const K1: f64 = 1.2;
+
+/// The BM25 field length normalization.
+const B: f64 = 0.75;
+
+/// A query for [`Index::rank`]: free text to rank modules by, and filters that
+/// modules must pass regardless of the text.
+#[derive(Clone, Debug, Default, bon::Builder)]
+#[builder(on(String, into))]
+pub struct SearchQuery {
+    /// Whitespace-separated terms, which must all match a module. Terms match
+    /// words of the module's fields by prefix, and tolerate a typo or two.
+    #[builder(default)]
+    pub text: String,
+
+    /// Only modules handling URLs with this protocol, such as `imap`.
+    pub protocol: Option<String>,
+
+    /// Only modules handling this content type, such as `text/csv`.
+    pub content_type: Option<String>,
+
+    /// Only modules providing a program matching this pattern, where `*`
+    /// matches any characters, such as `*-fetcher`.
+    pub program: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    fn accepts(&self, module: &ModuleManifest) -> bool {
+        let handles = &module.handles;
+
+        let protocol = self.protocol.as_deref().map(str::to_lowercase);
+        let handles_protocol = |protocol: &str| {
+            handles
+                .url_protocols
+                .iter()
+                .any(|p| p.eq_ignore_ascii_case(protocol))
+                || handles
+                    .url_prefixes
+                    .iter()
+                    .chain(&handles.url_patterns)
+                    .any(|url| {
+                        url.to_lowercase()
+                            .starts_with(&alloc::format!("{protocol}:"))
+                    })
+        };
+
+        protocol.is_none_or(|protocol| handles_protocol(&protocol))
+            && self.content_type.as_deref().is_none_or(|wanted| {
+                handles
+                    .content_types
+                    .iter()
+                    .any(|handled| content_type_matches(handled, wanted))
+            })
+            && self.program.as_deref().is_none_or(|pattern| {
+                module
+                    .provides
+                    .programs
+                    .iter()
+                    .any(|program| glob_matches(pattern, program))
+            })
+    }
+}
+
+impl From<&str> for SearchQuery {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/// A module found by [`Index::rank`], with its relevance to the query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult<'a> {
+    pub module: &'a ModuleManifest,
+    pub score: f64,
+}
+
+impl Index {
+    /// Searches the index for modules matching the given query, most relevant
+    /// first.
+    ///
+    /// Relevance is scored with BM25F, where matches in the name and the label
+    /// of a module weigh more than matches in its title, programs and handled
+    /// inputs, which weigh more than matches in its summary and links. Exact
+    /// word matches score higher than prefix matches, and terms which aren't
+    /// found in any module match words with a typo or two. Modules with the
+    /// same score are returned in index order, so without any text every
+    /// module passing the filters is returned in index order.
+    pub fn rank(&self, query: impl Into<SearchQuery>) -> Vec<SearchResult<'_>> {
+        let query = query.into();
+        let terms = words(&query.text);
+
+        let modules: Vec<(&ModuleManifest, Document)> = self
+            .modules
+            .iter()
+            .filter(|module| query.accepts(module))
+            .map(|module| (module, Document::new(module)))
+            .collect();
+
+        let mut average_lengths = [0.0; FIELDS];
+        for (_, document) in &modules {
+            for (average, field) in average_lengths.iter_mut().zip(&document.fields) {
+                *average += field.len() as f64 / modules.len() as f64;
+            }
+        }
+
+        // the quality of the best match of every term in every field of every module
+        let mut matches: Vec<Vec<[f64; FIELDS]>> = modules
+            .iter()
+            .map(|(_, document)| terms.iter().map(|term| document.matches(term)).collect())
+            .collect();
+
+        // typos are only tolerated in terms which aren't found as they are, so
+        // that `mail` doesn't also find `email`
+        for term in 0..terms.len() {
+            let found = matches
+                .iter()
+                .any(|terms| terms[term].iter().any(|&quality| quality >= PREFIX_MATCH));
+            if found {
+                for quality in matches.iter_mut().flat_map(|terms| &mut terms[term]) {
+                    if *quality < PREFIX_MATCH {
+                        *quality = 0.0;
+                    }
+                }
+            }
+        }
+
+        let document_frequencies: Vec<usize> = (0..terms.len())
+            .map(|term| {
+                matches
+                    .iter()
+                    .filter(|terms| terms[term].iter().any(|&quality| quality > 0.0))
+                    .count()
+            })
+            .collect();
+
+        let mut results: Vec<SearchResult> = modules
+            .iter()
+            .zip(&matches)
+            .filter_map(|((module, document), term_matches)| {
+                let mut score = 0.0;
+                for (term, field_matches) in term_matches.iter().enumerate() {
+                    let mut frequency = 0.0;
+                    for (field, quality) in field_matches.iter().enumerate() {
+                        let length = document.fields[field].len() as f64;
+                        let average = average_lengths[field].max(1.0);
+                        let normalization = 1.0 - B + B * length / average;
+                        frequency += FIELD_WEIGHTS[field] * quality / normalization;
+                    }
+                    if frequency == 0.0 {
+                        // every term must match
+                        return None;
+                    }
+                    let idf = inverse_document_frequency(modules.len(), document_frequencies[term]);
+                    score += idf * frequency * (K1 + 1.0) / (frequency + K1);
+                }
+                Some(SearchResult { module, score })
+            })
+            .collect();
+
+        // a stable sort keeps the index order of equally relevant modules
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results
+    }
+
+    /// Returns a resolver for the modules of the index, such as to find the
+    /// modules that handle a URL before any are installed.
+    ///
+    /// Modules with invalid manifests are left out.
+    pub fn resolver(&self) -> Resolver {
+        let mut resolver = Resolver::new();
+        for module in &self.modules {
+            // replacing checks the whole manifest before inserting any of it
+            if let Err(err) = resolver.replace_manifest(module) {
+                tracing::debug!(?err, module = module.name, "skipping invalid manifest");
+            }
+        }
+        resolver
+    }
+
+    /// Returns the modules of the index that handle the given URL, most
+    /// specific first.
+    ///
+    /// This builds a resolver for every call, see [`Index::resolver`] for
+    /// resolving many URLs.
+    pub fn modules_for_url(&self, url: &str) -> Result<Vec<&ModuleManifest>, UrlParseError> {
+        let resolutions = self.resolver().resolve(url)?;
+
+        Ok(resolutions
+            .iter()
+            .filter_map(|resolution| {
+                self.modules
+                    .iter()
+                    .find(|module| module.name == resolution.name)
+            })
+            .collect())
+    }
+}
+
+/// The number of fields of a [`Document`].
+const FIELDS: usize = 6;
+
+/// How much a match in each field of a [`Document`] counts.
+const FIELD_WEIGHTS: [f64; FIELDS] = [
+    5.0, // name
+    4.0, // label
+    2.0, // title
+    2.0, // programs and handled inputs
+    1.0, // summary and tags
+    0.5, // links
+];
+
+/// The words of the searchable fields of a module.
+struct Document {
+    fields: [Vec<String>; FIELDS],
+}
+
+impl Document {
+    fn new(module: &ModuleManifest) -> Self {
+        let handles = &module.handles;
+        let join = |lists: &[&Vec<String>]| {
+            lists
+                .iter()
+                .flat_map(|list| list.iter())
+                .flat_map(|item| words(item))
+                .collect()
+        };
+
+        Self {
+            fields: [
+                words(&module.name),
+                words(module.label.as_deref().unwrap_or_default()),
+                words(module.title.as_deref().unwrap_or_default()),
+                join(&[
+                    &module.provides.programs,
+                    &handles.url_protocols,
+                    &handles.url_prefixes,
+                    &handles.url_patterns,
+                    &handles.file_extensions,
+                    &handles.content_types,
+                ]),
+                words(module.summary.as_deref().unwrap_or_default())
+                    .into_iter()
+                    .chain(join(&[&module.tags]))
+                    .collect(),
+                join(&[&module.links]),
+            ],
+        }
+    }
+
+    /// Returns the quality of the best match of the term in each field.
+    fn matches(&self, term: &str) -> [f64; FIELDS] {
+        self.fields.each_ref().map(|words| {
+            words
+                .iter()
+                .map(|word| match_quality(term, word))
+                .fold(0.0, f64::max)
+        })
+    }
+}
+
+const EXACT_MATCH: f64 = 1.0;
+const PREFIX_MATCH: f64 = 0.8;
+const TYPO_MATCH: f64 = 0.5;
+
+/// How well a query term matches a word, from 0 (not at all) to 1 (exactly).
+fn match_quality(term: &str, word: &str) -> f64 {
+    if term == word {
+        return EXACT_MATCH;
+    }
+    if word.starts_with(term) {
+        return PREFIX_MATCH;
+    }
+
+    // longer terms tolerate more typos
+    let max_typos = match term.chars().count() {
+        0..=3 => return 0.0,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    // typos either in the whole word, or in a prefix of it about as long as
+    // the term, such as `antrop` for `anthropic`
+    let length = term.chars().count();
+    let typos = (length - max_typos..=length + max_typos)
+        .map(|prefix_length| {
+            let prefix: String = word.chars().take(prefix_length).collect();
+            edit_distance(term, &prefix)
+        })
+        .min()
+        .unwrap_or(usize::MAX);
+    match typos {
+        1 => TYPO_MATCH,
+        2 if max_typos >= 2 => TYPO_MATCH / 2.0,
+        _ => 0.0,
+    }
+}
+
+/// The number of character insertions, deletions, substitutions and
+/// transpositions of adjacent characters needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // three rows of the distance matrix, for transpositions
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i);
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before[j - 2] + 1);
+            }
+            current.push(distance);
+        }
+        before = core::mem::replace(&mut previous, current);
+    }
+
+    previous[b.len()]
+}
+
+fn inverse_document_frequency(documents: usize, matching: usize) -> f64 {
+    let (documents, matching) = (documents as f64, matching as f64);
+    (1.0 + (documents - matching + 0.5) / (matching + 0.5)).ln()
+}
+
+/// Splits text into lowercase words of letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Whether a module handling the `handled` content type, such as `text/*`,
+/// handles the `wanted` one, such as `text/csv`.
+fn content_type_matches(handled: &str, wanted: &str) -> bool {
+    let essence = |content_type: &str| {
+        let essence = content_type.split(';').next().unwrap_or_default();
+        essence.trim().to_ascii_lowercase()
+    };
+    let (handled, wanted) = (essence(handled), essence(wanted));
+
+    handled == wanted
+        || handled == "*/*"
+        || handled
+            .strip_suffix("/*")
+            .is_some_and(|toptype| wanted.split('/').next() == Some(toptype))
+}
+
+/// Whether the text matches a pattern where `*` matches any characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*` in the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
@@ -0,0 +1,258 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::HttpError;
+use alloc::{format, string::String, vec::Vec};
+use asimov_id::{KeyError, PublicKey};
+use core::str::FromStr;
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// The keys whose signatures on a module index are trusted.
+///
+/// An index is signed with a detached Ed25519 signature of its content,
+/// published next to it with a `.sig` suffix, such as `index.jsonl.sig`, and
+/// encoded in Base58. When there are trusted keys, an index is only accepted
+/// with a valid signature by one of them. Without any trusted keys, indexes
+/// are accepted without a signature.
+///
+/// The keys of module publishers, pinned in the index, are trusted the same
+/// way with the signatures on their release assets, see
+/// [`Index::publisher_keys`](super::Index::publisher_keys).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrustedKeys(Vec<PublicKey>);
+
+impl TrustedKeys {
+    pub fn new(keys: impl IntoIterator<Item = PublicKey>) -> Self {
+        Self(keys.into_iter().collect())
+    }
+
+    /// The configured trusted keys: `~/.asimov/index/trusted-keys`.
+    pub fn config_path() -> PathBuf {
+        asimov_env::paths::asimov_root()
+            .join("index")
+            .join("trusted-keys")
+    }
+
+    /// Reads the keys of [`TrustedKeys::config_path`], if it exists.
+    pub fn from_config() -> Result<Self, TrustedKeysError> {
+        let path = Self::config_path();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => content
+                .parse()
+                .map_err(|(line, source)| TrustedKeysError::Parse { path, line, source }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(TrustedKeysError::Io(path, err)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.0
+    }
+
+    /// Returns the trusted key which made the signature of the content.
+    pub fn verify(
+        &self,
+        content: &[u8],
+        signature: &IndexSignature,
+    ) -> Result<&PublicKey, SignatureError> {
+        self.0
+            .iter()
+            .find(|key| {
+                ed25519_dalek::VerifyingKey::from_bytes(&key.into_bytes())
+                    .and_then(|key| key.verify_strict(content, &signature.0))
+                    .is_ok()
+            })
+            .ok_or(SignatureError::Untrusted)
+    }
+
+    /// Fetches the signature of the index at `url` and verifies the content
+    /// of the index with it, unless there are no trusted keys.
+    pub(super) async fn verify_fetched(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        content: &[u8],
+    ) -> Result<Option<IndexSignature>, SignatureError> {
+        if self.is_empty() {
+            tracing::debug!(
+                url,
+                "no trusted keys, skipping index signature verification"
+            );
+            return Ok(None);
+        }
+
+        let signature_url = format!("{url}.sig");
+        let response = client
+            .get(&signature_url)
+            .send()
+            .await
+            .inspect_err(|err| tracing::debug!(?err))
+            .map_err(|err| SignatureError::Fetch(err.into()))?;
+        if !response.status().is_success() {
+            return Err(SignatureError::Fetch(HttpError::NotSuccess(
+                response.status(),
+            )));
+        }
+        let signature = response
+            .text()
+            .await
+            .map_err(|err| SignatureError::Fetch(err.into()))?;
+
+        let signature = signature.parse()?;
+        let key = self.verify(content, &signature)?;
+        tracing::debug!(url, %key, "verified index signature");
+
+        Ok(Some(signature))
+    }
+}
+
+impl FromIterator<PublicKey> for TrustedKeys {
+    fn from_iter<I: IntoIterator<Item = PublicKey>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl FromStr for TrustedKeys {
+    /// The line number of the invalid key, and why it is invalid.
+    type Err = (usize, KeyError);
+
+    /// Parses one key per line, ignoring empty lines and `#` comments.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.split('#').next().unwrap_or_default().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(line_number, line)| line.parse().map_err(|err| (line_number, err)))
+            .collect()
+    }
+}
+
+/// A detached Ed25519 signature of a module index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexSignature(ed25519_dalek::Signature);
+
+impl IndexSignature {
+    pub fn from_bytes(bytes: &[u8; 64]) -> Self {
+        Self(ed25519_dalek::Signature::from_bytes(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.0.to_bytes()
+    }
+}
+
+impl From<ed25519_dalek::Signature> for IndexSignature {
+    fn from(signature: ed25519_dalek::Signature) -> Self {
+        Self(signature)
+    }
+}
+
+impl FromStr for IndexSignature {
+    type Err = SignatureError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 64];
+        let count = bs58::decode(input.trim())
+            .onto(&mut bytes)
+            .map_err(|err| SignatureError::Malformed(format!("{err}")))?;
+        if count != bytes.len() {
+            return Err(SignatureError::Malformed(format!(
+                "expected 64 bytes, got {count}"
+            )));
+        }
+        Ok(Self::from_bytes(&bytes))
+    }
+}
+
+impl core::fmt::Display for IndexSignature {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&bs58::encode(self.to_bytes()).into_string())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("failed to fetch index signature: {0}")]
+    Fetch(#[source] HttpError),
+    #[error("malformed index signature: {0}")]
+    Malformed(String),
+    #[error("index signature is not by a trusted key")]
+    Untrusted,
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedKeysError {
+    #[error("failed to read trusted keys `{0}`: {1}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("invalid trusted key in `{path}` on line {line}: {source}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: KeyError,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const INDEX: &[u8] = b"{\"name\":\"ipfs\"}\n";
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn public_key(seed: u8) -> PublicKey {
+        PublicKey::from(&signing_key(seed).verifying_key())
+    }
+
+    #[test]
+    fn verify() {
+        let signature = IndexSignature::from(signing_key(1).sign(INDEX));
+        let signature: IndexSignature = signature.to_string().parse().unwrap();
+
+        let trusted = TrustedKeys::new([public_key(2), public_key(1)]);
+        assert_eq!(trusted.verify(INDEX, &signature).unwrap(), &public_key(1));
+
+        let tampered = b"{\"name\":\"ipfs\",\"links\":[\"https://example.org\"]}\n";
+        assert!(matches!(
+            trusted.verify(tampered, &signature),
+            Err(SignatureError::Untrusted)
+        ));
+
+        let untrusted = TrustedKeys::new([public_key(2)]);
+        assert!(matches!(
+            untrusted.verify(INDEX, &signature),
+            Err(SignatureError::Untrusted)
+        ));
+    }
+
+    #[test]
+    fn parse() {
+        let config = format!(
+            "# the public index\n{}\n\n{}  # a company index\n",
+            public_key(1),
+            public_key(2),
+        );
+        let trusted: TrustedKeys = config.parse().unwrap();
+        assert_eq!(trusted.keys(), [public_key(1), public_key(2)]);
+
+        let (line, _) = format!("{}\nnot a key\n", public_key(1))
+            .parse::<TrustedKeys>()
+            .unwrap_err();
+        assert_eq!(line, 2);
+
+        assert!(matches!(
+            "3mJr7AoUXx2Wqd".parse::<IndexSignature>(),
+            Err(SignatureError::Malformed(_))
+        ));
+    }
+}
@@ -49,6 +49,11 @@ pub use asimov_core::{InvalidModuleName, ModuleName};
 mod models;
 pub use models::*;
 
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+pub use config::*;
+
 #[cfg(feature = "index")]
 pub mod index;
 #[cfg(feature = "index")]
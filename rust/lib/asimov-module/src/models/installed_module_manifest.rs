@@ -1,12 +1,83 @@
 // This is free and unencumbered software released into the public domain.
 
-use alloc::string::String;
+use alloc::{collections::BTreeMap, string::String};
 
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct InstalledModuleManifest {
     pub version: Option<String>,
 
+    /// The models installed for the module, by their names in
+    /// [`Requires::models`](super::Requires::models).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub installed_models: BTreeMap<String, InstalledModel>,
+
+    /// Where the module was installed from, if known.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source: Option<InstallSource>,
+
+    /// The SHA-256 hash of the release asset the module was installed from,
+    /// in hex.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub sha256: Option<String>,
+
+    /// Whether the module was installed only because other modules require
+    /// it, rather than explicitly, so that it can be removed once no longer
+    /// required.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "core::ops::Not::not")
+    )]
+    pub dependency: bool,
+
     #[cfg_attr(feature = "serde", serde(flatten))]
     pub manifest: super::ModuleManifest,
 }
+
+/// A model installed for a module.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct InstalledModel {
+    /// The chosen variant, for a model with
+    /// [`Choices`](super::RequiredModel::Choices).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub variant: Option<String>,
+
+    /// The installed file, within the model's repository.
+    pub file: String,
+
+    /// The verified SHA-256 hash of the file, in hex, if the manifest declares it.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub sha256: Option<String>,
+}
+
+/// Where an installed module came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(tag = "type", rename_all = "lowercase")
+)]
+pub enum InstallSource {
+    /// A release asset, downloaded from the given location.
+    Release { url: String },
+    /// A local archive at the given path.
+    Archive { path: String },
+    /// A local, unpacked directory at the given path.
+    Directory { path: String },
+}
@@ -99,6 +99,51 @@ pub enum ReadVarError {
         #[source]
         source: std::io::Error,
     },
+
+    #[error(transparent)]
+    Profile(#[from] crate::ProfileError),
+
+    #[error("failed to decrypt variable `{name}`: {source}")]
+    Secret {
+        name: String,
+        #[source]
+        source: crate::SecretError,
+    },
+
+    #[error("invalid value for variable `{name}`: {source}")]
+    Invalid {
+        name: String,
+        #[source]
+        source: crate::InvalidValue,
+    },
+}
+
+#[cfg(feature = "config")]
+#[derive(Debug, thiserror::Error)]
+pub enum WriteVarError {
+    #[error("variable named `{0}` not found in module manifest")]
+    UnknownVar(String),
+
+    #[error("failed to write variable `{name}`: {source}")]
+    Io {
+        name: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to encrypt variable `{name}`: {source}")]
+    Secret {
+        name: String,
+        #[source]
+        source: crate::SecretError,
+    },
+
+    #[error("invalid value for variable `{name}`: {source}")]
+    Invalid {
+        name: String,
+        #[source]
+        source: crate::InvalidValue,
+    },
 }
 
 impl ModuleManifest {
@@ -133,57 +178,108 @@ impl ModuleManifest {
         Err(std::io::ErrorKind::NotFound.into())
     }
 
+    /// Returns the validated values of every variable of the module; see
+    /// [`ModuleManifest::variable_value`].
     #[cfg(feature = "std")]
     pub fn read_variables(
         &self,
         profile: Option<&str>,
-    ) -> Result<alloc::collections::BTreeMap<String, String>, ReadVarError> {
+    ) -> Result<alloc::collections::BTreeMap<String, crate::VariableValue>, ReadVarError> {
         self.config
             .as_ref()
             .map(|c| c.variables.as_slice())
             .unwrap_or_default()
             .iter()
-            .map(|var| Ok((var.name.clone(), self.variable(&var.name, profile)?)))
+            .map(|var| Ok((var.name.clone(), self.variable_value(&var.name, profile)?)))
             .collect()
     }
 
+    /// Returns the value of a variable as given, after checking it against
+    /// the type and the constraints of the variable; see
+    /// [`ModuleManifest::variable_value`].
     #[cfg(feature = "std")]
     pub fn variable(&self, key: &str, profile: Option<&str>) -> Result<String, ReadVarError> {
-        let Some(var) = self
-            .config
-            .as_ref()
-            .and_then(|conf| conf.variables.iter().find(|var| var.name == key))
-        else {
-            return Err(ReadVarError::UnknownVar(key.into()));
-        };
-
-        if let Some(value) = var
-            .environment
-            .as_deref()
-            .and_then(|env_name| std::env::var(env_name).ok())
-        {
-            return Ok(value);
-        }
+        let (var, resolved) = self.raw_variable(key, profile)?;
+        var.parse_value(&resolved.value)
+            .map_err(|source| ReadVarError::Invalid {
+                name: key.into(),
+                source,
+            })?;
+        Ok(resolved.value)
+    }
+
+    /// Returns the value of a variable: from its environment variable if set,
+    /// or else as configured in the default [`ConfigStore`](crate::ConfigStore),
+    /// taking project-local values and inherited profiles into account, or
+    /// else its default value. The value is parsed as the type of the
+    /// variable, and checked against its constraints.
+    ///
+    /// Secret values are decrypted, so take care not to log them; see
+    /// [`ModuleManifest::redacted`].
+    #[cfg(feature = "std")]
+    pub fn variable_value(
+        &self,
+        key: &str,
+        profile: Option<&str>,
+    ) -> Result<crate::VariableValue, ReadVarError> {
+        let (var, resolved) = self.raw_variable(key, profile)?;
+        var.parse_value(&resolved.value)
+            .map_err(|source| ReadVarError::Invalid {
+                name: key.into(),
+                source,
+            })
+    }
+
+    /// Returns the value of a variable as given, with where it comes from;
+    /// see [`ConfigStore::resolve`](crate::ConfigStore::resolve).
+    #[cfg(feature = "std")]
+    pub fn resolve_variable(
+        &self,
+        key: &str,
+        profile: Option<&str>,
+    ) -> Result<crate::ResolvedValue, ReadVarError> {
+        self.raw_variable(key, profile)
+            .map(|(_, resolved)| resolved)
+    }
+
+    #[cfg(feature = "std")]
+    fn raw_variable(
+        &self,
+        key: &str,
+        profile: Option<&str>,
+    ) -> Result<(&ConfigurationVariable, crate::ResolvedValue), ReadVarError> {
+        let var = self
+            .find_variable(key)
+            .ok_or_else(|| ReadVarError::UnknownVar(key.into()))?;
 
         let profile = profile.unwrap_or("default");
-        let path = asimov_env::paths::asimov_root()
-            .join("configs")
-            .join(profile)
-            .join(&self.name)
-            .join(key);
-
-        std::fs::read_to_string(&path).or_else(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
-                var.default_value
-                    .clone()
-                    .ok_or_else(|| ReadVarError::UnconfiguredVar(key.into()))
-            } else {
-                Err(ReadVarError::Io {
-                    name: key.into(),
-                    source: err,
-                })
-            }
-        })
+        let resolved = crate::ConfigStore::default().resolve(profile, &self.name, var)?;
+        Ok((var, resolved))
+    }
+
+    /// Saves the value of a variable in the default
+    /// [`ConfigStore`](crate::ConfigStore), encrypted if it is secret, after
+    /// checking it against the type and the constraints of the variable.
+    #[cfg(feature = "config")]
+    pub fn write_variable(
+        &self,
+        key: &str,
+        value: &str,
+        profile: Option<&str>,
+    ) -> Result<(), WriteVarError> {
+        let var = self
+            .find_variable(key)
+            .ok_or_else(|| WriteVarError::UnknownVar(key.into()))?;
+
+        let profile = profile.unwrap_or("default");
+        crate::ConfigStore::default().write(profile, &self.name, var, value)
+    }
+
+    #[cfg(feature = "std")]
+    fn find_variable(&self, key: &str) -> Option<&ConfigurationVariable> {
+        self.config
+            .as_ref()
+            .and_then(|conf| conf.variables.iter().find(|var| var.name == key))
     }
 }
 
@@ -303,20 +399,109 @@ pub enum RequiredModel {
     /// ```yaml
     /// hf:first/model: model_file.bin
     /// ```
-    Url(String),
+    Url(ModelFile),
 
     /// Multiple variants:
     /// ```yaml
     /// hf:second/model:
     ///   small: model_small.bin
     ///   medium: model_medium.bin
-    ///   large: model_large.bin
+    ///   large:
+    ///     file: model_large.bin
+    ///     sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+    ///     size: 4368709120
     /// ```
     #[cfg_attr(
         feature = "serde",
-        serde(deserialize_with = "ordered::deserialize_ordered")
+        serde(
+            deserialize_with = "ordered::deserialize_ordered",
+            serialize_with = "ordered::serialize_ordered"
+        )
     )]
-    Choices(Vec<(String, String)>),
+    Choices(Vec<(String, ModelFile)>),
+}
+
+/// A model file, given either by its name alone or with details to verify
+/// and to choose between variants by:
+/// ```yaml
+/// file: model_large.bin
+/// sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+/// size: 4368709120
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(from = "model_file::Repr", into = "model_file::Repr")
+)]
+pub struct ModelFile {
+    /// The file name, within the model's repository.
+    pub file: String,
+
+    /// The SHA-256 hash of the file, in hex, to verify it with.
+    pub sha256: Option<String>,
+
+    /// The size of the file in bytes, to choose a variant that fits with.
+    pub size: Option<u64>,
+}
+
+impl From<String> for ModelFile {
+    fn from(file: String) -> Self {
+        Self {
+            file,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for ModelFile {
+    fn from(file: &str) -> Self {
+        Self::from(String::from(file))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod model_file {
+    use super::*;
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    #[serde(untagged)]
+    pub enum Repr {
+        File(String),
+        Details(Details),
+    }
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Details {
+        file: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+    }
+
+    impl From<Repr> for ModelFile {
+        fn from(repr: Repr) -> Self {
+            match repr {
+                Repr::File(file) => file.into(),
+                Repr::Details(Details { file, sha256, size }) => Self { file, sha256, size },
+            }
+        }
+    }
+
+    impl From<ModelFile> for Repr {
+        fn from(model: ModelFile) -> Self {
+            match model {
+                ModelFile {
+                    file,
+                    sha256: None,
+                    size: None,
+                } => Repr::File(file),
+                ModelFile { file, sha256, size } => Repr::Details(Details { file, sha256, size }),
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -417,7 +602,8 @@ pub struct Configuration {
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct ConfigurationVariable {
     /// The name of the variable. Configured variables are by default saved in
-    /// `~/.asimov/configs/$profile/$module/$name`.
+    /// `~/.asimov/configs/$profile/$module/$name`, or in a project's
+    /// `.asimov/configs/$module/$name`.
     pub name: String,
 
     /// Optional description to provide information about the variable.
@@ -445,6 +631,7 @@ pub struct ConfigurationVariable {
 
     /// Whether the value is sensitive, such as a credential or an access token.
     /// Tools must not display secret values unless explicitly asked to.
+    /// Configured secret values are encrypted at rest.
     #[cfg_attr(
         feature = "serde",
         serde(default, skip_serializing_if = "core::ops::Not::not")
@@ -459,6 +646,47 @@ pub struct ConfigurationVariable {
         serde(default, skip_serializing_if = "core::ops::Not::not")
     )]
     pub optional: bool,
+
+    /// The type of the value, a string if not given.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, rename = "type", skip_serializing_if = "Option::is_none")
+    )]
+    pub value_type: Option<VariableType>,
+
+    /// The allowed values of an [`VariableType::Enum`] variable.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "empty_vec_if_null",
+            skip_serializing_if = "Vec::is_empty"
+        )
+    )]
+    pub choices: Vec<String>,
+
+    /// A regular expression which the whole value must match.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub pattern: Option<String>,
+
+    /// The smallest allowed value of an integer variable, or else the
+    /// smallest allowed length of the value in characters.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub min: Option<i64>,
+
+    /// The largest allowed value of an integer variable, or else the largest
+    /// allowed length of the value in characters.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max: Option<i64>,
 }
 
 impl ConfigurationVariable {
@@ -466,6 +694,45 @@ impl ConfigurationVariable {
     pub fn is_required(&self) -> bool {
         !self.optional && self.default_value.is_none()
     }
+
+    pub fn value_type(&self) -> VariableType {
+        self.value_type.unwrap_or_default()
+    }
+}
+
+/// The type of the value of a [`ConfigurationVariable`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum VariableType {
+    #[default]
+    String,
+    /// A signed 64-bit integer.
+    Integer,
+    /// `true` or `false`, also given as `yes`/`no`, `on`/`off` or `1`/`0`.
+    Boolean,
+    /// An absolute URL.
+    Url,
+    /// One of the [`ConfigurationVariable::choices`].
+    Enum,
+    /// Any JSON value.
+    Json,
+}
+
+impl core::fmt::Display for VariableType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Boolean => "boolean",
+            Self::Url => "url",
+            Self::Enum => "enum",
+            Self::Json => "json",
+        })
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -483,21 +750,38 @@ mod ordered {
     use super::*;
     use alloc::fmt;
     use serde::{
-        Deserializer,
+        Deserializer, Serializer,
         de::{MapAccess, Visitor},
+        ser::SerializeMap,
     };
 
-    pub fn deserialize_ordered<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+    pub fn serialize_ordered<S>(
+        items: &[(String, ModelFile)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(items.len()))?;
+        for (key, value) in items {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize_ordered<'de, D>(
+        deserializer: D,
+    ) -> Result<Vec<(String, ModelFile)>, D::Error>
     where
         D: Deserializer<'de>,
     {
         struct OrderedVisitor;
 
         impl<'de> Visitor<'de> for OrderedVisitor {
-            type Value = Vec<(String, String)>;
+            type Value = Vec<(String, ModelFile)>;
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("a map of string keys to string values (preserving order)")
+                f.write_str("a map of string keys to model files (preserving order)")
             }
 
             fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
@@ -505,7 +789,7 @@ mod ordered {
                 A: MapAccess<'de>,
             {
                 let mut items = Vec::with_capacity(access.size_hint().unwrap_or(0));
-                while let Some((k, v)) = access.next_entry::<String, String>()? {
+                while let Some((k, v)) = access.next_entry::<String, ModelFile>()? {
                     items.push((k, v));
                 }
                 Ok(items)
@@ -539,6 +823,12 @@ requires:
         small: small_url
         medium: medium_url
         large: large_url
+      hf:third/model:
+        small: small_url
+        large:
+          file: large_url
+          sha256: abc123
+          size: 1024
 
 provides:
   programs:
@@ -569,6 +859,15 @@ config:
     - name: nickname
       optional: true
 
+    - name: port
+      type: integer
+      min: 1
+      max: 65535
+
+    - name: level
+      type: enum
+      choices: [debug, info]
+
 "#;
 
         let dec: ModuleManifest = serde_yaml_ng::from_str(yaml).expect("deser should succeed");
@@ -639,6 +938,7 @@ config:
                 default_value: Some("foobar".into()),
                 secret: false,
                 optional: false,
+                ..Default::default()
             }),
         );
         assert!(!variables[0].is_required());
@@ -648,13 +948,20 @@ config:
 
         assert!(variables[2].optional);
         assert!(!variables[2].is_required());
+        assert_eq!(variables[2].value_type(), VariableType::String);
+
+        assert_eq!(variables[3].value_type(), VariableType::Integer);
+        assert_eq!((variables[3].min, variables[3].max), (Some(1), Some(65535)));
+
+        assert_eq!(variables[4].value_type(), VariableType::Enum);
+        assert_eq!(variables[4].choices, ["debug", "info"]);
 
         let requires = dec.requires;
 
         assert_eq!(requires.modules.len(), 1);
         assert_eq!(requires.modules.first().unwrap(), "other");
 
-        assert_eq!(requires.models.len(), 2);
+        assert_eq!(requires.models.len(), 3);
 
         assert_eq!(
             requires.models["hf:first/model"],
@@ -669,5 +976,26 @@ config:
                 ("large".into(), "large_url".into())
             ]),
         );
+
+        let third = RequiredModel::Choices(vec![
+            ("small".into(), "small_url".into()),
+            (
+                "large".into(),
+                ModelFile {
+                    file: "large_url".into(),
+                    sha256: Some("abc123".into()),
+                    size: Some(1024),
+                },
+            ),
+        ]);
+        assert_eq!(requires.models["hf:third/model"], third);
+
+        // choices are saved in installed manifests, and read back in order:
+        let json = serde_json::to_string(&third).unwrap();
+        assert_eq!(
+            json,
+            r#"{"small":"small_url","large":{"file":"large_url","sha256":"abc123","size":1024}}"#
+        );
+        assert_eq!(serde_json::from_str::<RequiredModel>(&json).unwrap(), third);
     }
 }
@@ -7,94 +7,172 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use core::{borrow::Borrow, convert::Infallible};
+use core::{borrow::Borrow, convert::Infallible, ops::Deref};
 use error::UrlParseError;
 
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+
 pub mod error;
 
-#[derive(Clone, Debug, Default)]
-pub struct Resolver {
-    modules: BTreeMap<String, Rc<Module>>,
-    file_extensions: BTreeMap<String, Vec<Rc<Module>>>,
-    content_types: BTreeMap<mime::Mime, Vec<Rc<Module>>>,
-    nodes: slab::Slab<Node>,
+#[cfg(feature = "postcard")]
+mod binary;
+
+mod sniff;
+pub use sniff::*;
+
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+pub use shared::*;
+
+/// A resolver that shares its modules with [`Rc`], for single-threaded use.
+pub type Resolver = GenericResolver<Rc<Module>>;
+
+/// A resolver that shares its modules with [`Arc`], which makes it `Send` and
+/// `Sync`. See also [`SharedResolver`] for updating a resolver that is in use.
+#[cfg(target_has_atomic = "ptr")]
+pub type SyncResolver = GenericResolver<Arc<Module>>;
+
+/// A pointer to a [`Module`] of a resolver, such as `Rc<Module>` or `Arc<Module>`.
+pub trait ModulePtr: Clone + Ord + Deref<Target = Module> + From<Module> {}
+
+impl<M> ModulePtr for M where M: Clone + Ord + Deref<Target = Module> + From<Module> {}
+
+/// Resolves URLs, file extensions and content types to the modules that
+/// handle them. Use it through the [`Resolver`] or [`SyncResolver`] aliases.
+#[derive(Clone, Debug)]
+pub struct GenericResolver<M> {
+    modules: BTreeMap<String, M>,
+    file_extensions: BTreeMap<String, Vec<M>>,
+    content_types: BTreeMap<mime::Mime, Vec<M>>,
+    nodes: slab::Slab<Node<M>>,
     roots: BTreeMap<Sect, usize>,
 }
 
-impl Resolver {
+impl<M> Default for GenericResolver<M> {
+    fn default() -> Self {
+        Self {
+            modules: BTreeMap::new(),
+            file_extensions: BTreeMap::new(),
+            content_types: BTreeMap::new(),
+            nodes: slab::Slab::new(),
+            roots: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: ModulePtr> GenericResolver<M> {
     pub fn new() -> Self {
-        Resolver::default()
+        Self::default()
     }
 
-    pub fn resolve(&self, url: &str) -> Result<Vec<Rc<Module>>, UrlParseError> {
+    /// Returns the modules that handle the given URL, most specific match first.
+    ///
+    /// Each result carries the parameters captured by the matching URL pattern,
+    /// see [`GenericResolver::insert_pattern`].
+    pub fn resolve(&self, url: &str) -> Result<Vec<Resolution<M>>, UrlParseError> {
         let input = split_url(url)?;
 
-        // `results` is a set of `(path_length, Module)` items.
-        // `path_length` first so they get sorted by the length of matching path.
-        // We reverse iter this later so that longer paths are returned first.
-        let mut results: BTreeSet<(usize, Rc<Module>)> = BTreeSet::new();
-
-        if matches!(input.first(), Some(Sect::Protocol(proto)) if proto == "file") {
-            if let Some(Sect::Path(filename)) = input.last() {
-                if let Some((_, ext)) = filename.split_once(".") {
-                    self.file_extensions
-                        .get(ext)
-                        .into_iter()
-                        .flatten()
-                        .for_each(|module| {
-                            results.insert((0, module.clone()));
-                        });
-                }
-            }
-        }
-
-        let with_freemove = |node_idx: usize| {
-            // Return the node ID
-            core::iter::once(node_idx)
-                // And the destination ID after following a `FreeMove` path from the node
-                .chain(self.nodes[node_idx].paths.get(&Sect::FreeMove).copied())
-        };
+        // `results` is a set of `(specificity, Resolution)` items.
+        // `specificity` first so they get sorted by how specific the matching rule is.
+        // We reverse iter this later so that more specific matches are returned first.
+        let mut results: BTreeSet<(usize, Resolution<M>)> = BTreeSet::new();
 
         // Initialize with start states that match the first input
-        let start_states: BTreeSet<usize> = self
-            .roots
-            .iter()
-            .filter_map(|(path, &node_idx)| path.matches_input(&input[0]).then_some(node_idx))
-            .collect();
+        let start_states = self.closure(self.roots.iter().filter_map(|(path, &node_idx)| {
+            path.matches_input(&input.sects[0]).then_some(node_idx)
+        }));
 
-        let final_states = if input.len() == 1 {
-            // There is no further input, just get free moves from the start_states
-            start_states.into_iter().flat_map(with_freemove).collect()
-        } else {
-            // Process remaining input
-            input[1..].iter().fold(start_states, |states, sect| {
+        // Process remaining input
+        let final_states = input.sects[1..].iter().fold(start_states, |states, sect| {
+            self.closure(
                 states
                     .into_iter()
                     .flat_map(|node_idx| &self.nodes[node_idx].paths)
-                    .filter_map(|(path, &node_idx)| path.matches_input(sect).then_some(node_idx))
-                    .flat_map(with_freemove)
-                    .collect()
-            })
-        };
+                    .filter_map(|(path, &node_idx)| path.matches_input(sect).then_some(node_idx)),
+            )
+        });
 
-        // Collect all modules from final states
+        // Collect all modules from final states whose query and fragment matchers
+        // also accept the input
         for node in final_states.iter().map(|&idx| &self.nodes[idx]) {
-            for module in &node.modules {
-                results.insert((node.path_length, module.clone()));
+            for rule in &node.rules {
+                if let Some(params) = rule.captures(&input) {
+                    let resolution = Resolution {
+                        module: rule.module.clone(),
+                        params,
+                    };
+                    results.insert((rule.specificity, resolution));
+                }
             }
         }
 
+        // Modules that handle the file extension come after all URL matches
+        let file_modules = match (input.sects.first(), input.sects.last()) {
+            (Some(Sect::Protocol(proto)), Some(Sect::Path(filename))) if proto == "file" => {
+                self.resolve_file_extension(filename)
+            },
+            _ => Vec::new(),
+        };
+
+        let mut seen = BTreeSet::new();
         Ok(results
             .into_iter()
-             // The `results` set is sorted by path_length.
-             // Reverse to prefer longer matches.
+             // The `results` set is sorted by specificity.
+             // Reverse to prefer more specific matches.
             .rev()
-            .map(|(_, module)| module)
+            .map(|(_, resolution)| resolution)
+            .chain(file_modules.into_iter().map(Resolution::from))
+            // A module matched by several rules is returned once, for its most
+            // specific match
+            .filter(|resolution| seen.insert(resolution.module.clone()))
             .collect())
     }
 
-    pub fn resolve_content_type(&self, content_type: &mime::Mime) -> Vec<Rc<Module>> {
-        let mut modules: BTreeSet<Rc<Module>> = BTreeSet::new();
+    /// Returns the modules that handle the extension of the given file name.
+    ///
+    /// Every extension of a file name with multiple dots is tried, longest
+    /// first, so `archive.tar.gz` matches modules handling either `tar.gz` or
+    /// `gz`, in that order. Extensions are matched case-insensitively.
+    pub fn resolve_file_extension(&self, filename: &str) -> Vec<M> {
+        let mut modules: Vec<M> = Vec::new();
+
+        for ext in file_extensions(filename) {
+            for module in self.file_extensions.get(&ext).into_iter().flatten() {
+                if !modules.contains(module) {
+                    modules.push(module.clone());
+                }
+            }
+        }
+
+        modules
+    }
+
+    /// Returns the modules that can read data starting with the given bytes,
+    /// for data that comes without reliable metadata, such as uploads.
+    ///
+    /// Modules that handle the extension of `filename`, if any, are returned
+    /// first, followed by the modules that handle the content type sniffed
+    /// from `prefix` with [`sniff_content_type`].
+    pub fn resolve_bytes(&self, prefix: &[u8], filename: Option<&str>) -> Vec<M> {
+        let mut modules = filename
+            .map(|filename| self.resolve_file_extension(filename))
+            .unwrap_or_default();
+
+        if let Some(content_type) = sniff_content_type(prefix) {
+            for module in self.resolve_content_type(&content_type) {
+                if !modules.contains(&module) {
+                    modules.push(module);
+                }
+            }
+        }
+
+        modules
+    }
+
+    pub fn resolve_content_type(&self, content_type: &mime::Mime) -> Vec<M> {
+        let mut modules: BTreeSet<M> = BTreeSet::new();
 
         let exact = self.content_types.get(content_type);
         modules.extend(exact.into_iter().flatten().cloned());
@@ -136,6 +214,47 @@ impl Resolver {
         Ok(())
     }
 
+    /// Replaces everything that a module handles with what the given manifest
+    /// says it handles, as if the module had never been inserted before.
+    ///
+    /// If the manifest is invalid, the resolver is left unchanged.
+    pub fn replace_manifest(
+        &mut self,
+        manifest: &ModuleManifest,
+    ) -> Result<(), InsertManifestError> {
+        // Validate the manifest before changing anything:
+        Self::new().insert_manifest(manifest)?;
+
+        self.remove_module(&manifest.name);
+        self.insert_manifest(manifest)
+    }
+
+    /// Removes a module along with every URL, file extension and content type
+    /// that it handles. Nodes that are no longer needed by other modules are
+    /// removed as well.
+    ///
+    /// Returns whether the module was in the resolver.
+    pub fn remove_module(&mut self, name: &str) -> bool {
+        let Some(module) = self.modules.remove(name) else {
+            return false;
+        };
+
+        self.file_extensions.retain(|_, modules| {
+            modules.retain(|m| *m != module);
+            !modules.is_empty()
+        });
+        self.content_types.retain(|_, modules| {
+            modules.retain(|m| *m != module);
+            !modules.is_empty()
+        });
+        for (_, node) in &mut self.nodes {
+            node.rules.retain(|rule| rule.module != module);
+        }
+        self.prune_nodes();
+
+        true
+    }
+
     pub fn insert_content_type(
         &mut self,
         module: &str,
@@ -160,7 +279,7 @@ impl Resolver {
         let ext = file_extension
             .strip_prefix(".")
             .unwrap_or(file_extension)
-            .to_string();
+            .to_lowercase();
 
         self.file_extensions.entry(ext).or_default().push(module);
 
@@ -173,34 +292,93 @@ impl Resolver {
 
         // Add a free move back to itself from the `FreeMove` node. (represents a protocol as a prefix):
         self.nodes[node_idx].paths.insert(Sect::FreeMove, node_idx);
-        self.nodes[node_idx].modules.insert(module);
+        self.nodes[node_idx].insert_rule(Rule::new(module, 1));
 
         Ok(())
     }
+    /// Adds a URL prefix handled by a module.
+    ///
+    /// The prefix matches any URL that begins with its protocol, domain and
+    /// path segments. If the prefix has a query string, the path must instead
+    /// match exactly and the query parameters are matched as in
+    /// [`GenericResolver::insert_pattern`].
     pub fn insert_prefix(&mut self, module: &str, prefix: &str) -> Result<(), UrlParseError> {
-        let mut path = split_url(prefix)?;
-        // Add a `FreeMove` node at the end of the path to separate the prefix from
-        // patterns at the same node
-        path.push(Sect::FreeMove);
+        let SplitUrl {
+            mut sects,
+            query,
+            fragment,
+        } = split_url(prefix)?;
+        let specificity = sects.len();
+        let has_query = !query.is_empty();
+
+        if !has_query {
+            // Add a `FreeMove` node at the end of the path to separate the prefix from
+            // patterns at the same node
+            sects.push(Sect::FreeMove);
+        }
         let module = self.add_module(module);
-        let node_idx = self.get_or_create_node(&path);
+        let node_idx = self.get_or_create_node(&sects);
 
-        // Add a free move back to itself from the `FreeMove` node. Enables matching
-        // zero-or-more of anything:
-        self.nodes[node_idx].paths.insert(Sect::FreeMove, node_idx);
-        self.nodes[node_idx].modules.insert(module);
+        if !has_query {
+            // Add a free move back to itself from the `FreeMove` node. Enables matching
+            // zero-or-more of anything:
+            self.nodes[node_idx].paths.insert(Sect::FreeMove, node_idx);
+        }
+        let rule = Rule::new(module, specificity).with_matchers(query, fragment);
+        self.nodes[node_idx].insert_rule(rule);
 
         Ok(())
     }
+    /// Adds a URL pattern handled by a module.
+    ///
+    /// A pattern matches URLs with the same protocol, domain and path segments,
+    /// where:
+    /// - a `*` domain segment (`https://*.example.org/`) matches zero or more subdomains,
+    /// - a `:name` path segment (`https://example.org/:name`) matches any single
+    ///   path segment and captures it as the `name` parameter,
+    /// - a `key=value` query parameter requires the URL to have that parameter with
+    ///   that value, a `key=:name` parameter requires a non-empty value and captures
+    ///   it as the `name` parameter, and a bare `key` (or `key=`) requires only that the
+    ///   parameter is present. Parameters may appear in any order, and the URL may
+    ///   have parameters that the pattern does not mention,
+    /// - a `#fragment` requires the URL to have that fragment, and `#:name` captures
+    ///   any non-empty fragment as the `name` parameter. Without a fragment in the
+    ///   pattern, the fragment of the URL is ignored.
     pub fn insert_pattern(&mut self, module: &str, pattern: &str) -> Result<(), UrlParseError> {
-        let path: Vec<Sect> = split_url(pattern)?
+        let SplitUrl {
+            sects,
+            query,
+            fragment,
+        } = split_url(pattern)?;
+
+        let mut path_captures = Vec::new();
+        let mut path_index = 0;
+        let path: Vec<Sect> = sects
             .into_iter()
             .map(Sect::into_pattern)
+            .map(|sect| {
+                if let Sect::WildcardPath(name) = &sect
+                    && !name.is_empty()
+                {
+                    path_captures.push((path_index, name.clone()));
+                }
+                if matches!(sect, Sect::Path(_) | Sect::WildcardPath(_)) {
+                    path_index += 1;
+                }
+                sect
+            })
             .collect();
+
+        let specificity = path
+            .iter()
+            .filter(|sect| **sect != Sect::WildcardDomain)
+            .count();
         let module = self.add_module(module);
         let node_idx = self.get_or_create_node(&path);
 
-        self.nodes[node_idx].modules.insert(module);
+        let mut rule = Rule::new(module, specificity).with_matchers(query, fragment);
+        rule.path_captures = path_captures;
+        self.nodes[node_idx].insert_rule(rule);
 
         Ok(())
     }
@@ -216,7 +394,7 @@ impl Resolver {
             source,
         })?;
 
-        let mut resolver = Resolver::new();
+        let mut resolver = Self::new();
 
         for entry in dir {
             let entry = entry.map_err(|source| FromDirError::ManifestDirIo {
@@ -258,7 +436,7 @@ impl Resolver {
         I: Iterator<Item = T>,
         T: Borrow<ModuleManifest>,
     {
-        iter.try_fold(Resolver::default(), |mut r, m| {
+        iter.try_fold(Self::default(), |mut r, m| {
             r.insert_manifest(m.borrow())?;
             Ok(r)
         })
@@ -272,45 +450,160 @@ impl Resolver {
             .or_insert_with(|| self.nodes.insert(Node::default()));
 
         path.iter()
-            .enumerate()
             .skip(1) // first one was already inserted to `self.roots`
-            .fold(root_idx, |cur_idx, (path_length, sect)| {
-                match (self.nodes[cur_idx].paths.get(sect), sect) {
-                    (Some(&idx), _sect) => idx,
-                    (None, Sect::WildcardDomain) => {
-                        // If the sect is a wildcard domain add a link to self, this will also match multiple subdomains.
-                        self.nodes[cur_idx].paths.insert(sect.clone(), cur_idx);
-                        cur_idx
-                    },
-                    (None, sect) => {
+            .fold(root_idx, |cur_idx, sect| {
+                // Wildcard path segments share a transition regardless of the
+                // parameter name they capture
+                let key = sect.to_key();
+                match self.nodes[cur_idx].paths.get(&key) {
+                    Some(&idx) => idx,
+                    None => {
                         // Create a new node
-                        let new_node_idx = self.nodes.insert(Node {
-                            path_length,
-                            ..Default::default()
-                        });
+                        let new_node_idx = self.nodes.insert(Node::default());
+
+                        if key == Sect::WildcardDomain {
+                            // A wildcard domain node links to itself so that it matches
+                            // multiple subdomains. The transition to it is also followed
+                            // without consuming input, so that it matches no subdomains.
+                            self.nodes[new_node_idx]
+                                .paths
+                                .insert(Sect::WildcardDomain, new_node_idx);
+                        }
 
                         // Add the transition from current node to new node
-                        self.nodes[cur_idx].paths.insert(sect.clone(), new_node_idx);
+                        self.nodes[cur_idx].paths.insert(key, new_node_idx);
                         new_node_idx
                     },
                 }
             })
     }
 
-    fn add_module(&mut self, name: &str) -> Rc<Module> {
+    /// Extends the given states with every state reachable from them without
+    /// consuming input, through `FreeMove` and `WildcardDomain` transitions.
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut closure = BTreeSet::new();
+        let mut pending: Vec<usize> = states.into_iter().collect();
+
+        while let Some(node_idx) = pending.pop() {
+            if !closure.insert(node_idx) {
+                continue;
+            }
+            let paths = &self.nodes[node_idx].paths;
+            pending.extend(paths.get(&Sect::FreeMove));
+            pending.extend(paths.get(&Sect::WildcardDomain));
+        }
+
+        closure
+    }
+
+    /// Removes the nodes that have no rules and no transitions leading to a
+    /// node with rules.
+    fn prune_nodes(&mut self) {
+        let mut needed: BTreeSet<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| !node.rules.is_empty())
+            .map(|(idx, _)| idx)
+            .collect();
+
+        // Walk back from the nodes with rules until no more nodes are needed
+        loop {
+            let before = needed.len();
+            for (idx, node) in &self.nodes {
+                if !needed.contains(&idx)
+                    && node
+                        .paths
+                        .values()
+                        .any(|next| *next != idx && needed.contains(next))
+                {
+                    needed.insert(idx);
+                }
+            }
+            if needed.len() == before {
+                break;
+            }
+        }
+
+        self.nodes.retain(|idx, _| needed.contains(&idx));
+        for (_, node) in &mut self.nodes {
+            node.paths.retain(|_, next| needed.contains(next));
+        }
+        self.roots.retain(|_, idx| needed.contains(idx));
+    }
+
+    /// Whether the nodes reachable from `self_idx` and `other_idx` have the
+    /// same rules and transitions, regardless of their indices. `seen` maps
+    /// the nodes of `self` to the nodes of `other` compared so far.
+    fn same_nodes(
+        &self,
+        other: &Self,
+        self_idx: usize,
+        other_idx: usize,
+        seen: &mut (BTreeMap<usize, usize>, BTreeSet<usize>),
+    ) -> bool {
+        if let Some(&idx) = seen.0.get(&self_idx) {
+            return idx == other_idx;
+        }
+        if !seen.1.insert(other_idx) {
+            // Two nodes of `self` can't be the same node of `other`
+            return false;
+        }
+        seen.0.insert(self_idx, other_idx);
+
+        let (a, b) = (&self.nodes[self_idx], &other.nodes[other_idx]);
+        a.rules.len() == b.rules.len()
+            && a.rules.iter().all(|rule| b.rules.contains(rule))
+            && a.paths.len() == b.paths.len()
+            && a.paths.iter().all(|(sect, &a_next)| {
+                b.paths
+                    .get(sect)
+                    .is_some_and(|&b_next| self.same_nodes(other, a_next, b_next, seen))
+            })
+    }
+
+    fn add_module(&mut self, name: &str) -> M {
         let name = name.to_string();
         self.modules
             .entry(name.clone())
-            .or_insert_with(|| Rc::new(Module { name }))
+            .or_insert_with(|| M::from(Module { name }))
             .clone()
     }
 }
 
-impl TryFrom<&[ModuleManifest]> for Resolver {
+/// Resolvers are equal when they handle the same URLs, file extensions and
+/// content types with the same modules, regardless of the order in which the
+/// modules were inserted and removed.
+impl<M: ModulePtr> PartialEq for GenericResolver<M> {
+    fn eq(&self, other: &Self) -> bool {
+        fn same_sets<K: Ord, M: Ord>(a: &BTreeMap<K, Vec<M>>, b: &BTreeMap<K, Vec<M>>) -> bool {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|((a_key, a), (b_key, b))| {
+                    a_key == b_key
+                        && a.iter().collect::<BTreeSet<_>>() == b.iter().collect::<BTreeSet<_>>()
+                })
+        }
+
+        let mut seen = (BTreeMap::new(), BTreeSet::new());
+        self.modules == other.modules
+            && same_sets(&self.file_extensions, &other.file_extensions)
+            && same_sets(&self.content_types, &other.content_types)
+            && self.nodes.len() == other.nodes.len()
+            && self.roots.len() == other.roots.len()
+            && self.roots.iter().all(|(sect, &self_idx)| {
+                other.roots.get(sect).is_some_and(|&other_idx| {
+                    self.same_nodes(other, self_idx, other_idx, &mut seen)
+                })
+            })
+    }
+}
+
+impl<M: ModulePtr> Eq for GenericResolver<M> {}
+
+impl<M: ModulePtr> TryFrom<&[ModuleManifest]> for GenericResolver<M> {
     type Error = InsertManifestError;
 
     fn try_from(value: &[ModuleManifest]) -> Result<Self, Self::Error> {
-        Resolver::try_from_iter(value.iter())
+        Self::try_from_iter(value.iter())
     }
 }
 
@@ -319,14 +612,162 @@ pub struct Module {
     pub name: String,
 }
 
-#[derive(Clone, Debug, Default)]
-struct Node {
+/// A module returned by [`GenericResolver::resolve`], along with the parameters
+/// that the matching URL pattern captured from the URL.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Resolution<M = Rc<Module>> {
+    pub module: M,
+    pub params: BTreeMap<String, String>,
+}
+
+impl<M> From<M> for Resolution<M> {
+    fn from(module: M) -> Self {
+        Self {
+            module,
+            params: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: Deref<Target = Module>> Deref for Resolution<M> {
+    type Target = Module;
+
+    fn deref(&self) -> &Self::Target {
+        &self.module
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node<M> {
     paths: BTreeMap<Sect, usize>,
-    modules: BTreeSet<Rc<Module>>,
-    path_length: usize,
+    rules: Vec<Rule<M>>,
+}
+
+impl<M> Default for Node<M> {
+    fn default() -> Self {
+        Self {
+            paths: BTreeMap::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl<M: PartialEq> Node<M> {
+    fn insert_rule(&mut self, rule: Rule<M>) {
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+        }
+    }
+}
+
+/// The module to return once the input has reached a node, provided that the
+/// parts of the URL which are not matched by the node graph also match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Rule<M> {
+    module: M,
+    /// The number of sections, query parameters and fragments the rule matches
+    specificity: usize,
+    /// The indices of the path segments to capture, with their parameter names
+    path_captures: Vec<(usize, String)>,
+    query: Vec<(String, Matcher)>,
+    fragment: Option<Matcher>,
+}
+
+impl<M> Rule<M> {
+    fn new(module: M, specificity: usize) -> Self {
+        Self {
+            module,
+            specificity,
+            path_captures: Vec::new(),
+            query: Vec::new(),
+            fragment: None,
+        }
+    }
+
+    fn with_matchers(mut self, query: Vec<(String, String)>, fragment: Option<String>) -> Self {
+        self.query = query
+            .into_iter()
+            .map(|(name, value)| (name, Matcher::new(value)))
+            .collect();
+        self.fragment = fragment.map(Matcher::new);
+        self.specificity += self.query.len() + usize::from(self.fragment.is_some());
+        self
+    }
+
+    /// Returns the captured parameters if the query and fragment of the input
+    /// match, or `None` otherwise.
+    fn captures(&self, input: &SplitUrl) -> Option<BTreeMap<String, String>> {
+        let mut params = BTreeMap::new();
+
+        if !self.path_captures.is_empty() {
+            let segments: Vec<&String> = input
+                .sects
+                .iter()
+                .filter_map(|sect| match sect {
+                    Sect::Path(segment) => Some(segment),
+                    _ => None,
+                })
+                .collect();
+            for (index, name) in &self.path_captures {
+                params.insert(name.clone(), (*segments.get(*index)?).clone());
+            }
+        }
+
+        for (name, matcher) in &self.query {
+            let matched = input
+                .query
+                .iter()
+                .filter(|(key, _)| key == name)
+                .find_map(|(_, value)| matcher.matches(value))?;
+            params.extend(matched);
+        }
+
+        if let Some(matcher) = &self.fragment {
+            let matched = matcher.matches(input.fragment.as_deref()?)?;
+            params.extend(matched);
+        }
+
+        Some(params)
+    }
+}
+
+/// Matches a query parameter value or a fragment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "postcard", derive(serde::Deserialize, serde::Serialize))]
+enum Matcher {
+    /// `q` or `q=` from `https://example.org/?q=`, matches any value
+    Any,
+    /// `example` from `https://example.org/?q=example`, matches a literal value
+    Literal(String),
+    /// `:query` from `https://example.org/?q=:query`, matches and captures any non-empty value
+    Capture(String),
+}
+
+impl Matcher {
+    fn new(value: String) -> Self {
+        if value.is_empty() {
+            Matcher::Any
+        } else if let Some(name) = value.strip_prefix(':') {
+            Matcher::Capture(name.into())
+        } else {
+            Matcher::Literal(value)
+        }
+    }
+
+    /// Returns the captured parameter, if any, when the value matches.
+    fn matches(&self, value: &str) -> Option<Option<(String, String)>> {
+        match self {
+            Matcher::Any => Some(None),
+            Matcher::Literal(literal) => (literal == value).then_some(None),
+            Matcher::Capture(name) => {
+                (!value.is_empty()).then(|| Some((name.clone(), value.into())))
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "postcard", derive(serde::Deserialize, serde::Serialize))]
 enum Sect {
     /// `https` from `https://example.org/`, matches the protocol (a.k.a. scheme) of an URL
     Protocol(String),
@@ -336,14 +777,9 @@ enum Sect {
     WildcardDomain,
     /// `file` and `path` from `https://example.org/file/path`, match literal path segments
     Path(String),
-    /// `:name` from `https://example.org/file/:name`, matches any single path segment
-    WildcardPath,
-    /// `q` from `https://example.org/?q=example`, matches a parameter name
-    QueryParamName(String),
-    /// `example` from `https://example.org/?q=example`, matches a literal parameter value
-    QueryParamValue(String),
-    /// `:query` from `https://example.org/?q=:query`, matches any query param value
-    WildcardQueryParamValue,
+    /// `:name` from `https://example.org/file/:name`, matches any single path segment.
+    /// The name is only kept in patterns, transitions between nodes use an empty name.
+    WildcardPath(String),
     /// Matches a single section of any kind
     FreeMove,
 }
@@ -352,23 +788,28 @@ impl Sect {
     /// Transform a sect that matches a pattern format to a wildcard.
     /// - If a domain section is "*", make it a wildcard domain pattern
     /// - If a path section begins with ":" ("/:foo/:bar"), make it a wildcard path pattern
-    /// - If the value of a query parameter begins with ":" ("q=:query"), make it a wildcard query param pattern
     pub fn into_pattern(self) -> Self {
         match self {
             Sect::Domain(p) if p == "*" => Sect::WildcardDomain,
-            Sect::Path(p) if p.starts_with(':') => Sect::WildcardPath,
-            Sect::QueryParamValue(p) if p.starts_with(':') => Sect::WildcardQueryParamValue,
+            Sect::Path(p) if p.starts_with(':') => Sect::WildcardPath(p[1..].into()),
             _ => self,
         }
     }
 
+    /// The sect to use as the transition between nodes.
+    fn to_key(&self) -> Self {
+        match self {
+            Sect::WildcardPath(_) => Sect::WildcardPath(String::new()),
+            _ => self.clone(),
+        }
+    }
+
     fn matches_input(&self, input: &Self) -> bool {
         use Sect::*;
         match (self, input) {
             (a, b) if a == b => true,
             (WildcardDomain, Domain(_)) => true,
-            (WildcardPath, Path(_)) => true,
-            (WildcardQueryParamValue, QueryParamValue(_)) => true,
+            (WildcardPath(_), Path(_)) => true,
             // As a special case if the path section is a `FreeMove` then always accept it.
             (FreeMove, _) => true,
             _ => false,
@@ -376,16 +817,39 @@ impl Sect {
     }
 }
 
+/// The extensions of a file name, longest first: `tar.gz` and `gz` for
+/// `archive.tar.gz`.
+fn file_extensions(filename: &str) -> impl Iterator<Item = String> {
+    let name = filename.to_lowercase();
+
+    let starts: Vec<usize> = name
+        .match_indices('.')
+        .map(|(index, _)| index + 1)
+        .filter(|&start| start < name.len())
+        .collect();
+
+    starts.into_iter().map(move |start| name[start..].into())
+}
+
+/// An URL split into the sections matched by the node graph, and the query
+/// parameters and fragment matched by [`Rule`]s.
+#[derive(Clone, Debug, Default)]
+struct SplitUrl {
+    sects: Vec<Sect>,
+    query: Vec<(String, String)>,
+    fragment: Option<String>,
+}
+
 /// Split and URL into sections that we care about. This is effectively a tokenizer.
-fn split_url(url: &str) -> Result<Vec<Sect>, UrlParseError> {
+fn split_url(url: &str) -> Result<SplitUrl, UrlParseError> {
     if url.is_empty() {
         return Err(UrlParseError::EmptyUrl);
     }
 
-    let mut res = Vec::new();
+    let mut res = SplitUrl::default();
 
     if !url.contains(':') {
-        res.push(Sect::Protocol(url.into()));
+        res.sects.push(Sect::Protocol(url.into()));
         return Ok(res);
     }
 
@@ -395,7 +859,7 @@ fn split_url(url: &str) -> Result<Vec<Sect>, UrlParseError> {
     })?;
 
     let proto = url.scheme();
-    res.push(Sect::Protocol(proto.into()));
+    res.sects.push(Sect::Protocol(proto.into()));
 
     if let Some(host) = url.host_str() {
         let mut host_parts: Vec<&str> = host.split('.').rev().collect();
@@ -408,27 +872,30 @@ fn split_url(url: &str) -> Result<Vec<Sect>, UrlParseError> {
         }
 
         for part in host_parts {
-            res.push(Sect::Domain(part.into()));
+            res.sects.push(Sect::Domain(part.into()));
         }
     }
 
     if url.cannot_be_a_base() {
-        res.push(Sect::Path(url.path().into()))
+        res.sects.push(Sect::Path(url.path().into()))
     } else if let Some(path_parts) = url.path_segments() {
         for part in path_parts {
             if part.is_empty() {
                 continue;
             }
-            res.push(Sect::Path(part.into()));
+            res.sects.push(Sect::Path(part.into()));
         }
     }
 
-    for (k, v) in url.query_pairs() {
-        res.push(Sect::QueryParamName(k.into()));
-        if !v.is_empty() {
-            res.push(Sect::QueryParamValue(v.into()));
-        }
-    }
+    res.query = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    res.fragment = url
+        .fragment()
+        .filter(|fragment| !fragment.is_empty())
+        .map(String::from);
 
     Ok(res)
 }
@@ -616,4 +1083,242 @@ mod test {
         let mut it = resolver.resolve("https://example.org").unwrap().into_iter();
         assert_eq!(None, it.next());
     }
+
+    #[test]
+    fn wildcard_domain_doesnt_leak_to_literal_patterns() {
+        let mut resolver = Resolver::new();
+        resolver
+            .insert_pattern("any-wiki", "https://*.wikipedia.org/wiki/:title")
+            .unwrap();
+        resolver
+            .insert_pattern("wiki", "https://wikipedia.org/about")
+            .unwrap();
+
+        let results = resolver.resolve("https://en.wikipedia.org/about").unwrap();
+        assert!(results.is_empty(), "the literal pattern shouldn't match");
+
+        let results = resolver.resolve("https://wikipedia.org/about").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "wiki");
+
+        for input in [
+            "https://en.m.wikipedia.org/wiki/Rust",
+            "https://en.wikipedia.org/wiki/Rust",
+            "https://wikipedia.org/wiki/Rust",
+        ] {
+            let results = resolver.resolve(input).unwrap();
+            assert_eq!(results.len(), 1, "input={input}");
+            assert_eq!(results[0].name, "any-wiki");
+            assert_eq!(results[0].params["title"], "Rust");
+        }
+    }
+
+    #[test]
+    fn captures() {
+        let mut resolver = Resolver::new();
+        resolver
+            .insert_pattern("x-followers", "https://x.com/:account/followers")
+            .unwrap();
+        resolver
+            .insert_pattern("x-following", "https://x.com/:user/following")
+            .unwrap();
+        resolver.insert_pattern("near-tx", "near://tx/:id").unwrap();
+        resolver
+            .insert_pattern("docs", "https://example.org/docs#:section")
+            .unwrap();
+
+        let results = resolver.resolve("https://x.com/asimov/followers").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "x-followers");
+        assert_eq!(
+            results[0].params,
+            BTreeMap::from([("account".into(), "asimov".into())])
+        );
+
+        let results = resolver.resolve("https://x.com/asimov/following").unwrap();
+        assert_eq!(results[0].name, "x-following");
+        assert_eq!(results[0].params["user"], "asimov");
+
+        let results = resolver.resolve("near://tx/1234").unwrap();
+        assert_eq!(results[0].params["id"], "1234");
+
+        let results = resolver.resolve("https://example.org/docs#intro").unwrap();
+        assert_eq!(results[0].name, "docs");
+        assert_eq!(results[0].params["section"], "intro");
+
+        let results = resolver.resolve("https://example.org/docs").unwrap();
+        assert!(results.is_empty(), "a fragment capture requires a fragment");
+    }
+
+    #[test]
+    fn query_params() {
+        let mut resolver = Resolver::new();
+        resolver
+            .insert_pattern("video", "https://youtube.com/watch?v=:id")
+            .unwrap();
+        resolver
+            .insert_pattern("playlist", "https://youtube.com/watch?list=:list&v=:id")
+            .unwrap();
+        resolver
+            .insert_pattern("music", "https://youtube.com/watch?v=:id&feature=music")
+            .unwrap();
+        resolver
+            .insert_prefix("search", "https://google.com/search?q=")
+            .unwrap();
+
+        // Parameters match in any order, and extra parameters are allowed:
+        let results = resolver
+            .resolve("https://www.youtube.com/watch?t=10&v=abc")
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "video");
+        assert_eq!(results[0].params["id"], "abc");
+
+        // More specific matches are returned first:
+        let results = resolver
+            .resolve("https://youtube.com/watch?v=abc&list=xyz")
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].name, "playlist");
+        assert_eq!(results[0].params["list"], "xyz");
+        assert_eq!(results[0].params["id"], "abc");
+        assert_eq!(results[1].name, "video");
+
+        // Literal values must match exactly:
+        let results = resolver
+            .resolve("https://youtube.com/watch?v=abc&feature=music")
+            .unwrap();
+        assert_eq!(results[0].name, "music");
+        let results = resolver
+            .resolve("https://youtube.com/watch?v=abc&feature=share")
+            .unwrap();
+        assert!(results.iter().all(|out| out.name != "music"));
+
+        // Captures require a non-empty value:
+        let results = resolver.resolve("https://youtube.com/watch?v=").unwrap();
+        assert!(results.is_empty());
+        let results = resolver.resolve("https://youtube.com/watch").unwrap();
+        assert!(results.is_empty());
+
+        // A prefix with a query matches the exact path, with any parameter value:
+        let results = resolver
+            .resolve("https://google.com/search?hl=en&q=rust")
+            .unwrap();
+        assert_eq!(results[0].name, "search");
+        assert!(results[0].params.is_empty());
+        let results = resolver
+            .resolve("https://google.com/search/more?q=rust")
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn sync_resolver() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut resolver = SyncResolver::new();
+        resolver.insert_pattern("near-tx", "near://tx/:id").unwrap();
+        assert_send_sync(&resolver);
+
+        let results = resolver.resolve("near://tx/1234").unwrap();
+        assert_send_sync(&results);
+        assert_eq!(results[0].name, "near-tx");
+        assert_eq!(results[0].params["id"], "1234");
+    }
+
+    #[test]
+    fn shared_resolver_updates_dont_affect_snapshots() {
+        let shared = SharedResolver::default();
+        shared
+            .update(|resolver| resolver.insert_protocol("near", "near"))
+            .unwrap();
+
+        let before = shared.load();
+        shared
+            .update(|resolver| resolver.insert_protocol("ipfs", "ipfs"))
+            .unwrap();
+        let after = shared.load();
+
+        assert!(before.resolve("ipfs://foo").unwrap().is_empty());
+        assert_eq!(after.resolve("ipfs://foo").unwrap()[0].name, "ipfs");
+        assert_eq!(after.resolve("near://foo").unwrap()[0].name, "near");
+
+        let failed = shared.update(|resolver| resolver.insert_prefix("bad", ""));
+        assert!(failed.is_err());
+        assert!(!shared.load().modules.contains_key("bad"));
+
+        let previous = shared.store(SyncResolver::new());
+        assert!(Arc::ptr_eq(&previous, &after));
+        assert!(shared.load().resolve("near://foo").unwrap().is_empty());
+    }
+
+    #[test]
+    fn multi_dot_file_extensions() {
+        let mut resolver = Resolver::new();
+        resolver.insert_file_extension("json", "json").unwrap();
+        resolver.insert_file_extension("gz", "gz").unwrap();
+        resolver.insert_file_extension("tar-gz", ".tar.gz").unwrap();
+        resolver
+            .insert_file_extension("maildir", ".maildir")
+            .unwrap();
+
+        let names = |filename| -> Vec<String> {
+            resolver
+                .resolve_file_extension(filename)
+                .iter()
+                .map(|module| module.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names("my.data.json"), ["json"]);
+        assert_eq!(names("archive.tar.gz"), ["tar-gz", "gz"]);
+        assert_eq!(names("ARCHIVE.TAR.GZ"), ["tar-gz", "gz"]);
+        assert_eq!(names(".maildir"), ["maildir"]);
+        assert_eq!(names("json"), [] as [&str; 0]);
+        assert_eq!(names("json."), [] as [&str; 0]);
+
+        let results = resolver.resolve("file:///tmp/my.data.json").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "json");
+    }
+
+    #[test]
+    fn resolve_bytes() {
+        let mut resolver = Resolver::new();
+        resolver
+            .insert_content_type("pdf", "application/pdf".parse().unwrap())
+            .unwrap();
+        resolver
+            .insert_content_type("json", mime::APPLICATION_JSON)
+            .unwrap();
+        resolver
+            .insert_content_type("text", mime::TEXT_STAR)
+            .unwrap();
+        resolver.insert_file_extension("jsonl", "jsonl").unwrap();
+
+        let names = |prefix: &[u8], filename| -> Vec<String> {
+            resolver
+                .resolve_bytes(prefix, filename)
+                .iter()
+                .map(|module| module.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(b"%PDF-1.7\n%\xe2\xe3", None), ["pdf"]);
+        assert_eq!(names(b"%PDF-1.7\n", Some("upload.bin")), ["pdf"]);
+        assert_eq!(names(b"\xef\xbb\xbf[{\"a\": 1}]", None), ["json"]);
+        assert_eq!(names(b"[section]\nkey = value\n", None), ["text"]);
+        assert_eq!(
+            names(b"{\"a\": 1}\n{\"a\": 2}\n", Some("data.jsonl")),
+            ["jsonl", "json"],
+        );
+        // a prefix may end in the middle of a character:
+        assert_eq!(
+            names("hello, wörld".as_bytes()[..9].as_ref(), None),
+            ["text"]
+        );
+        assert_eq!(names("wö".as_bytes()[..2].as_ref(), None), ["text"]);
+        assert_eq!(names(b"\x00\x01\x02", None), [] as [&str; 0]);
+        assert_eq!(names(b"", None), [] as [&str; 0]);
+    }
 }
@@ -0,0 +1,200 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{GenericResolver, Matcher, ModulePtr, Node, Rule, Sect, error::DecodeError};
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// The bytes that every resolver file starts with, followed by the format version.
+const MAGIC: &[u8; 8] = b"ASIMOVRS";
+
+/// The version of the format, to be bumped whenever the layout of
+/// [`ResolverData`] changes, so that older files are rebuilt rather than
+/// misread.
+const FORMAT_VERSION: u32 = 1;
+
+/// The serialized form of a resolver, where modules and nodes refer to each
+/// other by their position in `modules` and `nodes`.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ResolverData {
+    modules: Vec<String>,
+    file_extensions: Vec<(String, Vec<usize>)>,
+    content_types: Vec<(String, Vec<usize>)>,
+    nodes: Vec<NodeData>,
+    roots: Vec<(Sect, usize)>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct NodeData {
+    paths: Vec<(Sect, usize)>,
+    rules: Vec<RuleData>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct RuleData {
+    module: usize,
+    specificity: usize,
+    path_captures: Vec<(usize, String)>,
+    query: Vec<(String, Matcher)>,
+    fragment: Option<Matcher>,
+}
+
+impl<M: ModulePtr> GenericResolver<M> {
+    /// Serializes the resolver into a compact binary form, which
+    /// [`GenericResolver::from_bytes`] loads much faster than building the
+    /// resolver again from module manifests.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let modules: BTreeMap<&str, usize> = self
+            .modules
+            .keys()
+            .enumerate()
+            .map(|(pos, name)| (name.as_str(), pos))
+            .collect();
+        let module_pos = |module: &M| modules[module.name.as_str()];
+
+        // Slab indices may have gaps after removals, so nodes are renumbered
+        let nodes: BTreeMap<usize, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(pos, (idx, _))| (idx, pos))
+            .collect();
+        let edges = |paths: &BTreeMap<Sect, usize>| {
+            paths
+                .iter()
+                .map(|(sect, idx)| (sect.clone(), nodes[idx]))
+                .collect()
+        };
+
+        let data = ResolverData {
+            modules: self.modules.keys().cloned().collect(),
+            file_extensions: self
+                .file_extensions
+                .iter()
+                .map(|(ext, ms)| (ext.clone(), ms.iter().map(module_pos).collect()))
+                .collect(),
+            content_types: self
+                .content_types
+                .iter()
+                .map(|(ct, ms)| (ct.to_string(), ms.iter().map(module_pos).collect()))
+                .collect(),
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(_, node)| NodeData {
+                    paths: edges(&node.paths),
+                    rules: node
+                        .rules
+                        .iter()
+                        .map(|rule| RuleData {
+                            module: module_pos(&rule.module),
+                            specificity: rule.specificity,
+                            path_captures: rule.path_captures.clone(),
+                            query: rule.query.clone(),
+                            fragment: rule.fragment.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            roots: edges(&self.roots),
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        // Serializing into a `Vec` only fails if the data can't be represented,
+        // which isn't the case for any of the types above
+        postcard::to_extend(&data, bytes).expect("resolver data is serializable")
+    }
+
+    /// Loads a resolver serialized with [`GenericResolver::to_bytes`].
+    ///
+    /// Fails with [`DecodeError::UnsupportedVersion`] for files written by a
+    /// different version of this crate, in which case the resolver should be
+    /// built again from module manifests.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bytes = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or(DecodeError::InvalidHeader)?;
+        let (version, bytes) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(DecodeError::InvalidHeader)?;
+        let version = u32::from_le_bytes(*version);
+        if version != FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let data: ResolverData = postcard::from_bytes(bytes)?;
+
+        let modules: Vec<M> = data
+            .modules
+            .into_iter()
+            .map(|name| M::from(super::Module { name }))
+            .collect();
+        let module = |pos: usize| {
+            modules
+                .get(pos)
+                .cloned()
+                .ok_or(DecodeError::Invalid("module out of range"))
+        };
+        let node_count = data.nodes.len();
+        let edges = |edges: Vec<(Sect, usize)>| {
+            edges
+                .into_iter()
+                .map(|(sect, idx)| {
+                    if idx < node_count {
+                        Ok((sect, idx))
+                    } else {
+                        Err(DecodeError::Invalid("node out of range"))
+                    }
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+        };
+
+        let mut resolver = Self::new();
+        for (ext, positions) in data.file_extensions {
+            let ms = positions
+                .into_iter()
+                .map(module)
+                .collect::<Result<_, _>>()?;
+            resolver.file_extensions.insert(ext, ms);
+        }
+        for (content_type, positions) in data.content_types {
+            let content_type = content_type
+                .parse()
+                .map_err(|_| DecodeError::Invalid("invalid content type"))?;
+            let ms = positions
+                .into_iter()
+                .map(module)
+                .collect::<Result<_, _>>()?;
+            resolver.content_types.insert(content_type, ms);
+        }
+        for node in data.nodes {
+            let rules = node
+                .rules
+                .into_iter()
+                .map(|rule| {
+                    Ok(Rule {
+                        module: module(rule.module)?,
+                        specificity: rule.specificity,
+                        path_captures: rule.path_captures,
+                        query: rule.query,
+                        fragment: rule.fragment,
+                    })
+                })
+                .collect::<Result<_, DecodeError>>()?;
+            resolver.nodes.insert(Node {
+                paths: edges(node.paths)?,
+                rules,
+            });
+        }
+        resolver.roots = edges(data.roots)?;
+        resolver.modules = modules
+            .into_iter()
+            .map(|module| (module.name.clone(), module))
+            .collect();
+
+        Ok(resolver)
+    }
+}
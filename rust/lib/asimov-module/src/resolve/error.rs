@@ -67,6 +67,19 @@ pub enum UrlParseError {
     },
 }
 
+#[cfg(feature = "postcard")]
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("not a resolver file")]
+    InvalidHeader,
+    #[error("unsupported resolver file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("malformed resolver file: {0}")]
+    Malformed(#[from] postcard::Error),
+    #[error("invalid resolver file: {0}")]
+    Invalid(&'static str),
+}
+
 #[cfg(all(feature = "cli", feature = "std"))]
 impl From<InsertManifestError> for clientele::SysexitsError {
     fn from(_value: InsertManifestError) -> Self {
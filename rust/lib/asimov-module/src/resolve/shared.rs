@@ -0,0 +1,73 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::SyncResolver;
+use alloc::sync::Arc;
+use std::sync::{Mutex, PoisonError, RwLock};
+
+/// A [`SyncResolver`] that is shared between threads and tasks, and that can be
+/// replaced while in use, e.g. when modules are enabled or disabled.
+///
+/// Readers take a snapshot with [`SharedResolver::load`], which is an `Arc` to
+/// the current resolver and stays unchanged even when a new resolver is swapped
+/// in with [`SharedResolver::store`] or [`SharedResolver::update`].
+#[derive(Debug, Default)]
+pub struct SharedResolver {
+    current: RwLock<Arc<SyncResolver>>,
+    /// Serializes updates, so that concurrent calls to `update` don't lose changes.
+    update_lock: Mutex<()>,
+}
+
+impl SharedResolver {
+    pub fn new(resolver: SyncResolver) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(resolver)),
+            update_lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns a snapshot of the current resolver.
+    pub fn load(&self) -> Arc<SyncResolver> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the current resolver, returning the previous one.
+    pub fn store(&self, resolver: SyncResolver) -> Arc<SyncResolver> {
+        let _update = self
+            .update_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.swap(Arc::new(resolver))
+    }
+
+    /// Replaces the current resolver with a modified copy of it.
+    ///
+    /// The copy is made and modified without blocking readers, which keep
+    /// getting the unmodified resolver until `f` returns successfully. If `f`
+    /// fails the current resolver is left as it is.
+    pub fn update<E>(&self, f: impl FnOnce(&mut SyncResolver) -> Result<(), E>) -> Result<(), E> {
+        let _update = self
+            .update_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let mut resolver = SyncResolver::clone(&self.load());
+        f(&mut resolver)?;
+        self.swap(Arc::new(resolver));
+
+        Ok(())
+    }
+
+    fn swap(&self, resolver: Arc<SyncResolver>) -> Arc<SyncResolver> {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        core::mem::replace(&mut *current, resolver)
+    }
+}
+
+impl From<SyncResolver> for SharedResolver {
+    fn from(resolver: SyncResolver) -> Self {
+        Self::new(resolver)
+    }
+}
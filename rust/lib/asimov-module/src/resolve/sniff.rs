@@ -0,0 +1,127 @@
+// This is free and unencumbered software released into the public domain.
+
+/// Signatures of binary formats: the offset of the magic bytes, the magic
+/// bytes themselves, and the content type of the format.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (8, b"WAVE", "audio/wav"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"OggS", "application/ogg"),
+    (4, b"ftyp", "video/mp4"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"PAR1", "application/vnd.apache.parquet"),
+    (0, b"\x00asm", "application/wasm"),
+];
+
+/// Guesses the content type of data from its first bytes.
+///
+/// Binary formats are recognized by their magic bytes. Otherwise, when the
+/// bytes are text, the content type is guessed from how the text begins,
+/// falling back to `text/plain`. A few kilobytes of data are plenty; the
+/// prefix may end in the middle of a UTF-8 character.
+///
+/// # Examples
+///
+/// ```
+/// # use asimov_module::resolve::sniff_content_type;
+/// assert_eq!(
+///     sniff_content_type(b"\x89PNG\r\n\x1a\n...").unwrap(),
+///     mime::IMAGE_PNG,
+/// );
+/// assert_eq!(
+///     sniff_content_type(b"  {\"name\": \"example\"}").unwrap(),
+///     mime::APPLICATION_JSON,
+/// );
+/// assert_eq!(sniff_content_type(b"\x00\x01\x02\x03"), None);
+/// ```
+pub fn sniff_content_type(prefix: &[u8]) -> Option<mime::Mime> {
+    if prefix.is_empty() {
+        return None;
+    }
+
+    let signature = SIGNATURES.iter().find(|(offset, magic, _)| {
+        prefix
+            .get(*offset..*offset + magic.len())
+            .is_some_and(|bytes| bytes == *magic)
+    });
+    if let Some((_, _, content_type)) = signature {
+        return content_type.parse().ok();
+    }
+
+    let text = as_text(prefix)?;
+    sniff_text(text).parse().ok()
+}
+
+/// Returns the bytes as text, if they are UTF-8 without control characters
+/// other than whitespace. The byte order mark, if any, is skipped.
+fn as_text(prefix: &[u8]) -> Option<&str> {
+    let prefix = prefix.strip_prefix(b"\xef\xbb\xbf").unwrap_or(prefix);
+
+    let text = match core::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // the prefix was cut in the middle of a character
+        Err(err) if err.error_len().is_none() => {
+            core::str::from_utf8(&prefix[..err.valid_up_to()]).ok()?
+        },
+        Err(_) => return None,
+    };
+
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+        .then_some(text)
+}
+
+fn sniff_text(text: &str) -> &'static str {
+    let text = text.trim_start();
+    let starts_with = |start: &str| {
+        text.get(..start.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(start))
+    };
+
+    if starts_with("<!doctype html") || starts_with("<html") {
+        "text/html"
+    } else if starts_with("<svg") || (starts_with("<?xml") && text.contains("<svg")) {
+        "image/svg+xml"
+    } else if starts_with("<?xml") {
+        "application/xml"
+    } else if starts_with("{") && text.contains("\"@context\"") {
+        "application/ld+json"
+    } else if starts_with("{") || is_json_array(text) {
+        "application/json"
+    } else if starts_with("@prefix") || starts_with("@base") {
+        "text/turtle"
+    } else if starts_with("%!ps") {
+        "application/postscript"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Whether the text starts like a JSON array, rather than, say, like the
+/// `[section]` header of an INI file.
+fn is_json_array(text: &str) -> bool {
+    let Some(rest) = text.strip_prefix('[') else {
+        return false;
+    };
+    let rest = rest.trim_start();
+
+    rest.is_empty()
+        || rest.starts_with(['[', '{', '"', ']', '-'])
+        || rest.starts_with(|c: char| c.is_ascii_digit())
+        || ["true", "false", "null"]
+            .iter()
+            .any(|literal| rest.starts_with(literal))
+}
@@ -48,6 +48,32 @@ fn test_parse_invalid_index() {
     assert_eq!(error.0, 2);
 }
 
+#[test]
+fn test_parse_publisher_keys() {
+    let key = asimov_id::PublicKey::from([1; 32]);
+    let mut index: Index =
+        format!("{{\"name\":\"imap\",\"publisher_keys\":[\"{key}\"]}}\n{{\"name\":\"ipfs\"}}\n")
+            .parse()
+            .unwrap();
+    assert_eq!(index.publisher_keys("imap").unwrap().keys(), [key]);
+    assert!(index.publisher_keys("ipfs").is_none());
+
+    // a merged index doesn't override the keys of a module already present:
+    let other = format!(
+        "{{\"name\":\"imap\",\"publisher_keys\":[\"{}\"]}}\n{{\"name\":\"maildir\",\"publisher_keys\":[\"{key}\"]}}\n",
+        asimov_id::PublicKey::from([2; 32])
+    );
+    index.merge(other.parse().unwrap());
+    assert_eq!(index.publisher_keys("imap").unwrap().keys(), [key]);
+    assert_eq!(index.publisher_keys("maildir").unwrap().keys(), [key]);
+
+    assert!(
+        "{\"name\":\"imap\",\"publisher_keys\":[\"not a key\"]}"
+            .parse::<Index>()
+            .is_err()
+    );
+}
+
 #[test]
 fn test_search_terms() {
     let index: Index = SAMPLE_INDEX.parse().unwrap();
@@ -85,3 +111,134 @@ fn test_search_terms() {
     // An empty query matches everything, in index order:
     assert_eq!(names(""), ["anthropic", "imap", "ipfs", "maildir"]);
 }
+
+#[test]
+fn test_rank_by_relevance() {
+    let index: Index = r#"
+{"name":"imap","title":"IMAP","summary":"Email import from a mail server."}
+{"name":"email","label":"Email","summary":"Email utilities."}
+{"name":"mail-archive","summary":"Archives of email."}
+"#
+    .parse()
+    .unwrap();
+
+    let names = |query| -> Vec<String> {
+        index
+            .rank(query)
+            .iter()
+            .map(|result| result.module.name.clone())
+            .collect()
+    };
+
+    // Name and label matches beat summary matches, and shorter fields are
+    // more relevant:
+    assert_eq!(names("email"), ["email", "mail-archive", "imap"]);
+    assert_eq!(names("mail"), ["mail-archive", "imap"]);
+
+    // Every term must match:
+    assert_eq!(names("email server"), ["imap"]);
+    assert_eq!(names("email nothing"), [] as [&str; 0]);
+
+    // Without text, every module matches in index order:
+    assert_eq!(names(""), ["imap", "email", "mail-archive"]);
+}
+
+#[test]
+fn test_rank_with_typos() {
+    let index: Index = SAMPLE_INDEX.parse().unwrap();
+
+    let names = |query| -> Vec<String> {
+        index
+            .rank(query)
+            .iter()
+            .map(|result| result.module.name.clone())
+            .collect()
+    };
+
+    assert_eq!(names("antropic"), ["anthropic"]);
+    assert_eq!(names("mialdir"), ["maildir"]);
+    assert_eq!(names("antrop"), ["anthropic"]);
+    assert_eq!(names("GitHub.com/asimov-modules ANTHROPIC"), ["anthropic"]);
+    // short terms must match exactly or by prefix:
+    assert_eq!(names("ipf"), ["ipfs"]);
+    assert_eq!(names("ipfz"), ["ipfs"]);
+    assert_eq!(names("ifs"), [] as [&str; 0]);
+}
+
+#[test]
+fn test_rank_filters() {
+    use asimov_module::SearchQuery;
+
+    let index: Index = SAMPLE_INDEX.parse().unwrap();
+
+    let names = |query: SearchQuery| -> Vec<String> {
+        index
+            .rank(query)
+            .iter()
+            .map(|result| result.module.name.clone())
+            .collect()
+    };
+
+    assert_eq!(
+        names(SearchQuery::builder().protocol("IMAPS").build()),
+        ["imap"],
+    );
+    assert_eq!(
+        names(SearchQuery::builder().program("*-fetcher").build()),
+        ["imap", "ipfs", "maildir"],
+    );
+    assert_eq!(
+        names(SearchQuery::builder().program("asimov-*-cataloger").build()),
+        ["imap", "maildir"],
+    );
+    assert_eq!(
+        names(
+            SearchQuery::builder()
+                .text("email")
+                .program("*-cataloger")
+                .protocol("file")
+                .build()
+        ),
+        ["maildir"],
+    );
+
+    let index: Index = r#"
+{"name":"csv","handles":{"content_types":["text/csv"]}}
+{"name":"text","handles":{"content_types":["text/*"]}}
+{"name":"any","handles":{"content_types":["*/*"]}}
+"#
+    .parse()
+    .unwrap();
+    let names = |content_type| -> Vec<String> {
+        index
+            .rank(SearchQuery::builder().content_type(content_type).build())
+            .iter()
+            .map(|result| result.module.name.clone())
+            .collect()
+    };
+    assert_eq!(names("text/csv; charset=utf-8"), ["csv", "text", "any"]);
+    assert_eq!(names("application/json"), ["any"]);
+}
+
+#[test]
+fn test_modules_for_url() {
+    let index: Index = SAMPLE_INDEX.parse().unwrap();
+
+    let names = |url| -> Vec<String> {
+        index
+            .modules_for_url(url)
+            .unwrap()
+            .iter()
+            .map(|module| module.name.clone())
+            .collect()
+    };
+
+    assert_eq!(names("imaps://mail.example.org/INBOX"), ["imap"]);
+    assert_eq!(
+        names("ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi"),
+        ["ipfs"]
+    );
+    assert_eq!(names("file:///home/user/mail.maildir"), ["maildir"]);
+    assert_eq!(names("https://example.org/"), [] as [&str; 0]);
+    assert!(index.modules_for_url("").is_err());
+}
@@ -0,0 +1,275 @@
+// This is free and unencumbered software released into the public domain.
+
+use asimov_module::{
+    FetchIndexError, IndexCache, IndexCacheOptions, IndexSignature, IndexSource, SignatureError,
+    TrustedKeys,
+};
+use ed25519_dalek::{Signer, SigningKey};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+const PUBLIC_INDEX: &str = r#"
+{"name":"imap","label":"IMAP"}
+{"name":"ipfs","label":"IPFS"}
+"#;
+
+const PRIVATE_INDEX: &str = r#"
+{"name":"imap","label":"Company IMAP"}
+{"name":"jira","label":"Jira"}
+"#;
+
+/// Serves an index with an ETag, answering conditional requests for the
+/// current ETag with `304 Not Modified`, and its signature, if any.
+#[derive(Clone, Default)]
+struct Server {
+    /// The ETag and the content of the index.
+    index: Arc<Mutex<(String, String)>>,
+    /// The signature of the index.
+    signature: Arc<Mutex<Option<String>>>,
+    /// The headers of the requests received so far.
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    async fn start(etag: &str, content: &str) -> (Self, String) {
+        let server = Self::default();
+        server.set(etag, content);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/index.jsonl", listener.local_addr().unwrap());
+
+        let state = server.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let len = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]).to_lowercase();
+
+                let (etag, content) = state.index.lock().unwrap().clone();
+                let signature = state.signature.lock().unwrap().clone();
+                let response = if request.starts_with("get /index.jsonl.sig ") {
+                    match signature {
+                        Some(signature) => format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{signature}",
+                            signature.len()
+                        ),
+                        None => "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                            .to_string(),
+                    }
+                } else if request.contains(&format!("if-none-match: {etag}")) {
+                    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\netag: {etag}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{content}",
+                        content.len()
+                    )
+                };
+                state.requests.lock().unwrap().push(request);
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (server, url)
+    }
+
+    fn set(&self, etag: &str, content: &str) {
+        *self.index.lock().unwrap() = (etag.into(), content.into());
+    }
+
+    fn sign(&self, signature: Option<IndexSignature>) {
+        *self.signature.lock().unwrap() = signature.map(|signature| signature.to_string());
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn labels(index: &asimov_module::Index) -> Vec<String> {
+    index
+        .modules()
+        .iter()
+        .map(|module| module.label.clone().unwrap_or_default())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_refresh_with_etag() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url) = Server::start("\"v1\"", PUBLIC_INDEX).await;
+    let source = IndexSource::new("public", &url);
+
+    let cache = |ttl| {
+        let options = IndexCacheOptions::builder()
+            .sources(vec![source.clone()])
+            .ttl(ttl)
+            .build();
+        IndexCache::new(dir.path(), options)
+    };
+
+    // The first fetch downloads and caches the index:
+    let index = cache(Duration::from_secs(60)).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+    assert_eq!(server.requests().len(), 1);
+    assert!(dir.path().join("public.jsonl").is_file());
+
+    // A recent enough copy is used without any request:
+    cache(Duration::from_secs(60)).fetch().await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+
+    // An older copy is refreshed with a conditional request:
+    let index = cache(Duration::ZERO).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+    assert_eq!(server.requests().len(), 2);
+    assert!(server.requests()[1].contains("if-none-match: \"v1\""));
+
+    // A changed index is downloaded again:
+    server.set("\"v2\"", PRIVATE_INDEX);
+    let index = cache(Duration::ZERO).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["Company IMAP", "Jira"]);
+    assert_eq!(server.requests().len(), 3);
+
+    let index = cache(Duration::from_secs(60)).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["Company IMAP", "Jira"]);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_offline() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url) = Server::start("\"v1\"", PUBLIC_INDEX).await;
+
+    let cache = |offline| {
+        let options = IndexCacheOptions::builder()
+            .sources(vec![IndexSource::new("public", &url)])
+            .ttl(Duration::ZERO)
+            .offline(offline)
+            .build();
+        IndexCache::new(dir.path(), options)
+    };
+
+    let result = cache(true).fetch().await;
+    assert!(matches!(result, Err(FetchIndexError::NotCached(name)) if name == "public"));
+    assert!(server.requests().is_empty());
+
+    cache(false).fetch().await.unwrap();
+    let index = cache(true).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_unreachable_source_uses_cached_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_server, url) = Server::start("\"v1\"", PUBLIC_INDEX).await;
+
+    let cache = |url: &str| {
+        let options = IndexCacheOptions::builder()
+            .sources(vec![IndexSource::new("public", url)])
+            .ttl(Duration::ZERO)
+            .build();
+        IndexCache::new(dir.path(), options)
+    };
+
+    cache(&url).fetch().await.unwrap();
+
+    // Cached copies belong to the URL they were fetched from:
+    let unreachable = "http://127.0.0.1:1/index.jsonl";
+    assert!(cache(unreachable).fetch().await.is_err());
+
+    cache(&url).fetch().await.unwrap();
+    let path = dir.path().join("public.json");
+    let metadata = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, metadata.replace(&url, unreachable)).unwrap();
+
+    let index = cache(unreachable).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+}
+
+#[tokio::test]
+async fn test_merged_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_public, public_url) = Server::start("\"p1\"", PUBLIC_INDEX).await;
+    let (_private, private_url) = Server::start("\"c1\"", PRIVATE_INDEX).await;
+
+    let options = IndexCacheOptions::builder()
+        .sources(vec![
+            IndexSource::new("company", private_url),
+            IndexSource::new("public", public_url),
+        ])
+        .build();
+    let index = IndexCache::new(dir.path(), options).fetch().await.unwrap();
+
+    // Earlier sources take precedence:
+    assert_eq!(labels(&index), ["Company IMAP", "Jira", "IPFS"]);
+}
+
+#[tokio::test]
+async fn test_signed_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, url) = Server::start("\"v1\"", PUBLIC_INDEX).await;
+
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let other_key = SigningKey::from_bytes(&[2; 32]);
+    let trusted_keys = TrustedKeys::new([(&key.verifying_key()).into()]);
+    let sign = |key: &SigningKey, content: &str| Some(key.sign(content.as_bytes()).into());
+
+    let cache = |trusted_keys: &TrustedKeys| {
+        let source = IndexSource::new("public", &url).with_trusted_keys(trusted_keys.clone());
+        let options = IndexCacheOptions::builder()
+            .sources(vec![source])
+            .ttl(Duration::ZERO)
+            .build();
+        IndexCache::new(dir.path(), options)
+    };
+
+    // An unsigned index isn't accepted:
+    let result = cache(&trusted_keys).fetch().await;
+    assert!(matches!(
+        result,
+        Err(FetchIndexError::Signature(SignatureError::Fetch(_)))
+    ));
+
+    // Nor is one signed by another key:
+    server.sign(sign(&other_key, PUBLIC_INDEX));
+    let result = cache(&trusted_keys).fetch().await;
+    assert!(matches!(
+        result,
+        Err(FetchIndexError::Signature(SignatureError::Untrusted))
+    ));
+
+    server.sign(sign(&key, PUBLIC_INDEX));
+    let index = cache(&trusted_keys).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+
+    // A tampered index is rejected, and the verified cached copy is used:
+    server.set("\"v2\"", PRIVATE_INDEX);
+    let index = cache(&trusted_keys).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["IMAP", "IPFS"]);
+
+    // Copies cached without verification aren't trusted later on:
+    server.sign(None);
+    let index = cache(&TrustedKeys::default()).fetch().await.unwrap();
+    assert_eq!(labels(&index), ["Company IMAP", "Jira"]);
+    let result = IndexCache::new(
+        dir.path(),
+        IndexCacheOptions::builder()
+            .sources(vec![
+                IndexSource::new("public", &url).with_trusted_keys(trusted_keys.clone()),
+            ])
+            .offline(true)
+            .build(),
+    )
+    .fetch()
+    .await;
+    assert!(matches!(result, Err(FetchIndexError::NotCached(_))));
+}
@@ -0,0 +1,182 @@
+// This is free and unencumbered software released into the public domain.
+
+use asimov_module::{Handles, ModuleManifest, resolve::Resolver};
+use proptest::{prelude::*, sample::select};
+
+/// URLs built from a few parts, so that generated manifests share nodes.
+fn url() -> impl Strategy<Value = String> {
+    (
+        select(&["https", "near"]),
+        select(&["", "*.", "www.", "en."]),
+        select(&["example.org", "example.com"]),
+        prop::collection::vec(select(&["a", "b", ":id", ":name"]), 0..3),
+        select(&["", "?q=", "?q=:query", "?v=1&q=2"]),
+        select(&["", "#top", "#:section"]),
+    )
+        .prop_map(|(protocol, subdomain, domain, path, query, fragment)| {
+            let path = path.join("/");
+            format!("{protocol}://{subdomain}{domain}/{path}{query}{fragment}")
+        })
+}
+
+fn handles() -> impl Strategy<Value = Handles> {
+    fn some<S: Strategy>(strategy: S) -> impl Strategy<Value = Vec<S::Value>> {
+        prop::collection::vec(strategy, 0..3)
+    }
+
+    (
+        some(select(&["https", "near", "ipfs"]).prop_map(String::from)),
+        some(url()),
+        some(url()),
+        some(select(&["txt", ".json", "tar.gz", "GZ"]).prop_map(String::from)),
+        some(select(&["text/plain", "text/*", "*/*", "application/json"]).prop_map(String::from)),
+    )
+        .prop_map(
+            |(url_protocols, url_prefixes, url_patterns, file_extensions, content_types)| Handles {
+                url_protocols,
+                url_prefixes,
+                url_patterns,
+                file_extensions,
+                content_types,
+            },
+        )
+}
+
+/// Manifests with distinct names.
+fn manifests() -> impl Strategy<Value = Vec<ModuleManifest>> {
+    prop::collection::vec(handles(), 1..6).prop_map(|handles| {
+        handles
+            .into_iter()
+            .enumerate()
+            .map(|(i, handles)| ModuleManifest {
+                name: format!("module-{i}"),
+                handles,
+                ..Default::default()
+            })
+            .collect()
+    })
+}
+
+fn resolver(manifests: &[ModuleManifest]) -> Resolver {
+    Resolver::try_from(manifests).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn insert_then_remove_is_never_inserting(
+        manifests in manifests(),
+        index in any::<prop::sample::Index>(),
+        urls in prop::collection::vec(url(), 0..8),
+    ) {
+        let removed = &manifests[index.index(manifests.len())];
+        let kept: Vec<ModuleManifest> = manifests
+            .iter()
+            .filter(|manifest| manifest.name != removed.name)
+            .cloned()
+            .collect();
+
+        let mut actual = resolver(&manifests);
+        actual.remove_module(&removed.name);
+        let expected = resolver(&kept);
+
+        prop_assert_eq!(&actual, &expected);
+        for url in &urls {
+            prop_assert_eq!(actual.resolve(url).unwrap(), expected.resolve(url).unwrap());
+        }
+        prop_assert!(!actual.remove_module(&removed.name));
+    }
+
+    #[test]
+    fn removing_every_module_empties_the_resolver(manifests in manifests()) {
+        let mut actual = resolver(&manifests);
+        for manifest in manifests.iter().rev() {
+            actual.remove_module(&manifest.name);
+        }
+
+        prop_assert_eq!(actual, Resolver::new());
+    }
+
+    #[test]
+    fn replace_manifest_is_inserting_the_replacement(
+        manifests in manifests(),
+        index in any::<prop::sample::Index>(),
+        handles in handles(),
+    ) {
+        let index = index.index(manifests.len());
+        let replacement = ModuleManifest {
+            name: manifests[index].name.clone(),
+            handles,
+            ..Default::default()
+        };
+        let mut replaced = manifests.clone();
+        replaced[index] = replacement.clone();
+
+        let mut actual = resolver(&manifests);
+        actual.replace_manifest(&replacement).unwrap();
+
+        prop_assert_eq!(actual, resolver(&replaced));
+    }
+
+    #[test]
+    fn from_bytes_loads_to_bytes(
+        manifests in manifests(),
+        index in any::<prop::sample::Index>(),
+        urls in prop::collection::vec(url(), 0..8),
+    ) {
+        let mut expected = resolver(&manifests);
+        expected.remove_module(&manifests[index.index(manifests.len())].name);
+
+        let actual = Resolver::from_bytes(&expected.to_bytes()).unwrap();
+
+        prop_assert_eq!(&actual, &expected);
+        for url in &urls {
+            prop_assert_eq!(actual.resolve(url).unwrap(), expected.resolve(url).unwrap());
+        }
+    }
+}
+
+#[test]
+fn from_bytes_rejects_other_files() {
+    use asimov_module::resolve::error::DecodeError;
+
+    let bytes = Resolver::new().to_bytes();
+    assert_eq!(Resolver::from_bytes(&bytes).unwrap(), Resolver::new());
+
+    let result = Resolver::from_bytes(b"{\"name\": \"near\"}");
+    assert!(matches!(result, Err(DecodeError::InvalidHeader)));
+
+    let mut newer = bytes.clone();
+    newer[8] += 1;
+    let result = Resolver::from_bytes(&newer);
+    assert!(matches!(result, Err(DecodeError::UnsupportedVersion(2))));
+
+    let result = Resolver::from_bytes(&bytes[..bytes.len() - 1]);
+    assert!(matches!(result, Err(DecodeError::Malformed(_))));
+}
+
+#[test]
+fn replace_manifest_keeps_resolver_on_error() {
+    let manifest = ModuleManifest {
+        name: "near".into(),
+        handles: Handles {
+            url_protocols: vec!["near".into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut resolver = resolver(&[manifest]);
+    let before = resolver.clone();
+
+    let invalid = ModuleManifest {
+        name: "near".into(),
+        handles: Handles {
+            url_prefixes: vec!["near://".into()],
+            content_types: vec!["not a content type".into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(resolver.replace_manifest(&invalid).is_err());
+    assert_eq!(resolver, before);
+    assert_eq!(resolver.resolve("near://tx/1").unwrap()[0].name, "near");
+}
@@ -1,26 +1,62 @@
 // This is free and unencumbered software released into the public domain.
 
 use alloc::{collections::BTreeSet, format, string::String, vec::Vec};
-use asimov_module::InstalledModuleManifest;
 pub use asimov_module::ModuleName;
+use asimov_module::{InstalledModuleManifest, resolve::SyncResolver};
+use core::time::Duration;
 use std::path::{Path, PathBuf};
 use tokio::io;
 
 pub mod error;
 use error::*;
 
+mod check;
+pub use check::*;
+
+mod gc;
+pub use gc::*;
+
+mod lock;
+pub use lock::*;
+
+mod pin;
+pub use pin::*;
+
+mod scope;
+pub use scope::*;
+
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "watch")]
+pub use watch::*;
+
 pub const MANIFEST_FILE_NAME: &str = "manifest.json";
 pub const README_FILE_PATH: &str = "doc/README.md";
 pub const BIN_DIR_NAME: &str = "bin";
+pub const RESOLVER_FILE_NAME: &str = "resolver.bin";
+pub const PREVIOUS_DIR_NAME: &str = "previous";
+
+/// The end of the resolver file, after the fingerprint of the enabled modules
+/// and its length, which readers of the resolver itself ignore.
+const FINGERPRINT_MAGIC: &[u8; 8] = b"ASIMOVFP";
 
 #[derive(Clone, Debug, Default, bon::Builder)]
-pub struct Options {}
+pub struct Options {
+    /// How long to wait for another process to release the registry lock,
+    /// by default [`DEFAULT_LOCK_TIMEOUT`].
+    pub lock_timeout: Option<Duration>,
+}
 
 #[derive(Clone, Debug)]
 pub struct Registry {
     install_dir: PathBuf,
+    previous_dir: PathBuf,
     enable_dir: PathBuf,
     exec_dir: PathBuf,
+    resolver_file: PathBuf,
+    lock_file: PathBuf,
+    pins_file: PathBuf,
+    lock_timeout: Duration,
 }
 
 impl Default for Registry {
@@ -30,12 +66,17 @@ impl Default for Registry {
 }
 
 impl Registry {
-    pub fn new(asimov_dir: impl Into<PathBuf>, _options: Options) -> Self {
+    pub fn new(asimov_dir: impl Into<PathBuf>, options: Options) -> Self {
         let dir = asimov_dir.into();
         Self {
             install_dir: dir.join("modules").join("installed"),
+            previous_dir: dir.join("modules").join(PREVIOUS_DIR_NAME),
             enable_dir: dir.join("modules").join("enabled"),
             exec_dir: dir.join("libexec"),
+            resolver_file: dir.join("modules").join(RESOLVER_FILE_NAME),
+            lock_file: dir.join("modules").join(REGISTRY_LOCK_FILE_NAME),
+            pins_file: dir.join("modules").join(PINS_FILE_NAME),
+            lock_timeout: options.lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT),
         }
     }
 
@@ -43,17 +84,42 @@ impl Registry {
         install_dir: S1,
         enable_dir: S2,
         exec_dir: S3,
-        _options: Options,
+        options: Options,
     ) -> Self
     where
         S1: Into<PathBuf>,
         S2: Into<PathBuf>,
         S3: Into<PathBuf>,
     {
+        let enable_dir: PathBuf = enable_dir.into();
+        // next to the enable directory, as with the default layout
+        let resolver_file = enable_dir
+            .parent()
+            .unwrap_or(&enable_dir)
+            .join(RESOLVER_FILE_NAME);
+        let install_dir: PathBuf = install_dir.into();
+        // next to the install directory, as with the default layout
+        let previous_dir = install_dir
+            .parent()
+            .unwrap_or(&install_dir)
+            .join(PREVIOUS_DIR_NAME);
+        let lock_file = install_dir
+            .parent()
+            .unwrap_or(&install_dir)
+            .join(REGISTRY_LOCK_FILE_NAME);
+        let pins_file = install_dir
+            .parent()
+            .unwrap_or(&install_dir)
+            .join(PINS_FILE_NAME);
         Self {
-            install_dir: install_dir.into(),
-            enable_dir: enable_dir.into(),
+            install_dir,
+            previous_dir,
+            enable_dir,
             exec_dir: exec_dir.into(),
+            resolver_file,
+            lock_file,
+            pins_file,
+            lock_timeout: options.lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT),
         }
     }
 
@@ -81,11 +147,165 @@ impl Registry {
         self.install_dir.join(module_name.as_str())
     }
 
+    /// The directory with the version of `module_name` that was replaced by
+    /// the last [`Self::replace_module`], to go back to with
+    /// [`Self::rollback_module`].
+    pub fn previous_module_dir(&self, module_name: &ModuleName) -> PathBuf {
+        self.previous_dir.join(module_name.as_str())
+    }
+
+    /// The precompiled resolver for the enabled modules, see [`Self::resolver`], followed by a
+    /// fingerprint of the enabled modules it was built for.
+    pub fn resolver_file(&self) -> &Path {
+        &self.resolver_file
+    }
+
+    /// Returns a resolver for the enabled modules.
+    ///
+    /// The resolver is loaded from [`Self::resolver_file`], which is kept up to date as modules
+    /// are enabled and disabled. If the file is missing, unreadable or was built for other
+    /// enabled modules or manifests, the resolver is built from the manifests instead, and the
+    /// file is written again.
+    pub async fn resolver(&self) -> Result<SyncResolver, ResolverError> {
+        {
+            let _lock = self.lock(LockMode::Shared).await?;
+            if let Some(resolver) = self.read_resolver().await? {
+                return Ok(resolver);
+            }
+        }
+
+        // rebuilding writes the resolver file, so the lock is taken anew,
+        // exclusively unless the registry can't be changed
+        let _lock = match self.lock(LockMode::Exclusive).await {
+            Ok(lock) => lock,
+            Err(LockError::Open(..)) => self.lock(LockMode::Shared).await?,
+            Err(err) => return Err(err.into()),
+        };
+        // another process may have rebuilt it meanwhile
+        if let Some(resolver) = self.read_resolver().await? {
+            return Ok(resolver);
+        }
+
+        let resolver = self.build_resolver().await?;
+
+        if let Err(err) = self.write_resolver(&resolver).await {
+            tracing::warn!(?err, "failed to save the resolver for enabled modules");
+        }
+
+        Ok(resolver)
+    }
+
+    /// Loads the resolver file, unless it's missing or out of date.
+    async fn read_resolver(&self) -> Result<Option<SyncResolver>, ResolverError> {
+        let bytes = match tokio::fs::read(&self.resolver_file).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(ResolverError::Read(self.resolver_file.clone(), err)),
+        };
+        let Some((bytes, fingerprint)) = split_fingerprint(&bytes) else {
+            tracing::debug!("the resolver file lacks a fingerprint, rebuilding it");
+            return Ok(None);
+        };
+        if fingerprint != self.enabled_fingerprint().await.as_bytes() {
+            tracing::debug!("the resolver file is out of date, rebuilding it");
+            return Ok(None);
+        }
+        match SyncResolver::from_bytes(bytes) {
+            Ok(resolver) => Ok(Some(resolver)),
+            Err(err) => {
+                tracing::debug!(?err, "failed to load the resolver file, rebuilding it");
+                Ok(None)
+            },
+        }
+    }
+
+    /// Builds the resolver for the enabled modules from their manifests, and writes it to
+    /// [`Self::resolver_file`].
+    pub async fn update_resolver(&self) -> Result<SyncResolver, UpdateResolverError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
+        let resolver = self.build_resolver().await?;
+        self.write_resolver(&resolver).await?;
+        Ok(resolver)
+    }
+
+    async fn build_resolver(&self) -> Result<SyncResolver, UpdateResolverError> {
+        let mut resolver = SyncResolver::new();
+
+        for module in self.enabled_modules().await? {
+            resolver
+                .insert_manifest(&module.manifest)
+                .map_err(|e| UpdateResolverError::Insert(module.manifest.name.clone(), e))?;
+        }
+
+        Ok(resolver)
+    }
+
+    async fn write_resolver(&self, resolver: &SyncResolver) -> Result<(), UpdateResolverError> {
+        let path = &self.resolver_file;
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        let fingerprint = self.enabled_fingerprint().await;
+        let mut bytes = resolver.to_bytes();
+        bytes.extend_from_slice(fingerprint.as_bytes());
+        bytes.extend_from_slice(&(fingerprint.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(FINGERPRINT_MAGIC);
+
+        // write to a temporary file first, so that readers never see a partially written file
+        let result = match tokio::fs::write(&temp_path, bytes).await {
+            Ok(()) => tokio::fs::rename(&temp_path, path).await,
+            Err(err) => Err(err),
+        };
+
+        result.map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            UpdateResolverError::Write(path.clone(), e)
+        })
+    }
+
+    /// Describes the enabled modules by their names and the modification times of their
+    /// manifests, so that a resolver file built for others is noticed. Unlike comparing the
+    /// modification times of the files with that of [`Self::enable_dir`], this doesn't depend on
+    /// how finely the file system records them.
+    async fn enabled_fingerprint(&self) -> String {
+        let mut lines = BTreeSet::new();
+        if let Ok(mut read_dir) = tokio::fs::read_dir(&self.enable_dir).await {
+            while let Ok(Some(entry)) = read_dir.next_entry().await {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let manifest_path = entry.path().join(MANIFEST_FILE_NAME);
+                let modified = match tokio::fs::metadata(&manifest_path).await {
+                    Ok(metadata) => metadata
+                        .modified()
+                        .ok()
+                        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|time| time.as_nanos()),
+                    Err(_) => None,
+                };
+                lines.insert(match modified {
+                    Some(modified) => format!("{name} {modified}\n"),
+                    None => format!("{name}\n"),
+                });
+            }
+        }
+        lines.into_iter().collect()
+    }
+
+    /// Updates [`Self::resolver_file`] after a module was enabled or disabled. Failing to do so
+    /// doesn't fail the change itself, since [`Self::resolver`] notices outdated files anyway.
+    async fn refresh_resolver(&self) {
+        if let Err(err) = self.update_resolver().await {
+            tracing::warn!(?err, "failed to update the resolver for enabled modules");
+        }
+    }
+
     pub async fn add_module(
         &self,
         module_name: &ModuleName,
         dir: impl AsRef<Path>,
     ) -> Result<(), AddModuleError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
+
         if self.is_module_installed(module_name).await.unwrap_or(false) {
             return Err(AddModuleError::AlreadyInstalled);
         }
@@ -127,6 +347,7 @@ impl Registry {
         module_name: &ModuleName,
     ) -> Result<InstalledModuleManifest, ManifestError> {
         self.migrate_legacy_manifest(module_name).await;
+        let _lock = self.lock(LockMode::Shared).await?;
 
         let path = self
             .find_manifest_file(module_name)
@@ -159,6 +380,7 @@ impl Registry {
     }
 
     pub async fn remove_module(&self, module_name: &ModuleName) -> Result<(), RemoveModuleError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
         self.migrate_legacy_manifest(module_name).await;
 
         if self.find_manifest_file(module_name).await?.is_none() {
@@ -169,7 +391,132 @@ impl Registry {
 
         tokio::fs::remove_dir_all(&module_dir)
             .await
-            .map_err(|e| RemoveModuleError::RemoveModuleDir(module_dir, e))
+            .map_err(|e| RemoveModuleError::RemoveModuleDir(module_dir, e))?;
+
+        let previous_dir = self.previous_module_dir(module_name);
+        remove_dir_if_exists(&previous_dir)
+            .await
+            .map_err(|e| RemoveModuleError::RemoveModuleDir(previous_dir, e))
+    }
+
+    /// Replaces an installed module with the module assembled in `dir`, such
+    /// as a newer version, keeping the enabled state.
+    ///
+    /// The binaries of the new version are linked first, then the module
+    /// directory is swapped with `dir`, atomically where the platform
+    /// supports it, so that the module is never missing or half-installed.
+    /// The replaced version is kept for [`Self::rollback_module`].
+    pub async fn replace_module(
+        &self,
+        module_name: &ModuleName,
+        dir: impl AsRef<Path>,
+    ) -> Result<(), ReplaceModuleError> {
+        let dir = dir.as_ref();
+        let _lock = self.lock(LockMode::Exclusive).await?;
+        self.migrate_legacy_manifest(module_name).await;
+
+        if self.find_manifest_file(module_name).await?.is_none() {
+            return Err(ReplaceModuleError::NotInstalled);
+        }
+
+        let module_dir = self.module_dir(module_name);
+        self.swap_module(module_name, dir, &module_dir).await?;
+
+        // `dir` now has the replaced version:
+        let previous_dir = self.previous_module_dir(module_name);
+        let keep = async {
+            remove_dir_if_exists(&previous_dir).await?;
+            tokio::fs::create_dir_all(&self.previous_dir).await?;
+            tokio::fs::rename(dir, &previous_dir).await
+        };
+        if let Err(err) = keep.await {
+            tracing::warn!(
+                ?err,
+                ?previous_dir,
+                "failed to keep the replaced version of module"
+            );
+            let _ = remove_dir_if_exists(dir).await;
+        }
+
+        self.refresh_resolver_if_enabled(module_name).await;
+
+        Ok(())
+    }
+
+    /// Goes back to the version of a module that was replaced by the last
+    /// [`Self::replace_module`], keeping the enabled state. The version
+    /// rolled back from is kept in turn, so that a second rollback undoes
+    /// the first.
+    pub async fn rollback_module(&self, module_name: &ModuleName) -> Result<(), RollbackError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
+        let previous_dir = self.previous_module_dir(module_name);
+        if !tokio::fs::try_exists(previous_dir.join(MANIFEST_FILE_NAME))
+            .await
+            .unwrap_or(false)
+        {
+            return Err(RollbackError::NoPreviousVersion);
+        }
+
+        let module_dir = self.module_dir(module_name);
+        if !tokio::fs::try_exists(&module_dir).await.unwrap_or(false) {
+            return Err(RollbackError::NotInstalled);
+        }
+
+        self.swap_module(module_name, &previous_dir, &module_dir)
+            .await?;
+        self.refresh_resolver_if_enabled(module_name).await;
+
+        Ok(())
+    }
+
+    /// Swaps the module directory with another directory of the module,
+    /// linking the binaries of the other directory before, and unlinking
+    /// the binaries it no longer has after.
+    async fn swap_module(
+        &self,
+        module_name: &ModuleName,
+        dir: &Path,
+        module_dir: &Path,
+    ) -> Result<(), SwapModuleError> {
+        let old_programs = read_programs(&module_dir.join(BIN_DIR_NAME))
+            .await
+            .map_err(|e| SwapModuleError::ReadBinDir(module_dir.join(BIN_DIR_NAME), e))?;
+        let new_programs = read_programs(&dir.join(BIN_DIR_NAME))
+            .await
+            .map_err(|e| SwapModuleError::ReadBinDir(dir.join(BIN_DIR_NAME), e))?;
+
+        // the links point into the module directory, to the new binaries once swapped
+        let bin_dir = module_dir.join(BIN_DIR_NAME);
+        for program in new_programs.difference(&old_programs) {
+            if let Err(err) = self.add_binary(program, &bin_dir.join(program)).await {
+                for program in new_programs.difference(&old_programs) {
+                    let _ = self.remove_binary(program).await;
+                }
+                return Err(SwapModuleError::AddBinary(program.clone(), err));
+            }
+        }
+
+        if let Err(err) = swap_dirs(dir, module_dir).await {
+            for program in new_programs.difference(&old_programs) {
+                let _ = self.remove_binary(program).await;
+            }
+            return Err(SwapModuleError::Swap(dir.into(), module_dir.into(), err));
+        }
+        tracing::debug!(%module_name, ?dir, "swapped module directory");
+
+        for program in old_programs.difference(&new_programs) {
+            if let Err(err) = self.remove_binary(program).await {
+                tracing::warn!(?err, program, "failed to remove binary of replaced module");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn refresh_resolver_if_enabled(&self, module_name: &ModuleName) {
+        if self.is_module_enabled(module_name).await.unwrap_or(false) {
+            self.refresh_resolver().await;
+        }
     }
 
     async fn add_binary(&self, program_name: &str, binary_path: &Path) -> io::Result<()> {
@@ -182,10 +529,15 @@ impl Registry {
             None => binary_path.into(),
         };
 
+        // replace any existing link atomically:
         let link_path = self.exec_dir.join(program_name);
-        let _ = self.remove_binary(program_name).await;
+        let tmp_path = link_path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = tokio::fs::remove_file(&tmp_path).await;
 
-        create_symlink(&target_path, &link_path, false).await
+        create_symlink(&target_path, &tmp_path, false).await?;
+        tokio::fs::rename(&tmp_path, &link_path)
+            .await
+            .inspect_err(|_| drop(std::fs::remove_file(&tmp_path)))
     }
 
     pub async fn remove_binary(&self, name: impl AsRef<str>) -> Result<(), RemoveBinaryError> {
@@ -194,6 +546,7 @@ impl Registry {
         if !is_valid_program_name(name) {
             return Err(RemoveBinaryError::InvalidName(name.into()));
         }
+        let _lock = self.lock(LockMode::Exclusive).await?;
 
         let binary_path = self.exec_dir.join(name);
 
@@ -209,6 +562,8 @@ impl Registry {
     pub async fn installed_modules(
         &self,
     ) -> Result<Vec<InstalledModuleManifest>, InstalledModulesError> {
+        self.migrate_legacy_manifests().await;
+        let _lock = self.lock(LockMode::Shared).await?;
         let installed_dir = &self.install_dir;
 
         let mut module_names = BTreeSet::new();
@@ -232,6 +587,7 @@ impl Registry {
                 .unwrap_or(false)
             {
                 let Ok(module_name) = ModuleName::try_from(name) else {
+                    tracing::debug!(path = ?entry.path(), "skipping invalid module name");
                     continue;
                 };
 
@@ -241,8 +597,7 @@ impl Registry {
                     continue;
                 };
 
-                self.migrate_legacy_manifest(&module_name).await;
-
+                // left over from a failed migration, skipped without a manifest
                 module_names.insert(module_name);
             }
         }
@@ -256,6 +611,7 @@ impl Registry {
                 .await
                 .map_err(|e| InstalledModulesError::DirIo(manifest_path.clone(), e))?
             {
+                tracing::debug!(%module_name, "skipping module without a manifest");
                 continue;
             }
 
@@ -272,6 +628,9 @@ impl Registry {
     pub async fn enabled_modules(
         &self,
     ) -> Result<Vec<InstalledModuleManifest>, EnabledModulesError> {
+        // enabled entries may still point at legacy manifest files
+        self.migrate_legacy_manifests().await;
+        let _lock = self.lock(LockMode::Shared).await?;
         let enabled_dir = &self.enable_dir;
 
         let mut read_dir = tokio::fs::read_dir(&enabled_dir)
@@ -303,11 +662,6 @@ impl Registry {
 
             let manifest_path = self.module_dir(&module_name).join(MANIFEST_FILE_NAME);
 
-            // the entry may still point at a manifest file rather than a module directory
-            if !tokio::fs::try_exists(&manifest_path).await.unwrap_or(false) {
-                self.migrate_legacy_manifest(&module_name).await;
-            }
-
             if !tokio::fs::try_exists(&manifest_path)
                 .await
                 .map_err(|e| EnabledModulesError::DirIo(manifest_path.clone(), e))?
@@ -356,6 +710,7 @@ impl Registry {
     }
 
     pub async fn enable_module(&self, module_name: &ModuleName) -> Result<(), EnableError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
         self.migrate_legacy_manifest(module_name).await;
 
         let module_dir = self.module_dir(module_name);
@@ -375,11 +730,18 @@ impl Registry {
                 create_symlink(&target_path, &src_path, true).await
             },
             result => result,
-        }
-        .map_err(Into::into)
+        }?;
+
+        self.refresh_resolver().await;
+
+        Ok(())
     }
 
     pub async fn disable_module(&self, module_name: &ModuleName) -> Result<(), DisableError> {
+        let _lock = self
+            .lock(LockMode::Exclusive)
+            .await
+            .map_err(io::Error::from)?;
         let path = self.enable_dir.join(module_name.as_str());
 
         let result = match tokio::fs::remove_file(&path).await {
@@ -388,15 +750,17 @@ impl Registry {
             result => result,
         };
 
-        result
-            .or_else(|err| {
-                if err.kind() == io::ErrorKind::NotFound {
-                    Ok(())
-                } else {
-                    Err(err)
-                }
-            })
-            .map_err(Into::into)
+        result.or_else(|err| {
+            if err.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(err)
+            }
+        })?;
+
+        self.refresh_resolver().await;
+
+        Ok(())
     }
 
     /// The path that the entry in [`Self::enable_dir`] points to for `module_name`.
@@ -429,6 +793,26 @@ impl Registry {
         }
     }
 
+    /// Migrates the legacy manifest files of all modules, before a shared
+    /// lock is taken to read them, as migrating takes an exclusive one.
+    async fn migrate_legacy_manifests(&self) {
+        let Ok(mut read_dir) = tokio::fs::read_dir(&self.install_dir).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| manifest_file_module_name(Path::new(name)).map(String::from))
+            else {
+                continue;
+            };
+            if let Ok(module_name) = ModuleName::try_from(name) {
+                self.migrate_legacy_manifest(&module_name).await;
+            }
+        }
+    }
+
     /// Moves the manifest of `module_name` into its own directory, if it is still a file directly
     /// in the install directory: `~/.asimov/modules/installed/<name>.{json,yaml,yml}`.
     async fn migrate_legacy_manifest(&self, module_name: &ModuleName) {
@@ -443,6 +827,11 @@ impl Registry {
             if tokio::fs::try_exists(&path).await.unwrap_or(false) {
                 tracing::debug!(?path, "found a legacy manifest file, migrating...");
 
+                let Ok(_lock) = self.lock(LockMode::Exclusive).await else {
+                    tracing::debug!(?path, "failed to lock the registry to migrate");
+                    return;
+                };
+
                 self.move_legacy_manifest(module_name, &path).await;
             }
         }
@@ -518,6 +907,66 @@ async fn create_symlink(target: &Path, link: &Path, target_is_dir: bool) -> io::
     }
 }
 
+/// Swaps two directories, atomically where the platform supports it.
+async fn swap_dirs(a: &Path, b: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use rustix::{
+            fs::{CWD, RenameFlags, renameat_with},
+            io::Errno,
+        };
+
+        let (a, b) = (PathBuf::from(a), PathBuf::from(b));
+        let result = tokio::task::spawn_blocking(move || {
+            renameat_with(CWD, &a, CWD, &b, RenameFlags::EXCHANGE)
+        })
+        .await
+        .map_err(io::Error::other)?;
+        match result {
+            Ok(()) => return Ok(()),
+            // not supported by the file system or the kernel
+            Err(Errno::INVAL | Errno::NOSYS) => {},
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let tmp = a.with_extension(format!("{}.swap", std::process::id()));
+    tokio::fs::rename(a, &tmp).await?;
+    if let Err(err) = tokio::fs::rename(b, a).await {
+        let _ = tokio::fs::rename(&tmp, a).await;
+        return Err(err);
+    }
+    if let Err(err) = tokio::fs::rename(&tmp, b).await {
+        let _ = tokio::fs::rename(a, b).await;
+        let _ = tokio::fs::rename(&tmp, a).await;
+        return Err(err);
+    }
+    Ok(())
+}
+
+async fn remove_dir_if_exists(dir: &Path) -> io::Result<()> {
+    match tokio::fs::remove_dir_all(dir).await {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Returns the names of the programs in a directory of module binaries.
+async fn read_programs(bin_dir: &Path) -> io::Result<BTreeSet<String>> {
+    let mut programs = BTreeSet::new();
+    let mut entries = match tokio::fs::read_dir(bin_dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(programs),
+        Err(err) => return Err(err),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if let Ok(name) = entry.file_name().into_string() {
+            programs.insert(name);
+        }
+    }
+    Ok(programs)
+}
+
 /// Program names are joined onto [`Registry::exec_dir`], so a name that is not a plain path
 /// component could reach files outside of it. Module names carry the same guarantee in their type.
 fn is_valid_program_name(name: &str) -> bool {
@@ -529,6 +978,15 @@ fn is_valid_program_name(name: &str) -> bool {
             .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
 }
 
+/// Splits the contents of a resolver file into the resolver and the fingerprint of the enabled
+/// modules it was built for.
+fn split_fingerprint(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let bytes = bytes.strip_suffix(FINGERPRINT_MAGIC.as_slice())?;
+    let (bytes, len) = bytes.split_last_chunk::<4>()?;
+    let len = usize::try_from(u32::from_le_bytes(*len)).ok()?;
+    bytes.split_at_checked(bytes.len().checked_sub(len)?)
+}
+
 fn manifest_file_module_name(path: &Path) -> Option<&str> {
     match path.extension().and_then(|ext| ext.to_str()) {
         Some("json") | Some("yaml") | Some("yml") => {
@@ -0,0 +1,308 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    BIN_DIR_NAME, LockMode, MANIFEST_FILE_NAME, ModuleName, Registry, error::CheckError,
+    manifest_file_module_name, read_manifest, read_programs,
+};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use asimov_module::{InstalledModuleManifest, tracing};
+use std::path::{Path, PathBuf};
+use tokio::io;
+
+#[derive(Clone, Debug, Default, bon::Builder)]
+pub struct CheckOptions {
+    /// Whether to fix the problems that can be fixed without reinstalling
+    /// modules.
+    #[builder(default)]
+    pub repair: bool,
+}
+
+/// The problems found by [`Registry::check`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    pub issues: Vec<Issue>,
+}
+
+impl CheckReport {
+    /// Whether no problems were found, or all of them were repaired.
+    pub fn is_healthy(&self) -> bool {
+        self.issues.iter().all(|issue| issue.repaired)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub problem: Problem,
+    pub repaired: bool,
+}
+
+/// A problem with the installed or enabled modules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// An entry of the install directory that isn't named like a module.
+    InvalidModuleName { path: PathBuf },
+    /// A module directory without a manifest.
+    MissingManifest { module: ModuleName },
+    /// A module manifest that fails to parse.
+    UnreadableManifest { path: PathBuf, error: String },
+    /// A manifest still at its legacy path, such as `installed/<name>.yaml`.
+    /// Repaired by moving it into the module directory.
+    LegacyManifest { path: PathBuf },
+    /// An entry of the enable directory that doesn't lead to an installed
+    /// module. Repaired by removing it.
+    BrokenEnableLink { path: PathBuf },
+    /// A program that a module provides but lacks.
+    MissingProgram { module: ModuleName, program: String },
+    /// A program of a module that isn't executable. Repaired by making it
+    /// executable.
+    NotExecutable { module: ModuleName, program: String },
+    /// A program of a module without a working link in the libexec
+    /// directory. Repaired by linking it again.
+    UnlinkedProgram { module: ModuleName, program: String },
+    /// An entry of the libexec directory that no module owns. Repaired by
+    /// removing it if it's a link, while anything else is only reported.
+    OrphanedBinary { path: PathBuf },
+}
+
+impl core::fmt::Display for Problem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidModuleName { path } => {
+                write!(f, "`{}` is not named like a module", path.display())
+            },
+            Self::MissingManifest { module } => write!(f, "module `{module}` has no manifest"),
+            Self::UnreadableManifest { path, error } => {
+                write!(f, "manifest `{}` fails to parse: {error}", path.display())
+            },
+            Self::LegacyManifest { path } => {
+                write!(f, "manifest `{}` is at a legacy path", path.display())
+            },
+            Self::BrokenEnableLink { path } => {
+                write!(
+                    f,
+                    "`{}` enables a module that isn't installed",
+                    path.display()
+                )
+            },
+            Self::MissingProgram { module, program } => {
+                write!(f, "module `{module}` lacks its program `{program}`")
+            },
+            Self::NotExecutable { module, program } => {
+                write!(
+                    f,
+                    "program `{program}` of module `{module}` is not executable"
+                )
+            },
+            Self::UnlinkedProgram { module, program } => {
+                write!(f, "program `{program}` of module `{module}` is not linked")
+            },
+            Self::OrphanedBinary { path } => {
+                write!(f, "`{}` belongs to no installed module", path.display())
+            },
+        }
+    }
+}
+
+impl Registry {
+    /// Looks for broken or inconsistent state in the install, enable and
+    /// libexec directories, and fixes what it can with
+    /// [`CheckOptions::repair`].
+    ///
+    /// Problems that take reinstalling a module to fix, such as missing
+    /// programs or unreadable manifests, are only reported.
+    pub async fn check(&self, options: &CheckOptions) -> Result<CheckReport, CheckError> {
+        let mode = if !options.repair {
+            LockMode::Shared
+        } else {
+            LockMode::Exclusive
+        };
+        let _lock = self.lock(mode).await?;
+        let mut report = CheckReport::default();
+        let mut issue = |problem: Problem, repaired: bool| {
+            if !repaired {
+                tracing::debug!(%problem, "found a registry problem");
+            }
+            report.issues.push(Issue { problem, repaired });
+        };
+
+        // the programs of every module, even one with a broken manifest, so
+        // that its links aren't taken for orphans
+        let mut owned = BTreeSet::new();
+        let mut module_names = BTreeSet::new();
+        for path in read_dir(&self.install_dir).await? {
+            let name = path.file_name().and_then(|name| name.to_str());
+            if is_dir(&path).await {
+                match name.map(ModuleName::try_from) {
+                    Some(Ok(module_name)) => _ = module_names.insert(module_name),
+                    _ => issue(Problem::InvalidModuleName { path }, false),
+                }
+            } else if let Some(name) = manifest_file_module_name(&path) {
+                let module_name = ModuleName::try_from(name).ok();
+                let mut repaired = false;
+                if options.repair
+                    && let Some(module_name) = &module_name
+                {
+                    self.migrate_legacy_manifest(module_name).await;
+                    repaired = !tokio::fs::try_exists(&path).await.unwrap_or(true);
+                }
+                if repaired && let Some(module_name) = module_name {
+                    module_names.insert(module_name);
+                } else if let Ok(manifest) = read_manifest(&path).await {
+                    owned.extend(manifest.manifest.provides.programs);
+                }
+                issue(Problem::LegacyManifest { path }, repaired);
+            }
+        }
+
+        let mut modules: BTreeMap<ModuleName, InstalledModuleManifest> = BTreeMap::new();
+        for module_name in module_names {
+            let bin_dir = self.module_dir(&module_name).join(BIN_DIR_NAME);
+            owned.extend(read_programs(&bin_dir).await.unwrap_or_default());
+
+            let path = self.module_dir(&module_name).join(MANIFEST_FILE_NAME);
+            if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+                issue(
+                    Problem::MissingManifest {
+                        module: module_name,
+                    },
+                    false,
+                );
+                continue;
+            }
+            match read_manifest(&path).await {
+                Ok(manifest) => _ = modules.insert(module_name, manifest),
+                Err(err) => {
+                    let error = err.to_string();
+                    issue(Problem::UnreadableManifest { path, error }, false)
+                },
+            }
+        }
+
+        let mut disabled_any = false;
+        for path in read_dir(&self.enable_dir).await? {
+            // a legacy entry points at a manifest file rather than a directory
+            if tokio::fs::try_exists(&path).await.unwrap_or(true) {
+                continue;
+            }
+            let repaired = options.repair && remove_link(&path).await;
+            disabled_any |= repaired;
+            issue(Problem::BrokenEnableLink { path }, repaired);
+        }
+        if disabled_any {
+            self.refresh_resolver().await;
+        }
+
+        for (module_name, manifest) in &modules {
+            let bin_dir = self.module_dir(module_name).join(BIN_DIR_NAME);
+            for program in &manifest.manifest.provides.programs {
+                let (module, program) = (module_name.clone(), program.clone());
+                let binary_path = bin_dir.join(&program);
+                let Ok(metadata) = tokio::fs::metadata(&binary_path).await else {
+                    issue(Problem::MissingProgram { module, program }, false);
+                    continue;
+                };
+                if !is_executable(&metadata) {
+                    let repaired = options.repair && make_executable(&binary_path).await;
+                    let (module, program) = (module.clone(), program.clone());
+                    issue(Problem::NotExecutable { module, program }, repaired);
+                }
+                let link_path = self.exec_dir.join(&program);
+                if !tokio::fs::try_exists(&link_path).await.unwrap_or(true) {
+                    let repaired = options.repair
+                        && self
+                            .add_binary(&program, &binary_path)
+                            .await
+                            .inspect_err(|err| tracing::warn!(?err, program, "failed to link"))
+                            .is_ok();
+                    issue(Problem::UnlinkedProgram { module, program }, repaired);
+                }
+            }
+        }
+
+        for path in read_dir(&self.exec_dir).await? {
+            let name = path.file_name().and_then(|name| name.to_str());
+            if name.is_some_and(|name| owned.contains(name)) {
+                continue;
+            }
+            // anything but a link may have been put there by hand, so it's left alone
+            let is_link = tokio::fs::symlink_metadata(&path)
+                .await
+                .is_ok_and(|metadata| metadata.file_type().is_symlink());
+            let repaired = options.repair && is_link && remove_link(&path).await;
+            issue(Problem::OrphanedBinary { path }, repaired);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Returns the paths of the entries of a directory, or none if it is missing.
+async fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, CheckError> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(CheckError::ReadDir(dir.into(), err)),
+    };
+
+    let mut paths = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| CheckError::ReadDir(dir.into(), e))?
+    {
+        paths.push(entry.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+async fn is_dir(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+}
+
+async fn remove_link(path: &Path) -> bool {
+    let result = match tokio::fs::remove_file(path).await {
+        // on Windows a symlink to a directory has to be removed as a directory
+        Err(err) if err.kind() != io::ErrorKind::NotFound => tokio::fs::remove_dir(path).await,
+        result => result,
+    };
+    result
+        .inspect_err(|err| tracing::warn!(?err, ?path, "failed to remove"))
+        .is_ok()
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(unix)]
+async fn make_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+    let result = async {
+        let mut permissions = tokio::fs::metadata(path).await?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        tokio::fs::set_permissions(path, permissions).await
+    }
+    .await;
+    result
+        .inspect_err(|err| tracing::warn!(?err, ?path, "failed to make executable"))
+        .is_ok()
+}
+
+#[cfg(not(unix))]
+async fn make_executable(_path: &Path) -> bool {
+    true
+}
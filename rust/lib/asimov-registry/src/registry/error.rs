@@ -1,6 +1,12 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::{io, path::PathBuf, string::String};
+use super::{LockHolder, Scope};
+use asimov_module::resolve::error::InsertManifestError;
+use std::{
+    io,
+    path::PathBuf,
+    string::{String, ToString},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -23,6 +29,8 @@ pub enum AddModuleError {
     ReadBinDir(PathBuf, #[source] io::Error),
     #[error("failed to add module binary `{0}`: {1}")]
     AddBinary(String, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
@@ -37,6 +45,8 @@ pub enum ManifestError {
     FindManifest(#[from] FindManifestError),
     #[error("unable to read module manifest: {0}")]
     Read(#[from] ReadManifestError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
@@ -51,6 +61,42 @@ pub enum RemoveModuleError {
     NotInstalled,
     #[error("failed to remove directory of installed module `{0}`: {1}")]
     RemoveModuleDir(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum ReplaceModuleError {
+    #[error("error while searching for manifest file: {0}")]
+    FindManifest(#[from] FindManifestError),
+    #[error("module is not installed")]
+    NotInstalled,
+    #[error(transparent)]
+    Swap(#[from] SwapModuleError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum RollbackError {
+    #[error("module is not installed")]
+    NotInstalled,
+    #[error("no previous version of the module to roll back to")]
+    NoPreviousVersion,
+    #[error(transparent)]
+    Swap(#[from] SwapModuleError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum SwapModuleError {
+    #[error("failed to read directory of module binaries `{0}`: {1}")]
+    ReadBinDir(PathBuf, #[source] io::Error),
+    #[error("failed to add module binary `{0}`: {1}")]
+    AddBinary(String, #[source] io::Error),
+    #[error("failed to swap module directories `{0}` and `{1}`: {2}")]
+    Swap(PathBuf, PathBuf, #[source] io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -59,6 +105,8 @@ pub enum RemoveBinaryError {
     InvalidName(String),
     #[error("failed to remove binary: {0}")]
     Io(#[from] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
@@ -67,6 +115,8 @@ pub enum InstalledModulesError {
     DirIo(PathBuf, #[source] io::Error),
     #[error("failed to read manifest at `{0}`: {1}")]
     ReadManifestError(PathBuf, #[source] ReadManifestError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
@@ -75,6 +125,30 @@ pub enum EnabledModulesError {
     DirIo(PathBuf, #[source] io::Error),
     #[error("failed to read manifest at `{0}`: {1}")]
     ReadManifestError(PathBuf, #[source] ReadManifestError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum ResolverError {
+    #[error("failed to read resolver file `{0}`: {1}")]
+    Read(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Update(#[from] UpdateResolverError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum UpdateResolverError {
+    #[error("failed to read enabled modules: {0}")]
+    EnabledModules(#[from] EnabledModulesError),
+    #[error("failed to add module `{0}` to resolver: {1}")]
+    Insert(String, #[source] InsertManifestError),
+    #[error("failed to write resolver file `{0}`: {1}")]
+    Write(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
@@ -91,12 +165,121 @@ pub enum EnableError {
     NotInstalled,
     #[error("failed to enable module: {0}")]
     Io(#[from] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
 }
 
 #[derive(Debug, Error)]
 #[error("failed to disable module: {0}")]
 pub struct DisableError(#[from] pub io::Error);
 
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("failed to open registry lock file `{0}`: {1}")]
+    Open(PathBuf, #[source] io::Error),
+    #[error("failed to lock registry lock file `{0}`: {1}")]
+    Lock(PathBuf, #[source] io::Error),
+    #[error(
+        "timed out waiting for {} to release the registry lock `{}`",
+        .holder.as_ref().map_or("another process".into(), ToString::to_string),
+        .path.display()
+    )]
+    Timeout {
+        path: PathBuf,
+        holder: Option<LockHolder>,
+    },
+}
+
+impl From<LockError> for io::Error {
+    fn from(err: LockError) -> Self {
+        match err {
+            LockError::Open(_, ref source) | LockError::Lock(_, ref source) => {
+                io::Error::new(source.kind(), err)
+            },
+            LockError::Timeout { .. } => io::Error::new(io::ErrorKind::TimedOut, err),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PinError {
+    #[error("module is not installed")]
+    NotInstalled,
+    #[error("failed to read pins file `{0}`: {1}")]
+    Read(PathBuf, #[source] io::Error),
+    #[error("invalid pins file `{0}`: {1}")]
+    Parse(PathBuf, #[source] serde_json::Error),
+    #[error("failed to write pins file `{0}`: {1}")]
+    Write(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+#[error("invalid version pin `{0}`: {1}")]
+pub struct InvalidPinError(pub String, #[source] pub semver::Error);
+
+#[derive(Debug, Error)]
+pub enum ScopeError {
+    #[error("no registry for the {0} scope")]
+    NoSuchScope(Scope),
+    #[error("modules of the {0} scope can't be changed")]
+    ReadOnly(Scope),
+    #[error("module is not installed")]
+    NotInstalled,
+    #[error("unable to read module manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error("failed to read directory `{0}`: {1}")]
+    ReadDir(PathBuf, #[source] io::Error),
+    #[error("failed to add module `{0}` to resolver: {1}")]
+    Insert(String, #[source] InsertManifestError),
+    #[error(transparent)]
+    Enable(#[from] EnableError),
+    #[error(transparent)]
+    Disable(#[from] DisableError),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+    #[error("failed to update `{0}`: {1}")]
+    Io(PathBuf, #[source] io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum GcError {
+    #[error("failed to read installed modules: {0}")]
+    InstalledModules(#[from] InstalledModulesError),
+    #[error("failed to read manifest of unused module: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error("failed to disable unused module: {0}")]
+    Disable(#[from] DisableError),
+    #[error("failed to remove unused module: {0}")]
+    RemoveModule(#[from] RemoveModuleError),
+    #[error("failed to read directory `{0}`: {1}")]
+    ReadDir(PathBuf, #[source] io::Error),
+    #[error("failed to remove `{0}`: {1}")]
+    Remove(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Error)]
+pub enum CheckError {
+    #[error("failed to read directory `{0}`: {1}")]
+    ReadDir(PathBuf, #[source] io::Error),
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[cfg(feature = "watch")]
+#[derive(Debug, Error)]
+pub enum WatchError {
+    #[error("failed to watch for changes to modules: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("failed to create directory `{0}`: {1}")]
+    CreateDir(PathBuf, #[source] io::Error),
+    #[error("failed to read directory `{0}`: {1}")]
+    ReadDir(PathBuf, #[source] io::Error),
+}
+
 mod common {
     use super::*;
 
@@ -0,0 +1,295 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{LockMode, ModuleName, Registry, error::GcError};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use asimov_module::tracing;
+use core::time::Duration;
+use std::path::{Path, PathBuf};
+use tokio::io;
+
+/// How long a work directory of the installer, or an unfinished download,
+/// is left alone before [`Registry::gc`] takes it for abandoned, by default.
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Clone, Debug, Default, bon::Builder)]
+pub struct GcOptions {
+    /// Whether to only report what would be removed.
+    #[builder(default)]
+    pub dry_run: bool,
+
+    /// How long an installer work directory or download must have been left
+    /// untouched to be removed, by default [`DEFAULT_STALE_AFTER`].
+    pub stale_after: Option<Duration>,
+}
+
+/// What [`Registry::gc`] removed, or would remove in a dry run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The modules installed as dependencies that no other module requires
+    /// anymore.
+    pub modules: Vec<ModuleName>,
+
+    /// The symlinks to binaries or modules that no longer exist.
+    pub links: Vec<PathBuf>,
+
+    /// The abandoned work directories and downloads of the installer.
+    pub work_dirs: Vec<PathBuf>,
+
+    /// The disk space freed, in bytes.
+    pub reclaimed: u64,
+}
+
+impl Registry {
+    /// Removes the modules installed as dependencies that no explicitly
+    /// installed module requires anymore, the dangling symlinks left in the
+    /// libexec and enable directories, and the work directories and downloads
+    /// of aborted installs.
+    pub async fn gc(&self, options: &GcOptions) -> Result<GcReport, GcError> {
+        let mode = if options.dry_run {
+            LockMode::Shared
+        } else {
+            LockMode::Exclusive
+        };
+        // migrating takes an exclusive lock, which a dry run doesn't hold
+        self.migrate_legacy_manifests().await;
+        let _lock = self.lock(mode).await?;
+        let mut report = GcReport::default();
+
+        for module_name in self.unused_dependencies().await? {
+            report.reclaimed += dir_size(&self.module_dir(&module_name)).await;
+            report.reclaimed += dir_size(&self.previous_module_dir(&module_name)).await;
+            if !options.dry_run {
+                self.remove_dependency(&module_name).await?;
+            }
+            tracing::debug!(%module_name, "removed unused dependency");
+            report.modules.push(module_name);
+        }
+
+        for dir in [&self.exec_dir, &self.enable_dir] {
+            for link in dangling_links(dir).await? {
+                if !options.dry_run {
+                    remove_if_exists(&link, false)
+                        .await
+                        .map_err(|e| GcError::Remove(link.clone(), e))?;
+                }
+                report.links.push(link);
+            }
+        }
+
+        let stale_after = options.stale_after.unwrap_or(DEFAULT_STALE_AFTER);
+        for dir in self.stale_work_dirs(stale_after).await? {
+            report.reclaimed += dir_size(&dir).await;
+            if !options.dry_run {
+                remove_if_exists(&dir, true)
+                    .await
+                    .map_err(|e| GcError::Remove(dir.clone(), e))?;
+            }
+            report.work_dirs.push(dir);
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the modules installed as dependencies that aren't required,
+    /// directly or indirectly, by any module installed explicitly.
+    async fn unused_dependencies(&self) -> Result<Vec<ModuleName>, GcError> {
+        let installed: BTreeMap<String, (bool, Vec<String>)> = self
+            .installed_modules()
+            .await?
+            .into_iter()
+            .map(|module| {
+                let requires = module.manifest.requires.modules;
+                (module.manifest.name, (module.dependency, requires))
+            })
+            .collect();
+
+        let mut required = BTreeSet::new();
+        let mut queue: Vec<&String> = installed
+            .iter()
+            .filter(|(_, (dependency, _))| !dependency)
+            .map(|(name, _)| name)
+            .collect();
+        while let Some(name) = queue.pop() {
+            if !required.insert(name.as_str()) {
+                continue;
+            }
+            if let Some((_, requires)) = installed.get(name) {
+                queue.extend(requires);
+            }
+        }
+
+        Ok(installed
+            .iter()
+            .filter(|(name, _)| !required.contains(name.as_str()))
+            .filter_map(|(name, _)| ModuleName::try_from(name.as_str()).ok())
+            .collect())
+    }
+
+    async fn remove_dependency(&self, module_name: &ModuleName) -> Result<(), GcError> {
+        let manifest = self.read_manifest(module_name).await?;
+        self.disable_module(module_name).await?;
+        for program in &manifest.manifest.provides.programs {
+            if let Err(err) = self.remove_binary(program).await {
+                tracing::warn!(
+                    ?err,
+                    program,
+                    "failed to remove binary of unused dependency"
+                );
+            }
+        }
+        self.remove_module(module_name).await?;
+        Ok(())
+    }
+
+    /// Returns the work directories of the installer, named like
+    /// `.{module}-XXXXXX` next to the install directory, and the downloads
+    /// in its `.downloads` directory, which haven't been touched lately.
+    async fn stale_work_dirs(&self, stale_after: Duration) -> Result<Vec<PathBuf>, GcError> {
+        let modules_dir = self.install_dir.parent().unwrap_or(&self.install_dir);
+        let downloads_dir = modules_dir.join(".downloads");
+
+        let mut stale = Vec::new();
+        for (dir, is_work_dir) in [
+            (modules_dir, is_work_dir_name as fn(&str) -> bool),
+            (&downloads_dir, |_: &str| true),
+        ] {
+            let mut entries = match tokio::fs::read_dir(dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(GcError::ReadDir(dir.into(), err)),
+            };
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| GcError::ReadDir(dir.into(), e))?
+            {
+                let is_candidate = entry.file_name().to_str().is_some_and(is_work_dir)
+                    && entry.file_type().await.is_ok_and(|t| t.is_dir());
+                if is_candidate && is_untouched_for(&entry.path(), stale_after).await {
+                    stale.push(entry.path());
+                }
+            }
+        }
+        Ok(stale)
+    }
+}
+
+/// Whether a name is that of a work directory of the installer, made by
+/// `tempfile` with a prefix of `.{module}-` and six random characters.
+fn is_work_dir_name(name: &str) -> bool {
+    name.strip_prefix('.')
+        .and_then(|name| name.rsplit_once('-'))
+        .is_some_and(|(prefix, suffix)| {
+            !prefix.is_empty()
+                && suffix.len() == 6
+                && suffix.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
+
+/// Whether nothing in a directory has been modified for the given duration.
+async fn is_untouched_for(dir: &Path, duration: Duration) -> bool {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let is_recent = |metadata: &std::fs::Metadata| {
+            metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_none_or(|elapsed| elapsed < duration)
+        };
+        let Ok(metadata) = tokio::fs::metadata(&dir).await else {
+            return false;
+        };
+        if is_recent(&metadata) {
+            return false;
+        }
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            return false;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else {
+                return false;
+            };
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else if is_recent(&metadata) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Returns the symlinks in a directory whose targets don't exist.
+async fn dangling_links(dir: &Path) -> Result<Vec<PathBuf>, GcError> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(GcError::ReadDir(dir.into(), err)),
+    };
+
+    let mut links = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| GcError::ReadDir(dir.into(), e))?
+    {
+        let path = entry.path();
+        let is_link = entry.file_type().await.is_ok_and(|t| t.is_symlink());
+        if is_link && !tokio::fs::try_exists(&path).await.unwrap_or(true) {
+            links.push(path);
+        }
+    }
+    Ok(links)
+}
+
+/// Returns the size of the files in a directory, or zero if it is missing.
+async fn dir_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.metadata().await {
+                Ok(metadata) if metadata.is_dir() => dirs.push(entry.path()),
+                Ok(metadata) => size += metadata.len(),
+                Err(_) => {},
+            }
+        }
+    }
+    size
+}
+
+async fn remove_if_exists(path: &Path, is_dir: bool) -> io::Result<()> {
+    let result = if is_dir {
+        tokio::fs::remove_dir_all(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    };
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn work_dir_names() {
+        assert!(is_work_dir_name(".example-a1B2c3"));
+        assert!(is_work_dir_name(".http-client-Zz09aa"));
+        assert!(!is_work_dir_name(".downloads"));
+        assert!(!is_work_dir_name("example-a1B2c3"));
+        assert!(!is_work_dir_name(".-a1B2c3"));
+        assert!(!is_work_dir_name(".example-a1B2"));
+    }
+}
@@ -0,0 +1,266 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{Registry, error::LockError};
+use alloc::{collections::BTreeMap, format, string::String};
+use asimov_module::tracing;
+use core::time::Duration;
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Read as _, Seek as _, Write as _},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+pub const REGISTRY_LOCK_FILE_NAME: &str = "registry.lock";
+
+/// How long to wait for another process to release the registry lock, unless
+/// [`Options::lock_timeout`](super::Options::lock_timeout) says otherwise.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to try again for a lock held by another process.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The registry locks held by this process, by path, which are shared by all
+/// its operations, so that they can call each other.
+static HELD: Mutex<BTreeMap<PathBuf, HeldLock>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug)]
+struct HeldLock {
+    file: File,
+    exclusive: bool,
+    guards: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// For reading, alongside other readers.
+    Shared,
+    /// For changing the modules, apart from anyone else.
+    Exclusive,
+}
+
+/// The process holding a registry lock exclusively, as recorded in the lock
+/// file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub program: Option<String>,
+}
+
+impl core::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "process {}", self.pid)?;
+        if let Some(program) = &self.program {
+            write!(f, " ({program})")?;
+        }
+        Ok(())
+    }
+}
+
+/// An advisory lock on a registry, released when dropped.
+///
+/// Locks are taken by all operations changing the registry, and shared by
+/// those reading it. Within a process, they are counted rather than taken
+/// again, and a shared lock never becomes exclusive.
+#[derive(Debug)]
+#[must_use = "the lock is released when dropped"]
+pub struct RegistryLock {
+    /// The lock file, unless the registry can't be locked for reading, such
+    /// as in a read-only location.
+    path: Option<PathBuf>,
+}
+
+impl Registry {
+    /// The advisory lock file of the registry.
+    pub fn lock_file(&self) -> &Path {
+        &self.lock_file
+    }
+
+    /// Locks the registry, waiting for other processes to release it for up
+    /// to [`Options::lock_timeout`](super::Options::lock_timeout).
+    ///
+    /// The lock is held by the process rather than the caller, so it keeps
+    /// out other processes, but is shared by all tasks and threads of this
+    /// one, which may call each other while holding it. Operations that
+    /// change the registry must lock it exclusively up front: asking for an
+    /// exclusive lock while this process holds a shared one waits for that
+    /// to be released, and so times out if the caller holds it itself.
+    pub async fn lock(&self, mode: LockMode) -> Result<RegistryLock, LockError> {
+        let path = &self.lock_file;
+        let deadline = Instant::now() + self.lock_timeout;
+        loop {
+            match try_lock(path, mode) {
+                Ok(Some(lock)) => return Ok(lock),
+                Ok(None) => {},
+                // readers may go ahead where the registry can't be locked
+                Err(err) if mode == LockMode::Shared => {
+                    tracing::debug!(?err, ?path, "reading the registry without a lock");
+                    return Ok(RegistryLock { path: None });
+                },
+                Err(err) => return Err(err),
+            }
+            if Instant::now() >= deadline {
+                return Err(LockError::Timeout {
+                    path: path.clone(),
+                    holder: held_by_this_process(path)
+                        .then(this_process)
+                        .or_else(|| read_holder(path)),
+                });
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Takes the lock unless another process holds it.
+fn try_lock(path: &Path, mode: LockMode) -> Result<Option<RegistryLock>, LockError> {
+    let exclusive = mode == LockMode::Exclusive;
+    let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(lock) = held.get_mut(path) {
+        // converting a shared lock would give it up while waiting, and two
+        // processes doing so would wait on each other, so instead wait for
+        // this process to release it
+        if exclusive && !lock.exclusive {
+            return Ok(None);
+        }
+        lock.guards += 1;
+        return Ok(Some(RegistryLock {
+            path: Some(path.into()),
+        }));
+    }
+
+    if exclusive && let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| LockError::Open(path.into(), e))?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| LockError::Open(path.into(), e))?;
+    if !lock_file(&file, exclusive).map_err(|e| LockError::Lock(path.into(), e))? {
+        return Ok(None);
+    }
+    if exclusive {
+        write_holder(&mut file);
+    }
+    held.insert(
+        path.into(),
+        HeldLock {
+            file,
+            exclusive,
+            guards: 1,
+        },
+    );
+    Ok(Some(RegistryLock {
+        path: Some(path.into()),
+    }))
+}
+
+impl Drop for RegistryLock {
+    fn drop(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(lock) = held.get_mut(path) else {
+            return;
+        };
+        lock.guards -= 1;
+        if lock.guards > 0 {
+            return;
+        }
+        if let Some(lock) = held.remove(path) {
+            if lock.exclusive {
+                let _ = lock.file.set_len(0);
+            }
+            if let Err(err) = lock.file.unlock() {
+                tracing::debug!(?err, ?path, "failed to unlock the registry");
+            }
+        }
+    }
+}
+
+/// Locks a file, returning whether it was, or another process holds it.
+fn lock_file(file: &File, exclusive: bool) -> io::Result<bool> {
+    let result = if exclusive {
+        file.try_lock()
+    } else {
+        file.try_lock_shared()
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+/// Records this process as the holder of an exclusive lock, for others
+/// waiting on it.
+fn write_holder(file: &mut File) {
+    let holder = this_process();
+    let holder = match holder.program {
+        Some(program) => format!("{} {program}\n", holder.pid),
+        None => format!("{}\n", holder.pid),
+    };
+    let result = file
+        .set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| file.write_all(holder.as_bytes()));
+    if let Err(err) = result {
+        tracing::debug!(?err, "failed to record the holder of the registry lock");
+    }
+}
+
+fn this_process() -> LockHolder {
+    LockHolder {
+        pid: std::process::id(),
+        program: std::env::current_exe()
+            .ok()
+            .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned())),
+    }
+}
+
+/// Whether this process holds the lock, which it does only shared while
+/// waiting for it.
+fn held_by_this_process(path: &Path) -> bool {
+    HELD.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains_key(path)
+}
+
+fn read_holder(path: &Path) -> Option<LockHolder> {
+    let mut content = String::new();
+    File::open(path).ok()?.read_to_string(&mut content).ok()?;
+    let (pid, program) = match content.trim().split_once(' ') {
+        Some((pid, program)) => (pid, Some(program.into())),
+        None => (content.trim(), None),
+    };
+    Some(LockHolder {
+        pid: pid.parse().ok()?,
+        program,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString as _;
+
+    #[test]
+    fn holder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REGISTRY_LOCK_FILE_NAME);
+        assert_eq!(read_holder(&path), None);
+
+        std::fs::write(&path, "42 asimov\n").unwrap();
+        let holder = read_holder(&path).unwrap();
+        assert_eq!(holder.to_string(), "process 42 (asimov)");
+
+        std::fs::write(&path, "42\n").unwrap();
+        assert_eq!(read_holder(&path).unwrap().program, None);
+    }
+}
@@ -0,0 +1,222 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    LockMode, ModuleName, Registry,
+    error::{InvalidPinError, PinError},
+};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+};
+use asimov_module::tracing;
+use core::str::FromStr;
+use std::path::Path;
+use tokio::io;
+
+pub const PINS_FILE_NAME: &str = "pins.json";
+
+/// The versions that a module may be upgraded to, as kept by
+/// [`Registry::pin_module`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Pin {
+    /// Exactly this version, such as `1.2.3`.
+    Version(String),
+    /// Any version matching a semver requirement, such as `^1.2` or
+    /// `>=1.0, <2`.
+    Range(semver::VersionReq),
+}
+
+impl Pin {
+    /// Whether a module may be at the given version.
+    ///
+    /// Versions are compared leniently, ignoring any leading `v` and
+    /// filling in missing minor and patch numbers, so that `v1.2` matches
+    /// `^1.2`.
+    pub fn allows(&self, version: &str) -> bool {
+        match self {
+            Self::Version(pinned) => match (parse_version(pinned), parse_version(version)) {
+                (Some(pinned), Some(version)) => pinned == version,
+                _ => pinned.trim_start_matches('v') == version.trim_start_matches('v'),
+            },
+            Self::Range(req) => parse_version(version).is_some_and(|version| req.matches(&version)),
+        }
+    }
+}
+
+impl FromStr for Pin {
+    type Err = InvalidPinError;
+
+    /// Parses a version as an exact pin, and anything else as a semver
+    /// requirement, so that `1.2.3` means exactly that version but `^1.2.3`
+    /// or `1.2` any compatible one.
+    fn from_str(pin: &str) -> Result<Self, Self::Err> {
+        let pin = pin.trim();
+        if semver::Version::parse(pin.trim_start_matches('v')).is_ok() {
+            return Ok(Self::Version(pin.into()));
+        }
+        semver::VersionReq::parse(pin)
+            .map(Self::Range)
+            .map_err(|e| InvalidPinError(pin.into(), e))
+    }
+}
+
+impl TryFrom<String> for Pin {
+    type Error = InvalidPinError;
+
+    fn try_from(pin: String) -> Result<Self, Self::Error> {
+        pin.parse()
+    }
+}
+
+impl From<Pin> for String {
+    fn from(pin: Pin) -> Self {
+        pin.to_string()
+    }
+}
+
+impl core::fmt::Display for Pin {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Version(version) => f.write_str(version),
+            Self::Range(req) => write!(f, "{req}"),
+        }
+    }
+}
+
+impl Registry {
+    /// The file keeping the pins of the modules, see [`Self::pin_module`].
+    pub fn pins_file(&self) -> &Path {
+        &self.pins_file
+    }
+
+    /// Returns the pins of all modules.
+    pub async fn pins(&self) -> Result<BTreeMap<ModuleName, Pin>, PinError> {
+        let _lock = self.lock(LockMode::Shared).await?;
+        let pins = self.read_pins().await?;
+        Ok(pins
+            .into_iter()
+            .filter_map(
+                |(module_name, pin)| match ModuleName::try_from(module_name) {
+                    Ok(module_name) => Some((module_name, pin)),
+                    Err(err) => {
+                        tracing::debug!(?err, "skipping pin of invalid module name");
+                        None
+                    },
+                },
+            )
+            .collect())
+    }
+
+    pub async fn module_pin(&self, module_name: &ModuleName) -> Result<Option<Pin>, PinError> {
+        let _lock = self.lock(LockMode::Shared).await?;
+        Ok(self.read_pins().await?.remove(module_name.as_str()))
+    }
+
+    /// Pins an installed module, so that upgrades keep it to the versions
+    /// that the pin allows, replacing any previous pin.
+    pub async fn pin_module(&self, module_name: &ModuleName, pin: Pin) -> Result<(), PinError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
+        if !self.is_module_installed(module_name).await.unwrap_or(false) {
+            return Err(PinError::NotInstalled);
+        }
+        let mut pins = self.read_pins().await?;
+        pins.insert(module_name.as_str().into(), pin);
+        self.write_pins(&pins).await
+    }
+
+    /// Removes the pin of a module, returning whether it had one.
+    pub async fn unpin_module(&self, module_name: &ModuleName) -> Result<bool, PinError> {
+        let _lock = self.lock(LockMode::Exclusive).await?;
+        let mut pins = self.read_pins().await?;
+        if pins.remove(module_name.as_str()).is_none() {
+            return Ok(false);
+        }
+        self.write_pins(&pins).await?;
+        Ok(true)
+    }
+
+    async fn read_pins(&self) -> Result<BTreeMap<String, Pin>, PinError> {
+        let path = &self.pins_file;
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(PinError::Read(path.clone(), err)),
+        };
+        serde_json::from_slice(&content).map_err(|e| PinError::Parse(path.clone(), e))
+    }
+
+    async fn write_pins(&self, pins: &BTreeMap<String, Pin>) -> Result<(), PinError> {
+        let path = &self.pins_file;
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let content = serde_json::to_vec_pretty(pins).map_err(io::Error::other);
+
+        // write to a temporary file first, so that readers never see a partially written file
+        let result = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&temp_path, content?).await?;
+            tokio::fs::rename(&temp_path, path).await
+        }
+        .await;
+
+        result.map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            PinError::Write(path.clone(), e)
+        })
+    }
+}
+
+/// Parses a version such as `v1.2` as `1.2.0`.
+fn parse_version(version: &str) -> Option<semver::Version> {
+    let version = version.trim().trim_start_matches('v');
+    if let Ok(version) = semver::Version::parse(version) {
+        return Some(version);
+    }
+    let (core, rest) = match version.find(['-', '+']) {
+        Some(index) => version.split_at(index),
+        None => (version, ""),
+    };
+    let padding = match core.split('.').count() {
+        1 => ".0.0",
+        2 => ".0",
+        _ => return None,
+    };
+    semver::Version::parse(&format!("{core}{padding}{rest}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pins() {
+        assert_eq!(
+            "1.2.3".parse::<Pin>().unwrap(),
+            Pin::Version("1.2.3".into())
+        );
+        assert_eq!(
+            "v1.2.3".parse::<Pin>().unwrap(),
+            Pin::Version("v1.2.3".into())
+        );
+        assert!(matches!("^1.2".parse::<Pin>().unwrap(), Pin::Range(_)));
+        assert!(matches!("1.2".parse::<Pin>().unwrap(), Pin::Range(_)));
+        assert!("latest".parse::<Pin>().is_err());
+        assert_eq!(">=1.0, <2".parse::<Pin>().unwrap().to_string(), ">=1.0, <2");
+    }
+
+    #[test]
+    fn allowed_versions() {
+        let exact: Pin = "1.2.3".parse().unwrap();
+        assert!(exact.allows("v1.2.3"));
+        assert!(!exact.allows("1.2.4"));
+
+        let range: Pin = "~1.2".parse().unwrap();
+        assert!(range.allows("1.2.9"));
+        assert!(range.allows("v1.2"));
+        assert!(!range.allows("1.3.0"));
+        assert!(!range.allows("nightly"));
+    }
+}
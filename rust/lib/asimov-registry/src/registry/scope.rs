@@ -0,0 +1,365 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    LockMode, ModuleName, Options, Registry, create_symlink,
+    error::{InstalledModulesError, ManifestError, ScopeError},
+};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use asimov_module::{InstalledModuleManifest, PROJECT_DIR_NAME, resolve::SyncResolver, tracing};
+use std::path::{Path, PathBuf};
+use tokio::io;
+
+/// The environment variable overriding [`system_root`].
+pub const SYSTEM_ROOT_VAR: &str = "ASIMOV_SYSTEM_ROOT";
+
+/// The name of the directory in a scope with an entry for each module that
+/// is disabled there despite being enabled in a lower scope.
+pub const DISABLED_DIR_NAME: &str = "disabled";
+
+/// Where the modules of a [`LayeredRegistry`] are installed and enabled, in
+/// increasing order of precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    /// Installed for all users of the machine, and read-only.
+    System,
+    /// Installed by the user, in [`asimov_env::paths::asimov_root`].
+    User,
+    /// Installed for a project, in its `.asimov/` directory.
+    Project,
+}
+
+impl core::fmt::Display for Scope {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Project => "project",
+        })
+    }
+}
+
+/// A module of a [`LayeredRegistry`], with the scope it is installed in.
+#[derive(Clone, Debug)]
+pub struct ScopedModule {
+    pub scope: Scope,
+    pub manifest: InstalledModuleManifest,
+}
+
+/// The system-wide asimov root, from [`SYSTEM_ROOT_VAR`] or else such as
+/// `/usr/local/share/asimov`.
+pub fn system_root() -> PathBuf {
+    if let Some(root) = getenv::var(SYSTEM_ROOT_VAR) {
+        return root.into();
+    }
+
+    #[cfg(windows)]
+    if let Some(program_data) = std::env::var_os("ProgramData") {
+        return PathBuf::from(program_data).join("ASIMOV");
+    }
+
+    PathBuf::from("/usr/local/share/asimov")
+}
+
+/// Finds the project-local asimov root, a `.asimov/` directory with a
+/// `modules/` directory, in the given directory or the closest of its
+/// ancestors that has one.
+///
+/// The user's own `~/.asimov/` is never taken for a project.
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    let asimov_root = asimov_env::paths::asimov_root();
+    let asimov_root = asimov_root.canonicalize().unwrap_or(asimov_root);
+
+    start.ancestors().find_map(|dir| {
+        let project_root = dir.join(PROJECT_DIR_NAME);
+        let is_asimov_root = project_root
+            .canonicalize()
+            .is_ok_and(|project_root| project_root == asimov_root);
+        (!is_asimov_root && project_root.join("modules").is_dir()).then_some(project_root)
+    })
+}
+
+/// The registries of several scopes, seen as one.
+///
+/// A module installed in several scopes is taken from the highest one. Each
+/// scope may enable a module installed in any scope, or disable one that a
+/// lower scope enables, and the highest scope to do either decides.
+#[derive(Clone, Debug)]
+pub struct LayeredRegistry {
+    /// By increasing precedence.
+    layers: Vec<(Scope, Registry)>,
+}
+
+impl Default for LayeredRegistry {
+    /// Layers the system-wide registry, if there is one, the user's own,
+    /// and that of the project of the current directory, if any.
+    fn default() -> Self {
+        let system_root = system_root();
+        let system = system_root.join("modules").is_dir().then(|| {
+            (
+                Scope::System,
+                Registry::new(system_root, Options::default()),
+            )
+        });
+        let project = std::env::current_dir()
+            .ok()
+            .and_then(|dir| find_project_root(&dir))
+            .map(|root| (Scope::Project, Registry::new(root, Options::default())));
+
+        Self::new(
+            system
+                .into_iter()
+                .chain([(Scope::User, Registry::default())])
+                .chain(project),
+        )
+    }
+}
+
+impl LayeredRegistry {
+    /// Layers the registries of the given scopes, of which the last one
+    /// given for a scope is kept.
+    pub fn new(layers: impl IntoIterator<Item = (Scope, Registry)>) -> Self {
+        let layers: BTreeMap<Scope, Registry> = layers.into_iter().collect();
+        Self {
+            layers: layers.into_iter().collect(),
+        }
+    }
+
+    pub fn scopes(&self) -> impl DoubleEndedIterator<Item = Scope> + '_ {
+        self.layers.iter().map(|(scope, _)| *scope)
+    }
+
+    pub fn registry(&self, scope: Scope) -> Option<&Registry> {
+        self.layers
+            .iter()
+            .find_map(|(s, registry)| (*s == scope).then_some(registry))
+    }
+
+    /// Returns the scope that a module is installed in, the highest one if
+    /// it is installed in several.
+    pub async fn module_scope(&self, module_name: &ModuleName) -> Option<Scope> {
+        for (scope, registry) in self.layers.iter().rev() {
+            if registry
+                .is_module_installed(module_name)
+                .await
+                .unwrap_or(false)
+            {
+                return Some(*scope);
+            }
+        }
+        None
+    }
+
+    pub async fn read_manifest(
+        &self,
+        module_name: &ModuleName,
+    ) -> Result<ScopedModule, ManifestError> {
+        let scope = self
+            .module_scope(module_name)
+            .await
+            .ok_or(ManifestError::NotInstalled)?;
+        let registry = self.registry(scope).ok_or(ManifestError::NotInstalled)?;
+        Ok(ScopedModule {
+            scope,
+            manifest: registry.read_manifest(module_name).await?,
+        })
+    }
+
+    /// Returns the installed modules of all scopes, each from the highest
+    /// scope it is installed in.
+    pub async fn installed_modules(&self) -> Result<Vec<ScopedModule>, InstalledModulesError> {
+        let mut modules = BTreeMap::new();
+        for (scope, registry) in self.layers.iter().rev() {
+            if !tokio::fs::try_exists(&registry.install_dir)
+                .await
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            for manifest in registry.installed_modules().await? {
+                modules
+                    .entry(manifest.manifest.name.clone())
+                    .or_insert(ScopedModule {
+                        scope: *scope,
+                        manifest,
+                    });
+            }
+        }
+        Ok(modules.into_values().collect())
+    }
+
+    /// Returns the scope whose entry enables a module, unless it is disabled.
+    pub async fn enabled_scope(&self, module_name: &ModuleName) -> Option<Scope> {
+        for (scope, registry) in self.layers.iter().rev() {
+            if is_disabled_in(registry, module_name).await {
+                return None;
+            }
+            if registry
+                .is_module_enabled(module_name)
+                .await
+                .unwrap_or(false)
+            {
+                return Some(*scope);
+            }
+        }
+        None
+    }
+
+    pub async fn is_module_enabled(&self, module_name: &ModuleName) -> bool {
+        self.enabled_scope(module_name).await.is_some()
+    }
+
+    /// Returns the enabled modules of all scopes, each from the highest
+    /// scope it is installed in.
+    pub async fn enabled_modules(&self) -> Result<Vec<ScopedModule>, ScopeError> {
+        let mut module_names = BTreeSet::new();
+        for (_, registry) in &self.layers {
+            let mut entries = match tokio::fs::read_dir(&registry.enable_dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ScopeError::ReadDir(registry.enable_dir.clone(), err)),
+            };
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| ScopeError::ReadDir(registry.enable_dir.clone(), e))?
+            {
+                if let Some(Ok(module_name)) = entry.file_name().to_str().map(ModuleName::try_from)
+                {
+                    module_names.insert(module_name);
+                }
+            }
+        }
+
+        let mut modules = Vec::new();
+        for module_name in module_names {
+            if !self.is_module_enabled(&module_name).await {
+                continue;
+            }
+            match self.read_manifest(&module_name).await {
+                Ok(module) => modules.push(module),
+                Err(ManifestError::NotInstalled) => {
+                    tracing::debug!(%module_name, "skipping enabled module that isn't installed")
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(modules)
+    }
+
+    /// Builds a resolver for the enabled modules of all scopes.
+    pub async fn resolver(&self) -> Result<SyncResolver, ScopeError> {
+        let mut resolver = SyncResolver::new();
+        for module in self.enabled_modules().await? {
+            resolver
+                .insert_manifest(&module.manifest.manifest)
+                .map_err(|e| ScopeError::Insert(module.manifest.manifest.name.clone(), e))?;
+        }
+        Ok(resolver)
+    }
+
+    /// Enables a module in a scope, wherever it is installed.
+    pub async fn enable_module(
+        &self,
+        module_name: &ModuleName,
+        scope: Scope,
+    ) -> Result<(), ScopeError> {
+        let registry = self.writable(scope)?;
+        let installed_scope = self
+            .module_scope(module_name)
+            .await
+            .ok_or(ScopeError::NotInstalled)?;
+
+        let _lock = registry.lock(LockMode::Exclusive).await?;
+        let marker = disabled_marker(registry, module_name);
+        remove_file_if_exists(&marker)
+            .await
+            .map_err(|e| ScopeError::Io(marker, e))?;
+
+        if installed_scope == scope {
+            registry.enable_module(module_name).await?;
+            return Ok(());
+        }
+
+        // link to the module in the scope it is installed in
+        let target = self
+            .registry(installed_scope)
+            .ok_or(ScopeError::NotInstalled)?
+            .module_dir(module_name);
+        let target = std::path::absolute(&target).unwrap_or(target);
+        let link = registry.enable_dir.join(module_name.as_str());
+        registry.disable_module(module_name).await?;
+        tokio::fs::create_dir_all(&registry.enable_dir)
+            .await
+            .map_err(|e| ScopeError::Io(registry.enable_dir.clone(), e))?;
+        create_symlink(&target, &link, true)
+            .await
+            .map_err(|e| ScopeError::Io(link, e))
+    }
+
+    /// Disables a module in a scope, overriding any lower scope that
+    /// enables it.
+    pub async fn disable_module(
+        &self,
+        module_name: &ModuleName,
+        scope: Scope,
+    ) -> Result<(), ScopeError> {
+        let registry = self.writable(scope)?;
+        let _lock = registry.lock(LockMode::Exclusive).await?;
+        registry.disable_module(module_name).await?;
+
+        let mut enabled_below = false;
+        for (_, registry) in self.layers.iter().rev().filter(|(s, _)| *s < scope) {
+            if is_disabled_in(registry, module_name).await {
+                break;
+            }
+            if registry
+                .is_module_enabled(module_name)
+                .await
+                .unwrap_or(false)
+            {
+                enabled_below = true;
+                break;
+            }
+        }
+        if enabled_below {
+            let marker = disabled_marker(registry, module_name);
+            let write = async {
+                tokio::fs::create_dir_all(marker.parent().unwrap_or(&marker)).await?;
+                tokio::fs::write(&marker, b"").await
+            };
+            write.await.map_err(|e| ScopeError::Io(marker, e))?;
+        }
+        Ok(())
+    }
+
+    fn writable(&self, scope: Scope) -> Result<&Registry, ScopeError> {
+        if scope == Scope::System {
+            return Err(ScopeError::ReadOnly(scope));
+        }
+        self.registry(scope).ok_or(ScopeError::NoSuchScope(scope))
+    }
+}
+
+/// The entry disabling a module in the scope of a registry.
+fn disabled_marker(registry: &Registry, module_name: &ModuleName) -> PathBuf {
+    registry
+        .enable_dir
+        .with_file_name(DISABLED_DIR_NAME)
+        .join(module_name.as_str())
+}
+
+async fn is_disabled_in(registry: &Registry, module_name: &ModuleName) -> bool {
+    tokio::fs::try_exists(disabled_marker(registry, module_name))
+        .await
+        .unwrap_or(false)
+}
+
+async fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
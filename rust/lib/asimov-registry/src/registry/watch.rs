@@ -0,0 +1,311 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{MANIFEST_FILE_NAME, ModuleName, Registry, error::WatchError};
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    vec::Vec,
+};
+use asimov_module::tracing;
+use core::time::Duration;
+use notify::{RecursiveMode, Watcher as _};
+use std::path::{Component, Path, PathBuf};
+use tokio::{io, sync::mpsc};
+
+/// How long to wait for a change to the registry to settle, such as the
+/// several renames of a module upgrade, before looking at what changed.
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// A change to the installed or enabled modules, as reported by
+/// [`RegistryWatcher::next`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModuleEvent {
+    Installed(ModuleName),
+    Removed(ModuleName),
+    Enabled(ModuleName),
+    Disabled(ModuleName),
+    /// The module was replaced by another version, or rolled back.
+    Upgraded(ModuleName),
+}
+
+impl ModuleEvent {
+    pub fn module_name(&self) -> &ModuleName {
+        match self {
+            Self::Installed(name)
+            | Self::Removed(name)
+            | Self::Enabled(name)
+            | Self::Disabled(name)
+            | Self::Upgraded(name) => name,
+        }
+    }
+}
+
+/// Which modules a file system notification may concern.
+#[derive(Debug)]
+enum Touched {
+    Module(ModuleName),
+    All,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ModuleState {
+    /// The content of the manifest, which differs between versions.
+    manifest: Option<Vec<u8>>,
+    enabled: bool,
+}
+
+/// Reports changes to the modules of a [`Registry`], as returned by
+/// [`Registry::watch`]. Changes are noticed for as long as it is alive.
+#[derive(Debug)]
+pub struct RegistryWatcher {
+    registry: Registry,
+    _watcher: notify::RecommendedWatcher,
+    touched: mpsc::UnboundedReceiver<Touched>,
+    states: BTreeMap<ModuleName, ModuleState>,
+    pending: VecDeque<ModuleEvent>,
+}
+
+impl Registry {
+    /// Watches the install and enable directories for changes, made by this
+    /// or any other process, so that long-running consumers can rebuild
+    /// their resolvers as modules are enabled or disabled. The directories
+    /// are created if missing, as for a fresh registry.
+    ///
+    /// ```rust,no_run
+    /// # async fn example() {
+    /// # use asimov_registry::Registry;
+    /// let registry = Registry::default();
+    /// let mut watcher = registry.watch().await.unwrap();
+    /// loop {
+    ///     let event = watcher.next().await;
+    ///     let resolver = registry.resolver().await.unwrap();
+    /// }
+    /// # }
+    /// ```
+    pub async fn watch(&self) -> Result<RegistryWatcher, WatchError> {
+        for dir in [&self.install_dir, &self.enable_dir] {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| WatchError::CreateDir(dir.clone(), e))?;
+        }
+
+        let (sender, touched) = mpsc::unbounded_channel();
+        let dirs = [
+            (self.install_dir.clone(), canonical(&self.install_dir)),
+            (self.enable_dir.clone(), canonical(&self.enable_dir)),
+        ];
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let event: notify::Event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    tracing::debug!(?err, "failed to watch the registry");
+                    let _ = sender.send(Touched::All);
+                    return;
+                },
+            };
+            if event.need_rescan() {
+                let _ = sender.send(Touched::All);
+                return;
+            }
+            for path in &event.paths {
+                if let Some(module_name) = dirs.iter().find_map(|dirs| module_of(path, dirs)) {
+                    let _ = sender.send(Touched::Module(module_name));
+                }
+            }
+        })?;
+        // the directories of modules are watched too, to notice their
+        // manifests changing
+        watcher.watch(&self.install_dir, RecursiveMode::Recursive)?;
+        watcher.watch(&self.enable_dir, RecursiveMode::NonRecursive)?;
+
+        let mut watcher = RegistryWatcher {
+            registry: self.clone(),
+            _watcher: watcher,
+            touched,
+            states: BTreeMap::new(),
+            pending: VecDeque::new(),
+        };
+        for module_name in watcher.module_names().await? {
+            let state = watcher.read_state(&module_name).await;
+            watcher.states.insert(module_name, state);
+        }
+        Ok(watcher)
+    }
+}
+
+impl RegistryWatcher {
+    /// Waits for the next change to the modules.
+    ///
+    /// A module removed while it was enabled is reported as disabled first,
+    /// and a module installed and enabled at once as installed first. Errors
+    /// while watching, such as missed notifications, are logged and lead to
+    /// looking at all modules again, so that no change goes unreported.
+    pub async fn next(&mut self) -> ModuleEvent {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+
+            // the sender lives as long as the watcher, so this never ends
+            let Some(first) = self.touched.recv().await else {
+                return core::future::pending().await;
+            };
+            tokio::time::sleep(SETTLE_DELAY).await;
+            let mut touched = BTreeSet::new();
+            let mut all = false;
+            for touch in
+                core::iter::once(first).chain(core::iter::from_fn(|| self.touched.try_recv().ok()))
+            {
+                match touch {
+                    Touched::Module(module_name) => _ = touched.insert(module_name),
+                    Touched::All => all = true,
+                }
+            }
+            if all {
+                touched.extend(self.states.keys().cloned());
+                match self.module_names().await {
+                    Ok(module_names) => touched.extend(module_names),
+                    Err(err) => tracing::debug!(?err, "failed to list the modules"),
+                }
+            }
+
+            for module_name in touched {
+                let state = self.read_state(&module_name).await;
+                let previous = self.states.remove(&module_name).unwrap_or_default();
+                self.pending
+                    .extend(changes(&module_name, &previous, &state));
+                if state != ModuleState::default() {
+                    self.states.insert(module_name, state);
+                }
+            }
+        }
+    }
+
+    /// The names of the modules that are installed or enabled.
+    async fn module_names(&self) -> Result<BTreeSet<ModuleName>, WatchError> {
+        let mut module_names = BTreeSet::new();
+        for dir in [&self.registry.install_dir, &self.registry.enable_dir] {
+            let mut entries = match tokio::fs::read_dir(dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(WatchError::ReadDir(dir.clone(), err)),
+            };
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| WatchError::ReadDir(dir.clone(), e))?
+            {
+                if let Some(Ok(module_name)) = entry.file_name().to_str().map(ModuleName::try_from)
+                {
+                    module_names.insert(module_name);
+                }
+            }
+        }
+        Ok(module_names)
+    }
+
+    async fn read_state(&self, module_name: &ModuleName) -> ModuleState {
+        let manifest_path = self
+            .registry
+            .module_dir(module_name)
+            .join(MANIFEST_FILE_NAME);
+        ModuleState {
+            manifest: tokio::fs::read(manifest_path).await.ok(),
+            enabled: self
+                .registry
+                .is_module_enabled(module_name)
+                .await
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// The events that take a module from one state to another.
+fn changes(
+    module_name: &ModuleName,
+    previous: &ModuleState,
+    current: &ModuleState,
+) -> Vec<ModuleEvent> {
+    let mut events = Vec::new();
+    let name = || module_name.clone();
+    match (&previous.manifest, &current.manifest) {
+        (None, Some(_)) => events.push(ModuleEvent::Installed(name())),
+        (Some(_), None) => events.push(ModuleEvent::Removed(name())),
+        (Some(previous), Some(current)) if previous != current => {
+            events.push(ModuleEvent::Upgraded(name()))
+        },
+        _ => {},
+    }
+    match (previous.enabled, current.enabled) {
+        (false, true) => events.push(ModuleEvent::Enabled(name())),
+        // disabled before being removed
+        (true, false) => events.insert(0, ModuleEvent::Disabled(name())),
+        _ => {},
+    }
+    events
+}
+
+/// Returns the module that a path in a watched directory belongs to, given
+/// the directory both as configured and as reported by the platform.
+fn module_of(path: &Path, (dir, canonical_dir): &(PathBuf, Option<PathBuf>)) -> Option<ModuleName> {
+    let relative = path
+        .strip_prefix(dir)
+        .ok()
+        .or_else(|| path.strip_prefix(canonical_dir.as_ref()?).ok())?;
+    match relative.components().next()? {
+        Component::Normal(name) => ModuleName::try_from(name.to_str()?).ok(),
+        _ => None,
+    }
+}
+
+fn canonical(dir: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(dir).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn events_between_states() {
+        let name: ModuleName = "example".parse().unwrap();
+        let state = |manifest: Option<&[u8]>, enabled| ModuleState {
+            manifest: manifest.map(Vec::from),
+            enabled,
+        };
+
+        assert_eq!(
+            changes(&name, &state(None, false), &state(Some(b"1"), true)),
+            vec![
+                ModuleEvent::Installed(name.clone()),
+                ModuleEvent::Enabled(name.clone())
+            ]
+        );
+        assert_eq!(
+            changes(&name, &state(Some(b"1"), true), &state(None, false)),
+            vec![
+                ModuleEvent::Disabled(name.clone()),
+                ModuleEvent::Removed(name.clone())
+            ]
+        );
+        assert_eq!(
+            changes(&name, &state(Some(b"1"), true), &state(Some(b"2"), true)),
+            vec![ModuleEvent::Upgraded(name.clone())]
+        );
+        assert!(changes(&name, &state(Some(b"1"), true), &state(Some(b"1"), true)).is_empty());
+    }
+
+    #[test]
+    fn module_of_path() {
+        let dirs = (PathBuf::from("/asimov/modules/installed"), None);
+        assert_eq!(
+            module_of(Path::new("/asimov/modules/installed/example/bin/x"), &dirs),
+            Some("example".parse().unwrap())
+        );
+        assert_eq!(
+            module_of(Path::new("/asimov/modules/installed"), &dirs),
+            None
+        );
+        assert_eq!(module_of(Path::new("/elsewhere/example"), &dirs), None);
+    }
+}
@@ -1,7 +1,11 @@
-use std::path::PathBuf;
-
-use asimov_module::InstalledModuleManifest;
-use asimov_registry::Registry;
+use std::{path::PathBuf, time::Duration};
+
+use asimov_module::{InstalledModuleManifest, ModuleName, resolve::SyncResolver};
+use asimov_registry::{
+    CheckOptions, GcOptions, GcReport, LayeredRegistry, LockHolder, LockMode, ModuleEvent, Options,
+    Pin, Problem, Registry, Scope,
+    error::{EnableError, LockError, PinError, RollbackError, ScopeError},
+};
 use tempfile::tempdir;
 
 // See: https://asimov-specs.github.io/module-manifest/
@@ -202,6 +206,7 @@ pub async fn test_legacy_manifest_never_replaces_a_current_one() {
         let manifest = InstalledModuleManifest {
             version: Some(version.into()),
             manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+            ..Default::default()
         };
         serde_json::to_vec(&manifest).unwrap()
     };
@@ -243,6 +248,7 @@ pub async fn test_migrate_legacy_version() {
     let manifest = InstalledModuleManifest {
         version: Some("0.1.7".into()),
         manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+        ..Default::default()
     };
     tokio::fs::write(&legacy_path, serde_json::to_vec(&manifest).unwrap())
         .await
@@ -259,3 +265,557 @@ pub async fn test_migrate_legacy_version() {
             .is_file()
     );
 }
+
+#[tokio::test]
+pub async fn test_resolver_file() {
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+    registry.create_file_tree().await.unwrap();
+
+    let sample = "sample".parse().unwrap();
+
+    let module_dir = base_dir.path().join("modules/installed/sample");
+    tokio::fs::create_dir(&module_dir).await.unwrap();
+    tokio::fs::write(module_dir.join("manifest.json"), SAMPLE_MANIFEST)
+        .await
+        .unwrap();
+
+    assert!(!registry.resolver_file().exists());
+    assert!(
+        registry
+            .resolver()
+            .await
+            .unwrap()
+            .resolve("ipfs://cid")
+            .unwrap()
+            .is_empty()
+    );
+
+    registry.enable_module(&sample).await.unwrap();
+    let bytes = std::fs::read(registry.resolver_file()).unwrap();
+    let resolver = SyncResolver::from_bytes(&bytes).unwrap();
+    assert_eq!(resolver.resolve("ipfs://cid").unwrap()[0].name, "ipfs");
+    assert_eq!(registry.resolver().await.unwrap(), resolver);
+
+    registry.disable_module(&sample).await.unwrap();
+    let bytes = std::fs::read(registry.resolver_file()).unwrap();
+    let resolver = SyncResolver::from_bytes(&bytes).unwrap();
+    assert!(resolver.resolve("ipfs://cid").unwrap().is_empty());
+
+    // a corrupt file is rebuilt from the manifests
+    registry.enable_module(&sample).await.unwrap();
+    std::fs::write(registry.resolver_file(), b"garbage").unwrap();
+    let resolver = registry.resolver().await.unwrap();
+    assert_eq!(resolver.resolve("ipfs://cid").unwrap()[0].name, "ipfs");
+    let bytes = std::fs::read(registry.resolver_file()).unwrap();
+    assert_eq!(SyncResolver::from_bytes(&bytes).unwrap(), resolver);
+
+    // as is one written for other enabled modules, however recent
+    let enabled = base_dir.path().join("modules/enabled/sample");
+    std::fs::remove_file(&enabled).unwrap();
+    let future = std::time::SystemTime::now() + Duration::from_secs(60);
+    std::fs::File::options()
+        .write(true)
+        .open(registry.resolver_file())
+        .unwrap()
+        .set_modified(future)
+        .unwrap();
+    let resolver = registry.resolver().await.unwrap();
+    assert!(resolver.resolve("ipfs://cid").unwrap().is_empty());
+}
+
+#[tokio::test]
+pub async fn test_replace_and_rollback_module() {
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+    registry.create_file_tree().await.unwrap();
+
+    let sample = "sample".parse().unwrap();
+    let stage = |version: &str, programs: &[&str]| {
+        let dir = base_dir.path().join(format!("stage-{version}"));
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        let manifest = InstalledModuleManifest {
+            version: Some(version.into()),
+            manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+            ..Default::default()
+        };
+        std::fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        for program in programs {
+            std::fs::write(dir.join("bin").join(program), version).unwrap();
+        }
+        dir
+    };
+    let run =
+        |program: &str| std::fs::read_to_string(base_dir.path().join("libexec").join(program)).ok();
+
+    let dir = stage("0.1.0", &["asimov-sample-fetcher", "asimov-sample-old"]);
+    registry.add_module(&sample, &dir).await.unwrap();
+    registry.enable_module(&sample).await.unwrap();
+    assert!(matches!(
+        registry.rollback_module(&sample).await,
+        Err(RollbackError::NoPreviousVersion)
+    ));
+
+    let dir = stage("0.2.0", &["asimov-sample-fetcher", "asimov-sample-new"]);
+    registry.replace_module(&sample, &dir).await.unwrap();
+    assert!(!dir.exists());
+    assert_eq!(
+        registry.module_version(&sample).await.unwrap().as_deref(),
+        Some("0.2.0")
+    );
+    assert!(registry.is_module_enabled(&sample).await.unwrap());
+    assert_eq!(run("asimov-sample-fetcher").as_deref(), Some("0.2.0"));
+    assert_eq!(run("asimov-sample-new").as_deref(), Some("0.2.0"));
+    assert_eq!(run("asimov-sample-old"), None);
+    assert!(registry.previous_module_dir(&sample).is_dir());
+
+    registry.rollback_module(&sample).await.unwrap();
+    assert_eq!(
+        registry.module_version(&sample).await.unwrap().as_deref(),
+        Some("0.1.0")
+    );
+    assert!(registry.is_module_enabled(&sample).await.unwrap());
+    assert_eq!(run("asimov-sample-fetcher").as_deref(), Some("0.1.0"));
+    assert_eq!(run("asimov-sample-old").as_deref(), Some("0.1.0"));
+    assert_eq!(run("asimov-sample-new"), None);
+
+    // rolling back again goes forward to the replacement
+    registry.rollback_module(&sample).await.unwrap();
+    assert_eq!(
+        registry.module_version(&sample).await.unwrap().as_deref(),
+        Some("0.2.0")
+    );
+
+    registry.remove_module(&sample).await.unwrap();
+    assert!(!registry.previous_module_dir(&sample).exists());
+}
+
+#[tokio::test]
+pub async fn test_gc() {
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+    registry.create_file_tree().await.unwrap();
+
+    let add = async |name: &str, requires: &[&str], dependency: bool| {
+        let dir = base_dir.path().join(format!("stage-{name}"));
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        let mut manifest = InstalledModuleManifest {
+            dependency,
+            ..Default::default()
+        };
+        manifest.manifest.name = name.into();
+        manifest.manifest.requires.modules = requires.iter().map(|&r| r.into()).collect();
+        std::fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("bin").join(format!("asimov-{name}")), name).unwrap();
+        registry
+            .add_module(&name.parse().unwrap(), &dir)
+            .await
+            .unwrap();
+    };
+    add("app", &["http"], false).await;
+    add("http", &["json"], true).await;
+    add("json", &[], true).await;
+    add("orphan", &["leaf"], true).await;
+    add("leaf", &[], true).await;
+    registry
+        .enable_module(&"orphan".parse().unwrap())
+        .await
+        .unwrap();
+
+    let libexec = base_dir.path().join("libexec");
+    std::os::unix::fs::symlink(base_dir.path().join("missing"), libexec.join("asimov-gone"))
+        .unwrap();
+    let work_dir = base_dir.path().join("modules/.app-a1B2c3");
+    std::fs::create_dir(&work_dir).unwrap();
+    std::fs::write(work_dir.join("asset"), [0; 100]).unwrap();
+    let download_dir = base_dir.path().join("modules/.downloads/app-1.0.0");
+    std::fs::create_dir_all(&download_dir).unwrap();
+
+    // recent work dirs are kept, and nothing is removed in a dry run
+    let options = GcOptions::builder().dry_run(true).build();
+    let report = registry.gc(&options).await.unwrap();
+    let names: Vec<&str> = report.modules.iter().map(|m| m.as_str()).collect();
+    assert_eq!(names, ["leaf", "orphan"]);
+    assert_eq!(report.links, [libexec.join("asimov-gone")]);
+    assert!(report.work_dirs.is_empty());
+    assert!(registry.module_dir(&"orphan".parse().unwrap()).exists());
+
+    let options = GcOptions::builder().stale_after(Duration::ZERO).build();
+    let report = registry.gc(&options).await.unwrap();
+    assert_eq!(report.modules.len(), 2);
+    assert_eq!(report.work_dirs.len(), 2);
+    assert!(report.reclaimed >= 100);
+    assert!(!work_dir.exists() && !download_dir.exists());
+    assert!(!libexec.join("asimov-gone").exists());
+    assert!(!libexec.join("asimov-orphan").exists());
+    assert!(libexec.join("asimov-json").exists());
+    assert!(!base_dir.path().join("modules/enabled/orphan").exists());
+
+    let installed = registry.installed_modules().await.unwrap();
+    assert_eq!(installed.len(), 3);
+    assert_eq!(registry.gc(&options).await.unwrap(), GcReport::default());
+}
+
+#[tokio::test]
+pub async fn test_check_and_repair() {
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+    registry.create_file_tree().await.unwrap();
+
+    let installed = base_dir.path().join("modules/installed");
+    let enabled = base_dir.path().join("modules/enabled");
+    let libexec = base_dir.path().join("libexec");
+
+    // a module with a program that isn't executable nor linked
+    let stage = base_dir.path().join("stage");
+    std::fs::create_dir_all(stage.join("bin")).unwrap();
+    std::fs::write(stage.join("manifest.json"), SAMPLE_MANIFEST).unwrap();
+    std::fs::write(stage.join("bin/asimov-ipfs-fetcher"), "").unwrap();
+    let ipfs = "ipfs".parse().unwrap();
+    registry.add_module(&ipfs, &stage).await.unwrap();
+    std::fs::remove_file(libexec.join("asimov-ipfs-fetcher")).unwrap();
+
+    // a module lacking its program, and one with a corrupt manifest
+    let mut manifest: InstalledModuleManifest = serde_json::from_str(SAMPLE_MANIFEST).unwrap();
+    manifest.manifest.name = "hollow".into();
+    std::fs::create_dir(installed.join("hollow")).unwrap();
+    std::fs::write(
+        installed.join("hollow/manifest.json"),
+        serde_json::to_vec(&manifest).unwrap(),
+    )
+    .unwrap();
+    std::fs::create_dir(installed.join("corrupt")).unwrap();
+    std::fs::write(installed.join("corrupt/manifest.json"), "{").unwrap();
+    // whose linked program is still its own
+    std::fs::create_dir(installed.join("corrupt/bin")).unwrap();
+    std::fs::write(installed.join("corrupt/bin/asimov-corrupt-fetcher"), "").unwrap();
+    std::os::unix::fs::symlink(
+        installed.join("corrupt/bin/asimov-corrupt-fetcher"),
+        libexec.join("asimov-corrupt-fetcher"),
+    )
+    .unwrap();
+    std::fs::create_dir(installed.join("Not_A_Module")).unwrap();
+
+    std::fs::write(installed.join("legacy.yaml"), LEGACY_SAMPLE_MANIFEST).unwrap();
+    std::os::unix::fs::symlink("../installed/gone", enabled.join("gone")).unwrap();
+    std::fs::write(libexec.join("asimov-stray"), "").unwrap();
+    std::os::unix::fs::symlink(
+        installed.join("gone/bin/asimov-gone"),
+        libexec.join("asimov-gone"),
+    )
+    .unwrap();
+
+    let report = registry.check(&CheckOptions::default()).await.unwrap();
+    assert!(!report.is_healthy());
+    assert!(report.issues.iter().all(|issue| !issue.repaired));
+    let problems: Vec<Problem> = report.issues.into_iter().map(|i| i.problem).collect();
+    let hollow = "hollow".parse().unwrap();
+    let fetcher = String::from("asimov-ipfs-fetcher");
+    for problem in [
+        Problem::InvalidModuleName {
+            path: installed.join("Not_A_Module"),
+        },
+        Problem::LegacyManifest {
+            path: installed.join("legacy.yaml"),
+        },
+        Problem::BrokenEnableLink {
+            path: enabled.join("gone"),
+        },
+        Problem::MissingProgram {
+            module: hollow,
+            program: fetcher.clone(),
+        },
+        Problem::NotExecutable {
+            module: ipfs.clone(),
+            program: fetcher.clone(),
+        },
+        Problem::UnlinkedProgram {
+            module: ipfs.clone(),
+            program: fetcher.clone(),
+        },
+        Problem::OrphanedBinary {
+            path: libexec.join("asimov-gone"),
+        },
+        Problem::OrphanedBinary {
+            path: libexec.join("asimov-stray"),
+        },
+    ] {
+        assert!(problems.contains(&problem), "{problem}");
+    }
+    assert!(problems.iter().any(|problem| matches!(
+        problem,
+        Problem::UnreadableManifest { path, .. } if *path == installed.join("corrupt/manifest.json")
+    )));
+    assert_eq!(problems.len(), 9);
+
+    let report = registry
+        .check(&CheckOptions::builder().repair(true).build())
+        .await
+        .unwrap();
+    let unrepaired = report.issues.iter().filter(|i| !i.repaired).count();
+    // the migrated legacy manifest lists a program that isn't there either
+    assert_eq!(unrepaired, 5);
+    assert!(installed.join("legacy/manifest.json").exists());
+    assert!(!enabled.join("gone").exists());
+    // only links are removed, not files that may have been put there by hand
+    assert!(!libexec.join("asimov-gone").is_symlink());
+    assert!(libexec.join("asimov-stray").exists());
+    assert!(libexec.join("asimov-ipfs-fetcher").exists());
+    assert!(libexec.join("asimov-corrupt-fetcher").exists());
+
+    // only what takes a reinstall is left
+    let report = registry.check(&CheckOptions::default()).await.unwrap();
+    let problems: Vec<Problem> = report.issues.into_iter().map(|i| i.problem).collect();
+    assert!(matches!(
+        problems.as_slice(),
+        [
+            Problem::InvalidModuleName { .. },
+            Problem::UnreadableManifest { .. },
+            Problem::MissingProgram { .. },
+            Problem::MissingProgram { .. },
+            Problem::OrphanedBinary { .. },
+        ]
+    ));
+}
+
+#[tokio::test]
+pub async fn test_watch() {
+    // a fresh registry, without any of its directories
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+
+    let sample: ModuleName = "sample".parse().unwrap();
+    let stage = |version: &str| {
+        let dir = base_dir.path().join(format!("stage-{version}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = InstalledModuleManifest {
+            version: Some(version.into()),
+            manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+            ..Default::default()
+        };
+        std::fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        dir
+    };
+
+    let mut watcher = registry.watch().await.unwrap();
+    registry.create_file_tree().await.unwrap();
+    let mut next = async || {
+        tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("no event within the timeout")
+    };
+
+    registry.add_module(&sample, stage("0.1.0")).await.unwrap();
+    assert_eq!(next().await, ModuleEvent::Installed(sample.clone()));
+    registry.enable_module(&sample).await.unwrap();
+    assert_eq!(next().await, ModuleEvent::Enabled(sample.clone()));
+    registry
+        .replace_module(&sample, stage("0.2.0"))
+        .await
+        .unwrap();
+    assert_eq!(next().await, ModuleEvent::Upgraded(sample.clone()));
+    registry.remove_module(&sample).await.unwrap();
+    registry.disable_module(&sample).await.unwrap();
+    assert_eq!(next().await, ModuleEvent::Disabled(sample.clone()));
+    assert_eq!(next().await, ModuleEvent::Removed(sample.clone()));
+}
+
+#[tokio::test]
+pub async fn test_lock() {
+    let base_dir = tempdir().unwrap();
+    let options = Options::builder()
+        .lock_timeout(Duration::from_millis(300))
+        .build();
+    let registry = Registry::new(base_dir.path(), options);
+    registry.create_file_tree().await.unwrap();
+
+    let stage = base_dir.path().join("stage");
+    std::fs::create_dir(&stage).unwrap();
+    std::fs::write(stage.join("manifest.json"), SAMPLE_MANIFEST).unwrap();
+    let sample = "sample".parse().unwrap();
+    registry.add_module(&sample, &stage).await.unwrap();
+
+    // another process changing the registry
+    let other = std::fs::File::create(registry.lock_file()).unwrap();
+    other.try_lock().unwrap();
+    std::fs::write(registry.lock_file(), "4242 asimov\n").unwrap();
+    let Err(EnableError::Lock(LockError::Timeout { holder, .. })) =
+        registry.enable_module(&sample).await
+    else {
+        panic!("expected the lock to time out");
+    };
+    assert_eq!(
+        holder,
+        Some(LockHolder {
+            pid: 4242,
+            program: Some("asimov".into())
+        })
+    );
+    assert!(registry.installed_modules().await.is_err());
+
+    // another process reading the registry
+    other.unlock().unwrap();
+    other.try_lock_shared().unwrap();
+    assert_eq!(registry.installed_modules().await.unwrap().len(), 1);
+    assert!(registry.enable_module(&sample).await.is_err());
+
+    // both processes reading the registry, and then both changing it
+    let lock = registry.lock(LockMode::Shared).await.unwrap();
+    let Err(EnableError::Lock(LockError::Timeout { holder, .. })) =
+        registry.enable_module(&sample).await
+    else {
+        panic!("expected the lock to time out");
+    };
+    assert_eq!(holder.map(|holder| holder.pid), Some(std::process::id()));
+    // the shared lock is kept while waiting, so the other process waits too
+    assert!(other.try_lock().is_err());
+    drop(lock);
+    other.try_lock().unwrap();
+
+    // exclusive locks are taken again within a process
+    other.unlock().unwrap();
+    let lock = registry.lock(LockMode::Exclusive).await.unwrap();
+    registry.enable_module(&sample).await.unwrap();
+    assert_eq!(registry.enabled_modules().await.unwrap().len(), 1);
+    assert!(other.try_lock_shared().is_err());
+    drop(lock);
+    other.try_lock().unwrap();
+}
+
+#[tokio::test]
+pub async fn test_layered_registry() {
+    let base_dir = tempdir().unwrap();
+    let scope = |name: &str| {
+        let registry = Registry::new(base_dir.path().join(name), Default::default());
+        std::fs::create_dir_all(registry.install_dir()).unwrap();
+        std::fs::create_dir_all(base_dir.path().join(name).join("modules/enabled")).unwrap();
+        std::fs::create_dir_all(base_dir.path().join(name).join("libexec")).unwrap();
+        registry
+    };
+    let add = async |registry: &Registry, name: &str, version: &str| {
+        let dir = base_dir.path().join(format!("stage-{name}-{version}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut manifest = InstalledModuleManifest {
+            version: Some(version.into()),
+            manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+            ..Default::default()
+        };
+        manifest.manifest.name = name.into();
+        std::fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        let name = name.parse().unwrap();
+        registry.add_module(&name, &dir).await.unwrap();
+        name
+    };
+
+    let (system, user, project) = (scope("system"), scope("user"), scope("project"));
+    let ipfs = add(&system, "ipfs", "1.0.0").await;
+    system.enable_module(&ipfs).await.unwrap();
+    let http = add(&user, "http", "1.0.0").await;
+    let layered = LayeredRegistry::new([
+        (Scope::Project, project.clone()),
+        (Scope::System, system.clone()),
+        (Scope::User, user.clone()),
+    ]);
+    assert_eq!(
+        layered.scopes().collect::<Vec<_>>(),
+        [Scope::System, Scope::User, Scope::Project]
+    );
+
+    // modules come from the highest scope that installs them
+    assert_eq!(layered.module_scope(&ipfs).await, Some(Scope::System));
+    add(&user, "ipfs", "2.0.0").await;
+    let module = layered.read_manifest(&ipfs).await.unwrap();
+    assert_eq!(module.scope, Scope::User);
+    assert_eq!(module.manifest.version.as_deref(), Some("2.0.0"));
+    let installed = layered.installed_modules().await.unwrap();
+    let installed: Vec<_> = installed
+        .iter()
+        .map(|m| (m.manifest.manifest.name.as_str(), m.scope))
+        .collect();
+    assert_eq!(installed, [("http", Scope::User), ("ipfs", Scope::User)]);
+
+    // the highest scope to enable or disable a module decides
+    assert_eq!(layered.enabled_scope(&ipfs).await, Some(Scope::System));
+    layered.disable_module(&ipfs, Scope::Project).await.unwrap();
+    assert!(!layered.is_module_enabled(&ipfs).await);
+    assert!(system.is_module_enabled(&ipfs).await.unwrap());
+    layered.enable_module(&http, Scope::Project).await.unwrap();
+    assert_eq!(layered.enabled_scope(&http).await, Some(Scope::Project));
+    let enabled = layered.enabled_modules().await.unwrap();
+    assert_eq!(enabled.len(), 1);
+    assert_eq!(enabled[0].manifest.manifest.name, "http");
+    assert_eq!(enabled[0].scope, Scope::User);
+
+    layered.enable_module(&ipfs, Scope::Project).await.unwrap();
+    assert_eq!(layered.enabled_scope(&ipfs).await, Some(Scope::Project));
+    assert!(matches!(
+        layered.enable_module(&ipfs, Scope::System).await,
+        Err(ScopeError::ReadOnly(Scope::System))
+    ));
+    assert!(matches!(
+        layered
+            .enable_module(&"missing".parse().unwrap(), Scope::User)
+            .await,
+        Err(ScopeError::NotInstalled)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_pins() {
+    let base_dir = tempdir().unwrap();
+    let registry = Registry::new(base_dir.path(), Default::default());
+    registry.create_file_tree().await.unwrap();
+    let sample: ModuleName = "sample".parse().unwrap();
+
+    let pin: Pin = "^1.2".parse().unwrap();
+    assert!(matches!(
+        registry.pin_module(&sample, pin.clone()).await,
+        Err(PinError::NotInstalled)
+    ));
+
+    let dir = base_dir.path().join("stage");
+    std::fs::create_dir_all(&dir).unwrap();
+    let manifest = InstalledModuleManifest {
+        version: Some("1.2.0".into()),
+        manifest: serde_json::from_str(SAMPLE_MANIFEST).unwrap(),
+        ..Default::default()
+    };
+    std::fs::write(
+        dir.join("manifest.json"),
+        serde_json::to_vec(&manifest).unwrap(),
+    )
+    .unwrap();
+    registry.add_module(&sample, &dir).await.unwrap();
+
+    registry.pin_module(&sample, pin.clone()).await.unwrap();
+    assert_eq!(registry.module_pin(&sample).await.unwrap(), Some(pin));
+    let content = std::fs::read_to_string(registry.pins_file()).unwrap();
+    assert!(content.contains(r#""sample": "^1.2""#));
+
+    // a pin is replaced rather than added to
+    let pin: Pin = "1.2.0".parse().unwrap();
+    registry.pin_module(&sample, pin.clone()).await.unwrap();
+    let pins = registry.pins().await.unwrap();
+    assert_eq!(pins.len(), 1);
+    assert_eq!(pins[&sample], pin);
+
+    assert!(registry.unpin_module(&sample).await.unwrap());
+    assert!(!registry.unpin_module(&sample).await.unwrap());
+    assert_eq!(registry.module_pin(&sample).await.unwrap(), None);
+}
@@ -5,7 +5,7 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use asimov_module::{ModuleName, resolve::Resolver};
+use asimov_module::{ModuleName, resolve::SyncResolver};
 use asimov_registry::Registry;
 use asimov_runner::GraphOutput;
 use jiff::{Span, Timestamp, ToSpan};
@@ -45,7 +45,7 @@ pub struct Snapshotter<S> {
     storage: S,
     options: Options,
 
-    cached_resolver: Option<Resolver>,
+    cached_resolver: Option<SyncResolver>,
 }
 
 impl<S> Snapshotter<S> {
@@ -57,6 +57,12 @@ impl<S> Snapshotter<S> {
             cached_resolver: None,
         }
     }
+
+    /// Drops the cached resolver, to be rebuilt for the next snapshot, such
+    /// as when [`Registry::watch`] reports a module being enabled or disabled.
+    pub fn invalidate_resolver(&mut self) {
+        self.cached_resolver = None;
+    }
 }
 
 impl<S: crate::storage::Storage> Snapshotter<S> {
@@ -64,42 +70,40 @@ impl<S: crate::storage::Storage> Snapshotter<S> {
     #[tracing::instrument(skip(self), fields(url = url.as_ref()))]
     pub async fn snapshot(&mut self, url: impl AsRef<str>) -> Result<Snapshot> {
         if self.cached_resolver.is_none() {
-            let modules = self
-                .registry
-                .enabled_modules()
-                .await
-                .map_err(io::Error::other)?
-                .into_iter()
-                .map(|enabled| enabled.manifest)
-                .filter(|manifest| {
-                    manifest
-                        .provides
-                        .programs
-                        .iter()
-                        .any(|p| p.ends_with("-fetcher") || p.ends_with("-cataloger"))
-                });
-            let resolver = Resolver::try_from_iter(modules).map_err(io::Error::other)?;
+            let resolver = self.registry.resolver().await.map_err(io::Error::other)?;
             self.cached_resolver = Some(resolver);
         }
         let resolver = self.cached_resolver.as_ref().unwrap();
 
-        let module = resolver
+        let modules = resolver
             .resolve(url.as_ref())
-            .map_err(std::io::Error::other)?
-            .first()
-            .cloned()
-            .ok_or_else(|| std::io::Error::other("No module found for creating snapshot"))?;
+            .map_err(std::io::Error::other)?;
 
-        let module_name = ModuleName::try_from(module.name.clone()).map_err(io::Error::other)?;
+        // the most specific module that can take a snapshot
+        let mut programs = None;
+        for module in modules {
+            let module_name =
+                ModuleName::try_from(module.name.clone()).map_err(io::Error::other)?;
 
-        let programs = self
-            .registry
-            .read_manifest(&module_name)
-            .await
-            .map_err(io::Error::other)?
-            .manifest
-            .provides
-            .programs;
+            let module_programs = self
+                .registry
+                .read_manifest(&module_name)
+                .await
+                .map_err(io::Error::other)?
+                .manifest
+                .provides
+                .programs;
+
+            if module_programs
+                .iter()
+                .any(|p| p.ends_with("-fetcher") || p.ends_with("-cataloger"))
+            {
+                programs = Some(module_programs);
+                break;
+            }
+        }
+        let programs = programs
+            .ok_or_else(|| std::io::Error::other("No module found for creating snapshot"))?;
 
         let url = url.as_ref().to_string();
 